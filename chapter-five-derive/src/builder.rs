@@ -0,0 +1,261 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Expr, Fields, Path};
+
+use crate::types::option_inner;
+
+/// `#[derive(Builder)]` 的实现，为 Burger 生成 chapter-one 的 builder.rs 中手写的那些代码：
+/// * `BurgerBuilder`：每个字段对应一个 Option，None 表示还没有设置
+/// * 每个字段一个 `fn field(mut self, value) -> Self` 的链式 setter，`#[builder(into)]` 的字段接受 impl Into<T>，
+///   `Option<T>` 字段的 setter 接受 T
+/// * `build(&self)`：没有设置的字段使用 `#[builder(default)]` 或者 `#[builder(default = 表达式)]` 给出的默认值，
+///   `Option<T>` 字段默认是 None，其它字段是必需的；所有缺少的字段一起报告在 `BurgerBuilderError::MissingFields` 中
+/// * `#[builder(validate = path)]`：构建之后调用 `fn(&Burger) -> Result<(), E>`，E 实现 Display，
+///   失败时返回 `BurgerBuilderError::Invalid`
+///
+/// 和手写的版本一样，build 不消耗 builder，所以字段类型需要实现 Clone
+pub fn expand(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match ast.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &data.fields,
+                    "Builder can only be derived for structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "Builder can only be derived for structs",
+            ));
+        }
+    };
+
+    let mut validate: Option<Path> = None;
+    for attr in ast.attrs.iter().filter(|a| a.path().is_ident("builder")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("validate") {
+                validate = Some(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `validate = path::to::function`"))
+            }
+        })?;
+    }
+
+    let mut errors: Option<syn::Error> = None;
+    let mut builder_fields = Vec::new();
+    let mut setters = Vec::new();
+    let mut builds = Vec::new();
+    let mut idents = Vec::new();
+    for field in fields {
+        let ident = field
+            .ident
+            .as_ref()
+            .expect("Named fields have an identifier");
+        let ty = &field.ty;
+        let attributes = match parse_field_attributes(field) {
+            Ok(attributes) => attributes,
+            Err(error) => {
+                match errors {
+                    Some(ref mut errors) => errors.combine(error),
+                    None => errors = Some(error),
+                }
+                continue;
+            }
+        };
+
+        // Option<T> 字段的 setter 接受 T，builder 中仍然只包一层 Option
+        let optional = option_inner(ty);
+        let (stored, value) = match optional {
+            Some(inner) => (
+                quote! { #inner },
+                quote! { ::core::option::Option::Some(__value) },
+            ),
+            None => (quote! { #ty }, quote! { __value }),
+        };
+        builder_fields.push(quote! { #ident: ::core::option::Option<#stored> });
+
+        let name = ident.to_string().trim_start_matches("r#").to_string();
+        let doc = format!("设置 `{}`", name);
+        let vis = &field.vis;
+        let setter_vis = if matches!(vis, syn::Visibility::Inherited) {
+            &ast.vis
+        } else {
+            vis
+        };
+        setters.push(if attributes.into {
+            quote! {
+                #[doc = #doc]
+                #setter_vis fn #ident(mut self, value: impl ::core::convert::Into<#stored>) -> Self {
+                    self.#ident = ::core::option::Option::Some(::core::convert::Into::into(value));
+                    self
+                }
+            }
+        } else {
+            quote! {
+                #[doc = #doc]
+                #setter_vis fn #ident(mut self, value: #stored) -> Self {
+                    self.#ident = ::core::option::Option::Some(value);
+                    self
+                }
+            }
+        });
+
+        let required = attributes.default.is_none() && optional.is_none();
+        builds.push(if required {
+            quote! {
+                let #ident = match self.#ident {
+                    ::core::option::Option::Some(ref __value) => {
+                        ::core::option::Option::Some(::core::clone::Clone::clone(__value))
+                    }
+                    ::core::option::Option::None => {
+                        __missing.push(#name);
+                        ::core::option::Option::None
+                    }
+                };
+            }
+        } else {
+            let missing = match attributes.default {
+                Some(DefaultValue::Trait) => quote! { ::core::default::Default::default() },
+                Some(DefaultValue::Expr(ref expr)) => quote! { #expr },
+                None => quote! { ::core::option::Option::None },
+            };
+            quote! {
+                let #ident = ::core::option::Option::Some(match self.#ident {
+                    ::core::option::Option::Some(ref __value) => {
+                        let __value = ::core::clone::Clone::clone(__value);
+                        #value
+                    }
+                    ::core::option::Option::None => #missing,
+                });
+            }
+        });
+        idents.push(ident);
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let identifier = &ast.ident;
+    let vis = &ast.vis;
+    let builder = format_ident!("{}Builder", identifier);
+    let error = format_ident!("{}BuilderError", identifier);
+    let (impl_generics, type_generics, where_clause) = ast.generics.split_for_impl();
+    let generics = &ast.generics;
+    let validate = validate.map(|path| {
+        quote! {
+            #path(&__built).map_err(|e| #error::Invalid(::std::string::ToString::to_string(&e)))?;
+        }
+    });
+    let builder_doc = format!(
+        "[`{}`] 的 builder，由 `#[derive(Builder)]` 生成",
+        identifier
+    );
+    let error_doc = format!("[`{}::build`] 的错误", builder);
+
+    Ok(quote! {
+        #[doc = #builder_doc]
+        #[derive(Clone)]
+        #vis struct #builder #generics #where_clause {
+            #(#builder_fields,)*
+        }
+
+        #[doc = #error_doc]
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        #vis enum #error {
+            /// 没有设置的必需字段，按声明的顺序
+            MissingFields(::std::vec::Vec<&'static str>),
+            /// validate 返回的错误信息
+            Invalid(::std::string::String),
+        }
+
+        impl ::std::fmt::Display for #error {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter<'_>) -> ::std::fmt::Result {
+                match *self {
+                    Self::MissingFields(ref fields) => {
+                        ::core::write!(f, "Missing required field(s): {}", fields.join(", "))
+                    }
+                    Self::Invalid(ref message) => ::core::write!(f, "{}", message),
+                }
+            }
+        }
+
+        impl ::std::error::Error for #error {}
+
+        impl #impl_generics ::core::default::Default for #builder #type_generics #where_clause {
+            fn default() -> Self {
+                Self {
+                    #(#idents: ::core::option::Option::None,)*
+                }
+            }
+        }
+
+        impl #impl_generics #builder #type_generics #where_clause {
+            /// 所有字段都没有设置的 builder
+            #vis fn new() -> Self {
+                ::core::default::Default::default()
+            }
+
+            #(#setters)*
+
+            /// 构建对象，builder 可以继续使用
+            #vis fn build(&self) -> ::core::result::Result<#identifier #type_generics, #error> {
+                let mut __missing: ::std::vec::Vec<&'static str> = ::std::vec::Vec::new();
+                #(#builds)*
+                if !__missing.is_empty() {
+                    return ::core::result::Result::Err(#error::MissingFields(__missing));
+                }
+                let __built = #identifier {
+                    #(#idents: #idents.expect("Every missing field was reported"),)*
+                };
+                #validate
+                ::core::result::Result::Ok(__built)
+            }
+        }
+
+        impl #impl_generics #identifier #type_generics #where_clause {
+            #vis fn builder() -> #builder #type_generics {
+                #builder::new()
+            }
+        }
+    })
+}
+
+enum DefaultValue {
+    /// `#[builder(default)]`
+    Trait,
+    /// `#[builder(default = 表达式)]`
+    Expr(Expr),
+}
+
+struct FieldAttributes {
+    default: Option<DefaultValue>,
+    into: bool,
+}
+
+fn parse_field_attributes(field: &syn::Field) -> syn::Result<FieldAttributes> {
+    let mut attributes = FieldAttributes {
+        default: None,
+        into: false,
+    };
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("builder")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("default") {
+                attributes.default = Some(if meta.input.peek(syn::Token![=]) {
+                    DefaultValue::Expr(meta.value()?.parse()?)
+                } else {
+                    DefaultValue::Trait
+                });
+            } else if meta.path.is_ident("into") {
+                attributes.into = true;
+            } else {
+                return Err(meta.error("expected `default`, `default = ...` or `into`"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(attributes)
+}
@@ -0,0 +1,220 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Expr, Fields, Ident, Lit, LitStr, Type};
+
+use crate::types::{is_bool, option_inner};
+
+/// `#[derive(FromEnv)]` 的实现，生成 `impl ::chapter_one::from_env::FromEnv`
+///
+/// 每个字段先被读成一个 Option 局部变量，出错时把错误追加到 errors 中并得到 None，
+/// 所有字段都读完之后才用 `?` 组装结构体，所以一次调用能收集到所有字段的错误
+///
+/// 属性写错时返回 syn::Error 而不是 panic!，编译器会把错误标在出错的属性上
+pub fn expand(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    let fields = match ast.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    &ast.ident,
+                    "FromEnv can only be derived for structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "FromEnv can only be derived for structs",
+            ));
+        }
+    };
+
+    let mut prefix = None;
+    for attr in ast.attrs.iter().filter(|a| a.path().is_ident("env")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("prefix") {
+                prefix = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `prefix = \"...\"`"))
+            }
+        })?;
+    }
+    let prefix = prefix.map(|p| p.value()).unwrap_or_default();
+
+    let mut bindings = Vec::new();
+    let mut reads = Vec::new();
+    let mut names = Vec::new();
+    for field in fields {
+        let ident = field
+            .ident
+            .as_ref()
+            .expect("Named fields have an identifier");
+        let binding = format_ident!("__field_{}", ident);
+        let read = read_field(ident, &field.ty, parse_field_attributes(field)?);
+        reads.push(quote! { let #binding = #read; });
+        bindings.push(binding);
+        names.push(ident);
+    }
+
+    let identifier = &ast.ident;
+    let (impl_generics, type_generics, where_clause) = ast.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::chapter_one::from_env::FromEnv for #identifier #type_generics #where_clause {
+            const PREFIX: &'static str = #prefix;
+
+            fn from_lookup(
+                __prefix: &str,
+                __lookup: &dyn Fn(&str) -> ::core::option::Option<::std::string::String>,
+                __errors: &mut ::std::vec::Vec<::chapter_one::from_env::EnvError>,
+            ) -> ::core::option::Option<Self> {
+                #(#reads)*
+                ::core::option::Option::Some(Self {
+                    #(#names: #bindings?,)*
+                })
+            }
+        }
+    })
+}
+
+enum DefaultValue {
+    None,
+    /// `#[env(default)]`
+    Trait,
+    /// `#[env(default = "8080")]`，和变量的值一样被解析
+    Str(LitStr),
+    /// `#[env(default = 8080)]`
+    Expr(Expr),
+}
+
+struct FieldAttributes {
+    default: DefaultValue,
+    name: Option<LitStr>,
+    /// 嵌套结构体的前缀，None 表示这个字段不是嵌套的
+    nested: Option<String>,
+}
+
+fn parse_field_attributes(field: &syn::Field) -> syn::Result<FieldAttributes> {
+    let ident = field
+        .ident
+        .as_ref()
+        .expect("Named fields have an identifier");
+    let mut attributes = FieldAttributes {
+        default: DefaultValue::None,
+        name: None,
+        nested: None,
+    };
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("env")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("default") {
+                attributes.default = if meta.input.is_empty() || meta.input.peek(syn::Token![,]) {
+                    DefaultValue::Trait
+                } else {
+                    match meta.value()?.parse::<Expr>()? {
+                        Expr::Lit(syn::ExprLit {
+                            lit: Lit::Str(s), ..
+                        }) => DefaultValue::Str(s),
+                        expr => DefaultValue::Expr(expr),
+                    }
+                };
+            } else if meta.path.is_ident("name") {
+                attributes.name = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("nested") {
+                attributes.nested = Some(format!("{}_", upper(ident)));
+            } else if meta.path.is_ident("prefix") {
+                attributes.nested = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else {
+                return Err(meta.error(
+                    "expected `default`, `default = ...`, `name = \"...\"`, `nested` or `prefix = \"...\"`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+    if attributes.nested.is_some()
+        && (attributes.name.is_some() || !matches!(attributes.default, DefaultValue::None))
+    {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "a nested field cannot have a `name` or a `default`",
+        ));
+    }
+    Ok(attributes)
+}
+
+/// 生成读取一个字段的表达式，类型是 Option<字段类型>
+fn read_field(ident: &Ident, ty: &Type, attributes: FieldAttributes) -> TokenStream {
+    if let Some(prefix) = attributes.nested {
+        return quote! {
+            <#ty as ::chapter_one::from_env::FromEnv>::from_lookup(
+                &::std::format!("{}{}", __prefix, #prefix),
+                __lookup,
+                __errors,
+            )
+        };
+    }
+
+    let name = match attributes.name {
+        Some(name) => quote! { ::std::string::String::from(#name) },
+        None => {
+            let upper = upper(ident);
+            quote! { ::std::format!("{}{}", __prefix, #upper) }
+        }
+    };
+    let optional = option_inner(ty);
+    let inner = optional.unwrap_or(ty);
+    let parse = if is_bool(inner) {
+        quote! { ::chapter_one::from_env::parse_bool }
+    } else {
+        quote! { ::chapter_one::from_env::parse::<#inner> }
+    };
+    let wrap = |value: TokenStream| match optional {
+        Some(_) => quote! { ::core::option::Option::Some(#value) },
+        None => value,
+    };
+    let parsed = |raw: TokenStream| {
+        let value = wrap(quote! { __value });
+        quote! {
+            match #parse(&__name, #raw) {
+                ::core::result::Result::Ok(__value) => ::core::option::Option::Some(#value),
+                ::core::result::Result::Err(__error) => {
+                    __errors.push(__error);
+                    ::core::option::Option::None
+                }
+            }
+        }
+    };
+
+    let from_variable = parsed(quote! { &__raw });
+    let missing = match attributes.default {
+        DefaultValue::Str(default) => parsed(quote! { #default }),
+        DefaultValue::Expr(default) => quote! { ::core::option::Option::Some(#default) },
+        DefaultValue::Trait => {
+            quote! { ::core::option::Option::Some(::core::default::Default::default()) }
+        }
+        DefaultValue::None if optional.is_some() => {
+            quote! { ::core::option::Option::Some(::core::option::Option::None) }
+        }
+        DefaultValue::None => quote! {
+            {
+                __errors.push(::chapter_one::from_env::EnvError::Missing(__name));
+                ::core::option::Option::None
+            }
+        },
+    };
+    quote! {
+        {
+            let __name = #name;
+            match __lookup(&__name) {
+                ::core::option::Option::Some(__raw) => #from_variable,
+                ::core::option::Option::None => #missing,
+            }
+        }
+    }
+}
+
+/// 字段名对应的变量名，r#type 变成 TYPE
+fn upper(ident: &Ident) -> String {
+    let name = ident.to_string();
+    name.strip_prefix("r#").unwrap_or(&name).to_uppercase()
+}
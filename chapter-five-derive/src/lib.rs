@@ -2,6 +2,11 @@ use proc_macro::TokenStream;
 use quote::quote;
 use syn::{Expr, Lit};
 
+mod builder;
+mod from_env;
+mod parser;
+mod types;
+
 /// 在 proc_macro crate中，只有注解为 proc_macro_derive 的函数可以是 pub 的
 /// 这个属性需要指定 derive 的名字，和它允许什么属性，因为这个方法直接挂在（hook）编译器上，所以它的输入输出都是 TokenStream
 /// 过程宏不改变代码，只是分析它然后增加一些代码
@@ -69,3 +74,32 @@ fn get_name_attribute(ast: &syn::DeriveInput) -> Option<String> {
         None
     }
 }
+
+/// 从环境变量中读取一个结构体，见 chapter_one::from_env 中的说明
+///
+/// 和上面的 HelloWorld 不同，这里用 parse_macro_input! 解析输入，属性写错时返回带位置信息的 syn::Error
+#[proc_macro_derive(FromEnv, attributes(env))]
+pub fn from_env(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    from_env::expand(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// 从命令行参数中解析一个结构体，见 chapter_one::cli::Parser
+#[proc_macro_derive(Parser, attributes(arg, command))]
+pub fn parser(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    parser::expand(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// 生成 chapter-one 的 builder.rs 中手写的 builder，见 builder::expand
+#[proc_macro_derive(Builder, attributes(builder))]
+pub fn builder(input: TokenStream) -> TokenStream {
+    let ast = syn::parse_macro_input!(input as syn::DeriveInput);
+    builder::expand(&ast)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
@@ -0,0 +1,383 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{
+    Attribute, Data, DeriveInput, Expr, ExprLit, Fields, Ident, Lit, LitChar, LitStr, Meta, Type,
+};
+
+use crate::types::{is_bool, option_inner, vec_inner};
+
+/// `#[derive(Parser)]` 的实现，生成 `impl ::chapter_one::cli::Parser`
+///
+/// 字段的类型决定参数的种类：
+/// * bool：标志 `--verbose`
+/// * `Option<T>`：可以不给出的选项
+/// * `Vec<T>`：可以重复的选项，或者收集剩下所有位置参数的位置参数
+/// * 其它类型 T：必须给出的选项，除非有默认值
+///
+/// 字段和结构体的文档注释是帮助文本，`#[arg(...)]` 可以指定 short、long、positional、default 和 value_name
+///
+/// 不支持的组合会得到一个指向出错字段或者属性的 syn::Error，例如 bool 类型的位置参数
+pub fn expand(ast: &DeriveInput) -> syn::Result<TokenStream> {
+    // 在区分结构体的种类之前解析，这样没有字段的结构体也能改名，写错的属性也总会被报告
+    let mut command_name = None;
+    for attr in ast.attrs.iter().filter(|a| a.path().is_ident("command")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                command_name = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"`"))
+            }
+        })?;
+    }
+
+    let identifier = &ast.ident;
+    let name = command_name.map_or_else(|| default_name(identifier), |name| quote! { #name });
+
+    let fields = match ast.data {
+        Data::Struct(ref data) => match data.fields {
+            Fields::Named(ref fields) => &fields.named,
+            Fields::Unit => return Ok(unit(ast, name)),
+            Fields::Unnamed(_) => {
+                return Err(syn::Error::new_spanned(
+                    &data.fields,
+                    "Parser cannot be derived for tuple structs, name the fields instead",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &ast.ident,
+                "Parser can only be derived for structs",
+            ));
+        }
+    };
+
+    // 每个字段的错误都被收集起来一起报告，用户不需要一次修一个再重新编译
+    let mut errors: Option<syn::Error> = None;
+    let mut args = Vec::new();
+    let mut reads = Vec::new();
+    let mut shorts: Vec<char> = Vec::new();
+    let mut longs: Vec<String> = Vec::new();
+    let mut positionals: Vec<(&Ident, bool)> = Vec::new();
+    for field in fields {
+        let ident = field
+            .ident
+            .as_ref()
+            .expect("Named fields have an identifier");
+        match check_field(field, ident, &mut shorts, &mut longs, &mut positionals) {
+            Ok((arg, read)) => {
+                args.push(arg);
+                reads.push(read);
+            }
+            Err(error) => match errors {
+                Some(ref mut errors) => errors.combine(error),
+                None => errors = Some(error),
+            },
+        }
+    }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let about = documentation(&ast.attrs);
+    let about = about.map(|about| quote! { .about(#about) });
+    let (impl_generics, type_generics, where_clause) = ast.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::chapter_one::cli::Parser for #identifier #type_generics #where_clause {
+            fn command() -> ::chapter_one::cli::Command {
+                ::chapter_one::cli::Command::new(#name)
+                    #about
+                    #(.arg(#args))*
+            }
+
+            fn from_matches(
+                __matches: &::chapter_one::cli::Matches,
+            ) -> ::chapter_one::cli::Result<Self> {
+                ::core::result::Result::Ok(Self {
+                    #(#reads,)*
+                })
+            }
+        }
+    })
+}
+
+/// 检查一个字段和之前的字段是否冲突，然后生成它的代码
+fn check_field<'a>(
+    field: &'a syn::Field,
+    ident: &'a Ident,
+    shorts: &mut Vec<char>,
+    longs: &mut Vec<String>,
+    positionals: &mut Vec<(&'a Ident, bool)>,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let attributes = parse_field_attributes(field)?;
+    if let Some(ref short) = attributes.short {
+        if short.value() == 'h' {
+            return Err(syn::Error::new(
+                short.span(),
+                "-h is reserved for the generated help",
+            ));
+        }
+        if shorts.contains(&short.value()) {
+            return Err(syn::Error::new(
+                short.span(),
+                format!("-{} is already used by another field", short.value()),
+            ));
+        }
+        shorts.push(short.value());
+    }
+    // 没有写 long 的选项使用 Arg::option 生成的默认值，所以 `max_count` 和 `long = "max-count"` 也会冲突
+    if !attributes.positional {
+        let long = match attributes.long {
+            Some(ref long) => long.value(),
+            None => ident.to_string().trim_start_matches("r#").replace('_', "-"),
+        };
+        // 和 -h 一样，用户的 --help 会替换生成的帮助
+        if long == "help" || longs.contains(&long) {
+            let message = match long.as_str() {
+                "help" => "--help is reserved for the generated help".to_string(),
+                _ => format!("--{} is already used by another field", long),
+            };
+            return Err(match attributes.long {
+                Some(ref explicit) => syn::Error::new_spanned(explicit, message),
+                None => syn::Error::new_spanned(ident, message),
+            });
+        }
+        longs.push(long);
+    }
+    let help = documentation(&field.attrs);
+    let tokens = field_tokens(ident, &field.ty, help, &attributes)?;
+    if attributes.positional {
+        if let Some((previous, true)) = positionals.last() {
+            return Err(syn::Error::new_spanned(
+                ident,
+                format!(
+                    "`{}` can never receive a value, the repeated positional `{}` takes all remaining arguments",
+                    ident, previous
+                ),
+            ));
+        }
+        positionals.push((ident, vec_inner(&field.ty).is_some()));
+    }
+    Ok(tokens)
+}
+
+/// 没有字段的结构体只有 --help
+fn unit(ast: &DeriveInput, name: TokenStream) -> TokenStream {
+    let identifier = &ast.ident;
+    let about = documentation(&ast.attrs).map(|about| quote! { .about(#about) });
+    quote! {
+        impl ::chapter_one::cli::Parser for #identifier {
+            fn command() -> ::chapter_one::cli::Command {
+                ::chapter_one::cli::Command::new(#name) #about
+            }
+
+            fn from_matches(
+                _: &::chapter_one::cli::Matches,
+            ) -> ::chapter_one::cli::Result<Self> {
+                ::core::result::Result::Ok(Self)
+            }
+        }
+    }
+}
+
+/// 二进制文件的名字，编译的不是二进制文件时使用结构体的名字
+fn default_name(identifier: &Ident) -> TokenStream {
+    let fallback = kebab(&identifier.to_string());
+    quote! {
+        match ::core::option_env!("CARGO_BIN_NAME") {
+            ::core::option::Option::Some(__name) => __name,
+            ::core::option::Option::None => #fallback,
+        }
+    }
+}
+
+#[derive(Default)]
+struct FieldAttributes {
+    short: Option<LitChar>,
+    long: Option<LitStr>,
+    positional: bool,
+    default: Option<LitStr>,
+    value_name: Option<LitStr>,
+    /// 组合不合法时错误指向的属性
+    attribute: Option<Attribute>,
+}
+
+fn parse_field_attributes(field: &syn::Field) -> syn::Result<FieldAttributes> {
+    let ident = field
+        .ident
+        .as_ref()
+        .expect("Named fields have an identifier");
+    let mut attributes = FieldAttributes::default();
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("arg")) {
+        attributes.attribute.get_or_insert_with(|| attr.clone());
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("short") {
+                // `short` 单独使用时是字段名的第一个字母
+                attributes.short = Some(if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse()?
+                } else {
+                    let first = ident
+                        .to_string()
+                        .trim_start_matches("r#")
+                        .chars()
+                        .next()
+                        .expect("An identifier is not empty");
+                    LitChar::new(first, meta.path.require_ident()?.span())
+                });
+            } else if meta.path.is_ident("long") {
+                attributes.long = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("positional") {
+                attributes.positional = true;
+            } else if meta.path.is_ident("default") {
+                attributes.default = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("value_name") {
+                attributes.value_name = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error(
+                    "expected `short`, `short = 'c'`, `long = \"...\"`, `positional`, `default = \"...\"` or `value_name = \"...\"`",
+                ));
+            }
+            Ok(())
+        })?;
+    }
+    if attributes.positional && (attributes.short.is_some() || attributes.long.is_some()) {
+        return Err(syn::Error::new_spanned(
+            &attributes.attribute,
+            "a positional argument cannot have `short` or `long`",
+        ));
+    }
+    Ok(attributes)
+}
+
+/// 一个字段对应的 Arg 构造表达式，和从 Matches 中读取它的表达式
+fn field_tokens(
+    ident: &Ident,
+    ty: &Type,
+    help: Option<String>,
+    attributes: &FieldAttributes,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let name = ident.to_string().trim_start_matches("r#").to_string();
+    // 错误指向 #[arg(...)]，因为类型本身没有问题
+    let error = |message: &str| match attributes.attribute {
+        Some(ref attribute) => syn::Error::new_spanned(attribute, message),
+        None => syn::Error::new_spanned(ident, message),
+    };
+
+    let (constructor, read) = if is_bool(ty) {
+        if attributes.positional {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "a bool field is a flag and cannot be positional, use String or a type implementing FromStr",
+            ));
+        }
+        if attributes.default.is_some() || attributes.value_name.is_some() {
+            return Err(error(
+                "a flag does not take a value, `default` and `value_name` are not allowed",
+            ));
+        }
+        (
+            quote! { ::chapter_one::cli::Arg::flag(#name) },
+            quote! { #ident: __matches.flag(#name) },
+        )
+    } else if let Some(inner) = option_inner(ty) {
+        if attributes.default.is_some() {
+            return Err(error(
+                "an Option field cannot have a default, use the inner type instead",
+            ));
+        }
+        (
+            kind(&name, attributes.positional),
+            quote! { #ident: __matches.get::<#inner>(#name)? },
+        )
+    } else if let Some(inner) = vec_inner(ty) {
+        if attributes.default.is_some() {
+            return Err(error("a Vec field cannot have a default"));
+        }
+        let constructor = kind(&name, attributes.positional);
+        (
+            quote! { #constructor.multiple(true) },
+            quote! { #ident: __matches.get_all::<#inner>(#name)? },
+        )
+    } else {
+        let constructor = kind(&name, attributes.positional);
+        let constructor = match attributes.default {
+            Some(_) => constructor,
+            None => quote! { #constructor.required(true) },
+        };
+        (
+            constructor,
+            quote! {
+                #ident: __matches
+                    .get::<#ty>(#name)?
+                    .expect("A required argument or one with a default always has a value")
+            },
+        )
+    };
+
+    let short = attributes.short.as_ref().map(|s| quote! { .short(#s) });
+    let long = attributes.long.as_ref().map(|l| quote! { .long(#l) });
+    let default = attributes
+        .default
+        .as_ref()
+        .map(|d| quote! { .default_value(#d) });
+    let value_name = attributes
+        .value_name
+        .as_ref()
+        .map(|v| quote! { .value_name(#v) });
+    let help = help.map(|h| quote! { .help(#h) });
+    Ok((
+        quote! { #constructor #short #long #default #value_name #help },
+        read,
+    ))
+}
+
+fn kind(name: &str, positional: bool) -> TokenStream {
+    if positional {
+        quote! { ::chapter_one::cli::Arg::positional(#name) }
+    } else {
+        quote! { ::chapter_one::cli::Arg::option(#name) }
+    }
+}
+
+/// `///` 注释在编译器看来是 `#[doc = "..."]` 属性，每行一个
+/// 连续的行用空格连接，空行之后的段落被忽略，这样较长的说明可以留在代码中而不出现在帮助里
+fn documentation(attrs: &[Attribute]) -> Option<String> {
+    let mut lines = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path().is_ident("doc")) {
+        let Meta::NameValue(ref meta) = attr.meta else {
+            continue;
+        };
+        let Expr::Lit(ExprLit {
+            lit: Lit::Str(ref line),
+            ..
+        }) = meta.value
+        else {
+            continue;
+        };
+        let line = line.value().trim().to_string();
+        if line.is_empty() {
+            if lines.is_empty() {
+                continue;
+            }
+            break;
+        }
+        lines.push(line);
+    }
+    (!lines.is_empty()).then(|| lines.join(" "))
+}
+
+fn kebab(name: &str) -> String {
+    let mut kebab = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                kebab.push('-');
+            }
+            kebab.extend(c.to_lowercase());
+        } else {
+            kebab.push(c);
+        }
+    }
+    kebab
+}
@@ -0,0 +1,130 @@
+use chapter_five_derive::Builder;
+
+/// chapter-one 的 builder.rs 中，BurgerBuilder 几乎是 Burger 的逐字段复制，再加上每个字段一个 setter
+/// 这些代码完全由 Burger 的定义决定，所以可以交给过程宏生成，我们只需要写出默认值和检查规则
+fn main() {
+    let normal_burger = Burger::builder().name("Classic").build();
+    let cheese_burger = Burger::builder()
+        .name("Cheese")
+        .cheese(true)
+        .sauce("ketchup")
+        .build();
+    let veggie_biamac = Burger::builder()
+        .name("Veggie Biamac")
+        .vegetarian(true)
+        .patty_count(2)
+        .build();
+    for burger in [normal_burger, cheese_burger, veggie_biamac] {
+        match burger {
+            Ok(burger) => burger.print(),
+            Err(e) => println!("Failed to build burger: {}", e),
+        }
+    }
+
+    // 检查无效选项和缺少的字段
+    let invalid_burger = Burger::builder()
+        .name("Impossible")
+        .vegetarian(true)
+        .bacon(true)
+        .build();
+    if let Err(e) = invalid_burger {
+        println!("Failed to build burger: {}", e);
+    }
+    if let Err(e) = BurgerBuilder::new().build() {
+        println!("Failed to build burger: {}", e);
+    }
+}
+
+#[derive(Debug, PartialEq, Builder)]
+#[builder(validate = Burger::check)]
+struct Burger {
+    /// 没有默认值，必须设置
+    #[builder(into)]
+    name: String,
+    #[builder(default = 1)]
+    patty_count: i32,
+    #[builder(default)]
+    vegetarian: bool,
+    #[builder(default)]
+    cheese: bool,
+    #[builder(default)]
+    bacon: bool,
+    #[builder(default = true)]
+    salad: bool,
+    /// Option 字段默认是 None，setter 接受 &str
+    sauce: Option<&'static str>,
+}
+
+impl Burger {
+    fn check(&self) -> Result<(), String> {
+        if self.vegetarian && self.bacon {
+            Err("Sorry, but we don't server vegetarian bacon yet".to_string())
+        } else {
+            Ok(())
+        }
+    }
+
+    fn print(&self) {
+        let pretty_patties = if self.patty_count == 1 {
+            "patty"
+        } else {
+            "patties"
+        };
+        let pretty_bool = |val| if val { "" } else { "no " };
+        let pretty_vegetarian = if self.vegetarian { "vegetarian " } else { "" };
+        println!(
+            "{} is a {}burger with {} {}, {}cheese, {}bacon, {}salad and {} sauce",
+            self.name,
+            pretty_vegetarian,
+            self.patty_count,
+            pretty_patties,
+            pretty_bool(self.cheese),
+            pretty_bool(self.bacon),
+            pretty_bool(self.salad),
+            self.sauce.unwrap_or("no")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_defaults_for_unset_fields() {
+        let builder = Burger::builder().name("Cheese").cheese(true);
+        let burger = builder.build().expect("Failed to build burger");
+        assert_eq!(
+            Burger {
+                name: "Cheese".to_string(),
+                patty_count: 1,
+                vegetarian: false,
+                cheese: true,
+                bacon: false,
+                salad: true,
+                sauce: None,
+            },
+            burger
+        );
+        // build 不消耗 builder
+        assert_eq!(Ok(burger), builder.build());
+    }
+
+    #[test]
+    fn reports_missing_fields_and_validation_errors() {
+        assert_eq!(
+            Err(BurgerBuilderError::MissingFields(vec!["name"])),
+            BurgerBuilder::new().bacon(true).build()
+        );
+        assert_eq!(
+            Err(BurgerBuilderError::Invalid(
+                "Sorry, but we don't server vegetarian bacon yet".to_string()
+            )),
+            Burger::builder()
+                .name("x")
+                .vegetarian(true)
+                .bacon(true)
+                .build()
+        );
+    }
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use chapter_five_derive::Parser;
+use chapter_one::cli::Parser;
+
+/// chapter-one 的 cli_parser.rs 用 Command 和 Arg 手动声明参数，这里用 derive 从结构体生成同样的声明
+///
+/// 试试 `cargo run --bin cli_derive -- --help`，帮助文本来自下面的文档注释
+fn main() {
+    let options = Grep::parse();
+    println!("{:#?}", options);
+}
+
+/// Search files for a pattern
+///
+/// 空行之后的段落不会出现在帮助中
+#[derive(Debug, PartialEq, Parser)]
+struct Grep {
+    /// The regular expression to search for
+    #[arg(positional)]
+    pattern: String,
+    /// Files to search, standard input if none are given
+    #[arg(positional)]
+    files: Vec<PathBuf>,
+    /// Ignore case distinctions
+    #[arg(short)]
+    ignore_case: bool,
+    /// Stop after this many matches
+    #[arg(short = 'm', value_name = "NUM")]
+    max_count: Option<usize>,
+    /// Lines of context to print around each match
+    #[arg(short = 'C', default = "0")]
+    context: usize,
+    /// Only search files matching this glob, can be repeated
+    #[arg(long = "include", value_name = "GLOB")]
+    includes: Vec<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use chapter_one::cli::CliError;
+
+    use super::*;
+
+    #[test]
+    fn fields_follow_their_types() {
+        let grep = Grep::try_parse_from([
+            "-i",
+            "--include=*.rs",
+            "-m",
+            "3",
+            "fn main",
+            "a.rs",
+            "--include",
+            "*.toml",
+            "b.rs",
+        ])
+        .expect("Failed to parse arguments");
+        assert_eq!(
+            Grep {
+                pattern: "fn main".to_string(),
+                files: vec![PathBuf::from("a.rs"), PathBuf::from("b.rs")],
+                ignore_case: true,
+                max_count: Some(3),
+                context: 0,
+                includes: vec!["*.rs".to_string(), "*.toml".to_string()],
+            },
+            grep
+        );
+
+        assert_eq!(
+            Err(CliError::MissingRequired("<PATTERN>".to_string())),
+            Grep::try_parse_from(["-i"])
+        );
+        assert!(matches!(
+            Grep::try_parse_from(["x", "-C", "lots"]),
+            Err(CliError::InvalidValue { ref arg, .. }) if arg == "--context"
+        ));
+    }
+
+    #[test]
+    fn doc_comments_become_help() {
+        let Err(CliError::Help(help)) = Grep::try_parse_from(["--help"]) else {
+            panic!("Expected help");
+        };
+        assert!(help.starts_with(
+            "Search files for a pattern\n\nUsage: cli_derive [OPTIONS] <PATTERN> [FILES]...\n"
+        ));
+        assert!(help.contains("  -m, --max-count <NUM>    Stop after this many matches\n"));
+        assert!(help.contains("Lines of context to print around each match [default: 0]"));
+        assert!(!help.contains("空行"));
+    }
+
+    /// Print the version
+    #[derive(Debug, PartialEq, Parser)]
+    #[command(name = "version")]
+    struct Version;
+
+    #[test]
+    fn unit_structs_can_be_renamed() {
+        assert_eq!(Ok(Version), Version::try_parse_from(Vec::<String>::new()));
+        let Err(CliError::Help(help)) = Version::try_parse_from(["--help"]) else {
+            panic!("Expected help");
+        };
+        assert!(help.starts_with("Print the version\n\nUsage: version"));
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    path::{Path, PathBuf},
+    process,
+};
+
+use chapter_five_derive::FromEnv;
+use chapter_one::{env::Dotenv, from_env::FromEnv};
+
+/// custom_derive.rs 中的 HelloWorld 只是打印结构体的名字，这里的 derive 真正读取字段：
+/// 每个字段对应一个环境变量，类型通过 FromStr 解析，缺失或者无法解析的变量会被一次全部报告
+///
+/// 试试 `APP_PORT=8080 APP_DEBUG=yes APP_TLS_CERT=cert.pem cargo run --bin env_config`，
+/// 或者去掉 APP_PORT、把它改成 abc，看看错误信息
+fn main() {
+    // .env 是可选的，其中的值不会覆盖已经设置的变量
+    if Path::new(".env").exists()
+        && let Err(e) = Dotenv::new().load(".env")
+    {
+        eprintln!("Couldn't load .env: {}", e);
+    }
+
+    match Server::from_env() {
+        Ok(server) => println!("{:#?}", server),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, FromEnv)]
+#[env(prefix = "APP_")]
+struct Server {
+    port: u16,
+    #[env(default = "localhost")]
+    host: String,
+    #[env(default = false)]
+    debug: bool,
+    #[env(default)]
+    workers: usize,
+    /// 不加前缀的变量名
+    #[env(name = "DATABASE_URL")]
+    database_url: Option<String>,
+    /// APP_TLS_CERT 和 APP_TLS_KEY
+    #[env(nested)]
+    tls: Tls,
+}
+
+#[derive(Debug, PartialEq, FromEnv)]
+struct Tls {
+    cert: PathBuf,
+    #[env(default = "key.pem")]
+    key: PathBuf,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use chapter_one::from_env::{EnvError, FromEnvError};
+
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn reads_fields_defaults_and_nested_structs() {
+        let server = Server::from_map(&vars(&[
+            ("APP_PORT", "8080"),
+            ("APP_DEBUG", "on"),
+            ("DATABASE_URL", "postgres://db"),
+            ("APP_TLS_CERT", "cert.pem"),
+        ]))
+        .expect("Failed to read the server configuration");
+        assert_eq!(
+            Server {
+                port: 8080,
+                host: "localhost".to_string(),
+                debug: true,
+                workers: 0,
+                database_url: Some("postgres://db".to_string()),
+                tls: Tls {
+                    cert: PathBuf::from("cert.pem"),
+                    key: PathBuf::from("key.pem"),
+                },
+            },
+            server
+        );
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let Err(FromEnvError(errors)) =
+            Server::from_map(&vars(&[("APP_PORT", "eighty"), ("APP_WORKERS", "-1")]))
+        else {
+            panic!("The configuration should be invalid");
+        };
+        let names: Vec<&str> = errors
+            .iter()
+            .map(|e| match *e {
+                EnvError::Missing(ref name) => name.as_str(),
+                EnvError::Invalid { ref name, .. } => name.as_str(),
+            })
+            .collect();
+        assert_eq!(vec!["APP_PORT", "APP_WORKERS", "APP_TLS_CERT"], names);
+        assert_eq!(EnvError::Missing("APP_TLS_CERT".to_string()), errors[2]);
+    }
+}
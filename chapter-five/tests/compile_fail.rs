@@ -0,0 +1,8 @@
+/// `#[derive(Parser)]` 对不合法的字段报告指向出错位置的错误，这些错误只能通过编译失败来测试
+/// trybuild 编译 tests/ui 中的每个文件，检查编译失败并且错误信息和旁边的 .stderr 文件一致
+/// 修改错误信息之后用 `TRYBUILD=overwrite cargo test --test compile_fail` 重新生成 .stderr
+#[test]
+fn parser_derive() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,10 @@
+use chapter_five_derive::Parser;
+
+#[derive(Parser)]
+struct Options {
+    max_count: Option<usize>,
+    #[arg(long = "max-count")]
+    limit: Option<usize>,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use chapter_five_derive::Parser;
+
+#[derive(Parser)]
+struct Options {
+    #[arg(short)]
+    verbose: bool,
+    #[arg(short = 'v')]
+    version: bool,
+}
+
+fn main() {}
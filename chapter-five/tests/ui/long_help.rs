@@ -0,0 +1,9 @@
+use chapter_five_derive::Parser;
+
+#[derive(Parser)]
+struct Options {
+    #[arg(long = "help")]
+    manual: bool,
+}
+
+fn main() {}
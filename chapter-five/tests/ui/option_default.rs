@@ -0,0 +1,9 @@
+use chapter_five_derive::Parser;
+
+#[derive(Parser)]
+struct Options {
+    #[arg(default = "10")]
+    max_count: Option<usize>,
+}
+
+fn main() {}
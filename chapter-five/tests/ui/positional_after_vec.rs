@@ -0,0 +1,13 @@
+use std::path::PathBuf;
+
+use chapter_five_derive::Parser;
+
+#[derive(Parser)]
+struct Options {
+    #[arg(positional)]
+    files: Vec<PathBuf>,
+    #[arg(positional)]
+    output: PathBuf,
+}
+
+fn main() {}
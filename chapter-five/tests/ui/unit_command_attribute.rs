@@ -0,0 +1,7 @@
+use chapter_five_derive::Parser;
+
+#[derive(Parser)]
+#[command(title = "version")]
+struct Version;
+
+fn main() {}
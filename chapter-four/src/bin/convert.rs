@@ -0,0 +1,55 @@
+use std::{env, fs, process};
+
+use chapter_four::convert::{Format, convert};
+
+/// 得益于Serde，CSV、JSON和TOML的读写API几乎一样，但它们能表示的数据并不一样
+/// * JSON可以任意嵌套，有null
+/// * TOML的文档必须是表，没有null，但是有日期时间类型
+/// * CSV只是一张由字符串组成的二维表格
+///
+/// 所以格式之间的转换不一定成功，例如 pet_owner.json 中的 pets 是一个嵌套数组，无法放进CSV的单元格中
+///
+/// 用法：
+/// * convert INPUT OUTPUT，格式由扩展名决定，例如 `convert solar_system_compared_to_earth.csv planets.json`
+/// * convert INPUT FORMAT，结果打印到标准输出，例如 `convert preferences.toml json`
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} INPUT (OUTPUT | csv | json | toml)", args[0]);
+        process::exit(2);
+    }
+    let (input_path, target) = (&args[1], &args[2]);
+
+    let from = Format::from_path(input_path).unwrap_or_else(|| {
+        eprintln!(
+            "Cannot tell the format of '{}' from its extension",
+            input_path
+        );
+        process::exit(2)
+    });
+    let (to, output_path) = match target.parse::<Format>() {
+        Ok(format) => (format, None),
+        Err(_) => match Format::from_path(target) {
+            Some(format) => (format, Some(target)),
+            None => {
+                eprintln!("Cannot tell the format of '{}' from its extension", target);
+                process::exit(2)
+            }
+        },
+    };
+
+    let input = fs::read_to_string(input_path).expect("Failed to read input file");
+    match convert(&input, from, to) {
+        Ok(output) => match output_path {
+            Some(path) => {
+                fs::write(path, output).expect("Failed to write output file");
+                println!("Converted {} ({}) into {} ({})", input_path, from, path, to)
+            }
+            None => print!("{}", output),
+        },
+        Err(e) => {
+            eprintln!("Failed to convert {} into {}: {}", from, to, e);
+            process::exit(1)
+        }
+    }
+}
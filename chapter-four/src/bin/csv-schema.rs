@@ -0,0 +1,43 @@
+use std::{env, fs::File, io::BufReader, process};
+
+use chapter_four::schema::infer_schema;
+
+/// csv crate把每一行都当作字符串，`record.get(0)` 拿到的永远是 &str，类型需要我们自己决定
+/// 对于陌生的文件，可以先读取一部分样本，根据每一列能被解析成什么来推断类型，然后再按推断的类型读取整份文件
+///
+/// 推断只能基于样本，样本之外的行仍然可能不符合，所以 typed_rows 返回的每一行都是 Result
+///
+/// 用法：csv-schema [FILE] [SAMPLE_ROWS]，默认读取 csv.rs 生成的 solar_system_compared_to_earth.csv
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = args
+        .next()
+        .unwrap_or_else(|| "solar_system_compared_to_earth.csv".to_string());
+    let sample_rows = args
+        .next()
+        .map(|n| n.parse().expect("SAMPLE_ROWS must be a number"))
+        .unwrap_or(100);
+
+    let open = || match File::open(&path) {
+        Ok(file) => BufReader::new(file),
+        Err(e) => {
+            eprintln!("Failed to open '{}': {}", path, e);
+            process::exit(1)
+        }
+    };
+
+    let schema = infer_schema(open(), sample_rows).expect("Failed to infer schema");
+    println!("Schema of '{}' (sampled {} rows)", path, sample_rows);
+    print!("{}", schema);
+
+    println!("\nTyped rows:");
+    for row in schema.typed_rows(open()) {
+        match row {
+            Ok(values) => {
+                let values: Vec<_> = values.iter().map(ToString::to_string).collect();
+                println!("  {}", values.join(", "))
+            }
+            Err(e) => println!("  {}", e),
+        }
+    }
+}
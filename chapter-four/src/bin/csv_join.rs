@@ -0,0 +1,58 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    process,
+};
+
+use chapter_four::join::{Join, JoinKind, Strategy};
+
+/// 和数据库一样，连接两张表时有两种经典的算法
+/// * 哈希连接：把较小的一边放进 HashMap，流式读取另一边。速度快，但是那一边必须放得进内存
+/// * 排序归并连接：两边都按key排序后同时遍历。排序可以借助临时文件在外部完成，所以能处理比内存更大的文件
+///
+/// 用法：csv_join LEFT RIGHT KEYS [inner|left|full] [hash|sort-merge[=CHUNK_ROWS]]
+/// * KEYS 是逗号分隔的列名，两边列名不同时写成 left=right，例如 `planet=name`
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 4 {
+        eprintln!(
+            "Usage: {} LEFT RIGHT KEYS [inner|left|full] [hash|sort-merge[=CHUNK_ROWS]]",
+            args[0]
+        );
+        process::exit(2);
+    }
+
+    let (left_keys, right_keys): (Vec<_>, Vec<_>) = args[3]
+        .split(',')
+        .map(|key| key.split_once('=').unwrap_or((key, key)))
+        .unzip();
+    let mut join = Join::on_columns(&left_keys, &right_keys);
+    for arg in &args[4..] {
+        join = match arg.as_str() {
+            "inner" => join.kind(JoinKind::Inner),
+            "left" => join.kind(JoinKind::Left),
+            "full" => join.kind(JoinKind::FullOuter),
+            "hash" => join.strategy(Strategy::Hash),
+            "sort-merge" => join.strategy(Strategy::SortMerge {
+                chunk_rows: 100_000,
+            }),
+            other => match other.strip_prefix("sort-merge=").map(str::parse) {
+                Some(Ok(chunk_rows)) => join.strategy(Strategy::SortMerge { chunk_rows }),
+                _ => {
+                    eprintln!("Unknown option '{}'", other);
+                    process::exit(2)
+                }
+            },
+        };
+    }
+
+    let open = |path: &str| BufReader::new(File::open(path).expect("Failed to open csv file"));
+    match join.run(open(&args[1]), open(&args[2]), io::stdout().lock()) {
+        Ok(rows) => eprintln!("{} rows written", rows),
+        Err(e) => {
+            eprintln!("Failed to join: {}", e);
+            process::exit(1)
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::{
+    env,
+    fs::File,
+    io::{self, BufReader},
+    process,
+};
+
+use chapter_four::query::{Output, Query};
+
+/// 回答“哪些行星的重力比地球大”这类问题时，每次都写一段读取 csv::Reader 然后过滤的代码很繁琐
+/// 这里把过滤、排序、分组和聚合做成一个小型查询语言，语法见 chapter_four::query::Query
+///
+/// 查询先被解析成AST，执行时再把列名替换成下标，这样每一行只需要做下标访问，而不是查找列名
+///
+/// 用法：csv_query FILE QUERY [--table]，例如
+/// `csv_query solar_system_compared_to_earth.csv 'where gravity > 1 and name != "Earth" sort by radius desc' --table`
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 3 {
+        eprintln!("Usage: {} FILE QUERY [--table]", args[0]);
+        process::exit(2);
+    }
+    let output = if args[3..].iter().any(|a| a == "--table") {
+        Output::Table
+    } else {
+        Output::Csv
+    };
+
+    let query: Query = match args[2].parse() {
+        Ok(query) => query,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("  {}", args[2]);
+            if let chapter_four::query::QueryError::Parse { position, .. } = e {
+                eprintln!("  {}^", " ".repeat(args[2][..position].chars().count()));
+            }
+            process::exit(2)
+        }
+    };
+
+    let file = File::open(&args[1]).expect("Failed to open csv file");
+    if let Err(e) = query.execute(BufReader::new(file), io::stdout().lock(), output) {
+        eprintln!("Failed to run query: {}", e);
+        process::exit(1);
+    }
+}
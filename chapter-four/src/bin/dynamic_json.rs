@@ -1,37 +1,344 @@
 use std::{
-    collections::HashMap,
-    io::{self, BufRead},
+    fs,
+    io::{self, BufRead, Write},
 };
 
-use serde_json::json;
+use serde_json::{Map, Value, json};
 
 /// json! 宏现在会将字符串转换成 Value 类型
+///
+/// 不定义结构体也可以构建任意的JSON文档：Value 是一个枚举，对象是 Map<String, Value>，数组是 Vec<Value>
+/// 通过 get_mut 和 as_array_mut 等方法可以一层层找到（或者创建）需要修改的位置
+///
+/// 支持的输入
+/// * `KEY VALUE` 或者 `set KEY VALUE`：KEY 是用 . 分隔的路径，数字表示数组下标，例如 `owner.pets.0.name Waldo`
+///   不存在的中间节点会被自动创建，下标等于数组长度时追加一个元素
+/// * VALUE 先尝试按JSON解析（数字、true/false、null、"带引号的字符串"、[1, 2]、{"a": 1}），失败时作为普通字符串保存
+/// * `get KEY`、`del KEY`、`show`、`save FILE`、`help`
 fn main() {
-    let mut key_value_map = HashMap::new();
+    let mut document = Value::Object(Map::new());
     let stdin = io::stdin();
-    println!("Enter a key and a value");
+    println!("Enter a key and a value, or 'help' for a list of commands");
+    prompt();
     for input in stdin.lock().lines() {
         let input = input.expect("Failed to read line");
-        let key_value: Vec<_> = input.split_whitespace().collect();
-        let key = key_value[0].to_string();
-        let value = key_value[1].to_string();
-
-        println!("Saving Key-value pair: {} -> {}", key, value);
-        key_value_map.insert(key, json!(value));
-        println!(
-            "Enter another pair or stop by pressing '{}'",
-            END_OF_TRANSMISSION
-        )
+        match parse_command(&input).and_then(|command| run(&mut document, command)) {
+            Ok(Some(output)) => println!("{}", output),
+            Ok(None) => {}
+            Err(e) => println!("Error: {}", e),
+        }
+        prompt();
     }
 
-    let json =
-        serde_json::to_string_pretty(&key_value_map).expect("Failed to convert HashMap into JSON");
+    let json = serde_json::to_string_pretty(&document).expect("Failed to convert Value into JSON");
+    println!();
     println!("Your input has been made into the following JSON:");
     println!("{}", json);
 }
 
+fn prompt() {
+    print!("> ");
+    io::stdout().flush().expect("Failed to flush stdout");
+}
+
 #[cfg(target_os = "windows")]
 const END_OF_TRANSMISSION: &str = "Ctrl Z";
 
 #[cfg(not(target_os = "windows"))]
 const END_OF_TRANSMISSION: &str = "Ctrl D";
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    Set(String, Value),
+    Get(String),
+    Delete(String),
+    Show,
+    Save(String),
+    Help,
+    Nothing,
+}
+
+fn parse_command(input: &str) -> Result<Command, String> {
+    let input = input.trim();
+    let (word, rest) = match input.split_once(char::is_whitespace) {
+        Some((word, rest)) => (word, rest.trim()),
+        None => (input, ""),
+    };
+    let argument = |name: &str| {
+        if rest.is_empty() {
+            Err(format!("'{}' needs an argument", name))
+        } else {
+            Ok(rest.to_string())
+        }
+    };
+    match word {
+        "" => Ok(Command::Nothing),
+        "help" => Ok(Command::Help),
+        "show" => Ok(Command::Show),
+        "get" => Ok(Command::Get(argument("get KEY")?)),
+        "del" => Ok(Command::Delete(argument("del KEY")?)),
+        "save" => Ok(Command::Save(argument("save FILE")?)),
+        "set" => {
+            let (key, value) = rest
+                .split_once(char::is_whitespace)
+                .ok_or("'set' needs a key and a value")?;
+            Ok(Command::Set(key.to_string(), parse_value(value.trim())))
+        }
+        key if rest.is_empty() => Err(format!(
+            "Missing value for '{}', enter 'KEY VALUE' or stop by pressing '{}'",
+            key, END_OF_TRANSMISSION
+        )),
+        key => Ok(Command::Set(key.to_string(), parse_value(rest))),
+    }
+}
+
+/// 能按JSON解析的就按JSON保存，否则作为字符串，所以 `42` 是数字而 `"42"` 是字符串
+fn parse_value(input: &str) -> Value {
+    serde_json::from_str(input).unwrap_or_else(|_| json!(input))
+}
+
+fn run(document: &mut Value, command: Command) -> Result<Option<String>, String> {
+    let pretty = |value: &Value| {
+        serde_json::to_string_pretty(value).expect("Failed to convert Value into JSON")
+    };
+    match command {
+        Command::Set(key, value) => {
+            let output = format!("Saving key-value pair: {} -> {}", key, value);
+            set(document, &key, value)?;
+            Ok(Some(output))
+        }
+        Command::Get(key) => get(document, &key).map(|value| Some(pretty(value))),
+        Command::Delete(key) => {
+            let removed = delete(document, &key)?;
+            Ok(Some(format!("Removed {} ({})", key, removed)))
+        }
+        Command::Show => Ok(Some(pretty(document))),
+        Command::Save(file) => {
+            fs::write(&file, pretty(document))
+                .map_err(|e| format!("Failed to write '{}': {}", file, e))?;
+            Ok(Some(format!("Saved to {}", file)))
+        }
+        Command::Help => Ok(Some(format!(
+            "KEY VALUE       set a value, e.g. owner.pets.0.name Waldo\n\
+             set KEY VALUE   same as above, for keys named like a command\n\
+             get KEY         print a value\n\
+             del KEY         remove a value\n\
+             show            print the whole document\n\
+             save FILE       write the document to FILE\n\
+             {}          finish and print the document",
+            END_OF_TRANSMISSION
+        ))),
+        Command::Nothing => Ok(None),
+    }
+}
+
+fn segments(key: &str) -> Result<Vec<&str>, String> {
+    let segments: Vec<_> = key.split('.').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(format!("Invalid key '{}'", key));
+    }
+    Ok(segments)
+}
+
+fn get<'a>(document: &'a Value, key: &str) -> Result<&'a Value, String> {
+    let mut current = document;
+    for (depth, segment) in segments(key)?.into_iter().enumerate() {
+        current = match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(array) => segment.parse::<usize>().ok().and_then(|i| array.get(i)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("'{}' does not exist", prefix(key, depth + 1)))?;
+    }
+    Ok(current)
+}
+
+fn get_mut<'a>(document: &'a mut Value, key: &str) -> Result<&'a mut Value, String> {
+    let mut current = document;
+    for (depth, segment) in segments(key)?.into_iter().enumerate() {
+        current = match current {
+            Value::Object(map) => map.get_mut(segment),
+            Value::Array(array) => segment.parse::<usize>().ok().and_then(|i| array.get_mut(i)),
+            _ => None,
+        }
+        .ok_or_else(|| format!("'{}' does not exist", prefix(key, depth + 1)))?;
+    }
+    Ok(current)
+}
+
+/// 沿路径向下，不存在的节点按下一段是不是数字创建成数组或对象
+///
+/// 路径中后面的段可能出错（例如下标越界），这时前面已经创建的节点不应该留在文档中，
+/// 所以在副本上修改，成功之后才替换原来的文档
+fn set(document: &mut Value, key: &str, value: Value) -> Result<(), String> {
+    let mut updated = document.clone();
+    set_in(&mut updated, key, value)?;
+    *document = updated;
+    Ok(())
+}
+
+fn set_in(document: &mut Value, key: &str, value: Value) -> Result<(), String> {
+    let segments = segments(key)?;
+    let mut current = document;
+    for (depth, segment) in segments.iter().enumerate() {
+        let next_is_index = segments
+            .get(depth + 1)
+            .is_some_and(|s| s.parse::<usize>().is_ok());
+        let empty = || match next_is_index {
+            true => Value::Array(Vec::new()),
+            false => Value::Object(Map::new()),
+        };
+        let last = depth == segments.len() - 1;
+        current = match current {
+            Value::Object(map) => map.entry(segment.to_string()).or_insert_with(empty),
+            Value::Array(array) => {
+                let index: usize = segment.parse().map_err(|_| {
+                    format!("'{}' is an array, expected an index", prefix(key, depth))
+                })?;
+                if index > array.len() {
+                    return Err(format!(
+                        "Index {} is out of bounds for '{}' with {} elements",
+                        index,
+                        prefix(key, depth),
+                        array.len()
+                    ));
+                }
+                if index == array.len() {
+                    array.push(empty());
+                }
+                &mut array[index]
+            }
+            other => {
+                return Err(format!(
+                    "Cannot create '{}' inside {}",
+                    segment,
+                    type_name(other)
+                ));
+            }
+        };
+        if last {
+            *current = value;
+            return Ok(());
+        }
+    }
+    unreachable!("A key has at least one segment")
+}
+
+fn delete(document: &mut Value, key: &str) -> Result<Value, String> {
+    let (parent, last) = match key.rsplit_once('.') {
+        Some((parent, last)) => (Some(parent), last),
+        None => (None, key),
+    };
+    let container = match parent {
+        Some(parent) => get_mut(document, parent)?,
+        None => document,
+    };
+    let removed = match container {
+        Value::Object(map) => map.shift_remove(last),
+        Value::Array(array) => match last.parse::<usize>() {
+            Ok(i) if i < array.len() => Some(array.remove(i)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| format!("'{}' does not exist", key))
+}
+
+/// 路径的前 segments 段，用于错误信息
+fn prefix(key: &str, segments: usize) -> String {
+    key.split('.').take(segments).collect::<Vec<_>>().join(".")
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn document(lines: &[&str]) -> Value {
+        let mut document = Value::Object(Map::new());
+        for line in lines {
+            let command = parse_command(line).expect("Failed to parse command");
+            run(&mut document, command).expect("Failed to run command");
+        }
+        document
+    }
+
+    #[test]
+    fn builds_nested_documents_with_typed_values() {
+        let document = document(&[
+            "owner.name John",
+            "owner.age 23",
+            "owner.pets.0.name Waldo",
+            "owner.pets.0.age null",
+            "owner.pets.1 {\"name\": \"Speedy\", \"colour\": \"Green\"}",
+            "owner.verified true",
+            "owner.nickname \"42\"",
+        ]);
+        assert_eq!(
+            json!({
+                "owner": {
+                    "name": "John",
+                    "age": 23,
+                    "pets": [
+                        { "name": "Waldo", "age": null },
+                        { "name": "Speedy", "colour": "Green" },
+                    ],
+                    "verified": true,
+                    "nickname": "42",
+                }
+            }),
+            document
+        );
+    }
+
+    #[test]
+    fn deletes_and_gets() {
+        let mut document = document(&["a.b.0 1", "a.b.1 2", "a.c x"]);
+        assert_eq!(Ok(json!(1)), delete(&mut document, "a.b.0"));
+        assert_eq!(Ok(&json!([2])), get(&document, "a.b"));
+        assert_eq!(Ok(json!("x")), delete(&mut document, "a.c"));
+        assert_eq!(json!({ "a": { "b": [2] } }), document);
+    }
+
+    #[test]
+    fn failed_set_leaves_the_document_unchanged() {
+        let mut document = document(&["name John"]);
+        let before = document.clone();
+        assert_eq!(
+            Err("Index 3 is out of bounds for 'owner.pets' with 0 elements".to_string()),
+            set(&mut document, "owner.pets.3", json!("Waldo"))
+        );
+        assert_eq!(before, document);
+    }
+
+    #[test]
+    fn reports_errors_instead_of_panicking() {
+        assert!(parse_command("lonely").is_err());
+        assert!(parse_command("get").is_err());
+
+        let mut document = document(&["name John", "pets.0 Waldo"]);
+        assert_eq!(
+            Err("Cannot create 'first' inside a string".to_string()),
+            set(&mut document, "name.first", json!("J"))
+        );
+        assert_eq!(
+            Err("Index 5 is out of bounds for 'pets' with 1 elements".to_string()),
+            set(&mut document, "pets.5", json!("Speedy"))
+        );
+        assert_eq!(
+            Err("'pets.age' does not exist".to_string()),
+            get(&document, "pets.age").map(|_| ())
+        );
+        assert!(delete(&mut document, "missing").is_err());
+    }
+}
@@ -0,0 +1,50 @@
+use std::{env, process, thread, time::Duration};
+
+use chapter_four::{
+    hot_reload::HotConfig,
+    preferences::{self, Preferences},
+};
+
+/// 运行时修改 preferences.toml，不需要重启程序就能看到新的配置，见 chapter_four::hot_reload
+///
+/// 用法：RUST_LOG=info hot_reload [FILE]，FILE 默认是 toml.rs 生成的 preferences.toml
+/// 然后在另一个终端中编辑这个文件，例如 `toml-set preferences.toml privacy.public_email false`；
+/// 写入不合法的内容时会看到一条错误日志，程序继续使用之前的配置
+fn main() {
+    env_logger::init();
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| "preferences.toml".to_string());
+
+    // 旧版本的文件在内存中迁移，不会被改写，需要时可以用 migrate_preferences --write 升级文件
+    let migrations = preferences::migrations();
+    let config = HotConfig::watch(&path, Duration::from_millis(500), move |input| {
+        let preferences = migrations
+            .load::<Preferences>(input)
+            .map_err(|e| e.to_string())?
+            .value;
+        // 语法正确但是值不合理的配置同样不会被换进去
+        preferences.validate().map_err(|e| e.to_string())?;
+        Ok::<_, String>(preferences)
+    })
+    .unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1)
+    });
+    config.on_change(|old: &Preferences, new: &Preferences| {
+        if old.privacy != new.privacy {
+            println!("Privacy settings changed: {:?}", new.privacy);
+        }
+    });
+
+    println!("Watching {}, press Ctrl+C to stop", config.path().display());
+    loop {
+        // 每次循环取一个快照，一次请求中用到的配置总是一致的
+        let preferences = config.get();
+        println!(
+            "Display language: {}, public email: {}",
+            preferences.language.display, preferences.privacy.public_email
+        );
+        thread::sleep(Duration::from_secs(5));
+    }
+}
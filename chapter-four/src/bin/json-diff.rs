@@ -0,0 +1,102 @@
+use std::{
+    env, fs,
+    io::{self, IsTerminal},
+    process,
+};
+
+use chapter_four::patch::{self, Operation, Patch};
+use serde_json::Value;
+
+/// 比较两个JSON文件，例如修改前后的 pet_owner.json
+/// * 默认输出JSON Patch（RFC 6902），可以保存下来再应用到原来的文档上
+/// * --merge 输出JSON Merge Patch（RFC 7386）
+/// * --pretty 输出给人看的差异，删除的值红色，新增的值绿色，输出不是终端或者设置了 NO_COLOR 时不使用颜色
+///
+/// 和 diff 命令一样，文件相同时退出码为0，不同时为1
+///
+/// 用法：json-diff A B [--merge | --pretty]
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 3 || args.len() > 4 {
+        eprintln!("Usage: {} A B [--merge | --pretty]", args[0]);
+        process::exit(2);
+    }
+    let from = read(&args[1]);
+    let to = read(&args[2]);
+
+    let patch = patch::diff(&from, &to);
+    match args.get(3).map(String::as_str) {
+        None => println!(
+            "{}",
+            serde_json::to_string_pretty(&patch).expect("Failed to serialize patch")
+        ),
+        Some("--merge") => println!(
+            "{}",
+            serde_json::to_string_pretty(&patch::merge_diff(&from, &to))
+                .expect("Failed to serialize merge patch")
+        ),
+        Some("--pretty") => {
+            let colored = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+            print!("{}", render(&from, &patch, colored));
+        }
+        Some(other) => {
+            eprintln!("Unknown option '{}'", other);
+            process::exit(2);
+        }
+    }
+    if !patch.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn read(path: &str) -> Value {
+    let json = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read {}: {}", path, e);
+        process::exit(2)
+    });
+    serde_json::from_str(&json).unwrap_or_else(|e| {
+        eprintln!("Failed to parse {}: {}", path, e);
+        process::exit(2)
+    })
+}
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// 每个操作一行或两行。操作中的下标是相对于应用了前面操作之后的文档的，
+/// 所以一边渲染一边把操作应用到副本上，才能找到被删除或者替换的旧值
+fn render(from: &Value, patch: &Patch, colored: bool) -> String {
+    let line = |sign: char, color: &str, path: &dyn ToString, value: &Value| match colored {
+        true => format!(
+            "{}{} {}: {}{}\n",
+            color,
+            sign,
+            path.to_string(),
+            value,
+            RESET
+        ),
+        false => format!("{} {}: {}\n", sign, path.to_string(), value),
+    };
+    let mut current = from.clone();
+    let mut output = String::new();
+    for operation in &patch.0 {
+        match operation {
+            Operation::Add { path, value } => output += &line('+', GREEN, path, value),
+            Operation::Remove { path } | Operation::Replace { path, .. } => {
+                let old = path
+                    .get(&current)
+                    .expect("The diff only touches existing values");
+                output += &line('-', RED, path, old);
+                if let Operation::Replace { value, .. } = operation {
+                    output += &line('+', GREEN, path, value);
+                }
+            }
+            _ => unreachable!("A diff only contains add, remove and replace operations"),
+        }
+        Patch(vec![operation.clone()])
+            .apply(&mut current)
+            .expect("Failed to apply an operation of the diff");
+    }
+    output
+}
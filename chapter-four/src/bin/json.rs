@@ -3,7 +3,9 @@ use std::{
     io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
 };
 
-use serde::{Deserialize, Serialize};
+use chapter_four::json_schema::JsonSchema;
+use serde::{Deserialize, Serialize, de};
+use serde_json::{Value, json};
 
 /// JSON由两种结构组成
 /// * 一组k-v对，由 {} 包围，叫做对象，可以作为值
@@ -15,6 +17,8 @@ use serde::{Deserialize, Serialize};
 /// 在Serde框架之下，所有格式的序列化和反序列化逻辑都隐藏在相同的特质定义之下，我们可以不关心内部实现，直接使用相同的API
 ///
 /// JSON没有对应枚举 enum 的概念，Serde允许你通过注解来处理这种结构
+///
+/// 读取时先用JSON Schema检查文档，这样手工修改 pet_owner.json 出错时能一次看到所有问题，而不是serde报告的第一个错误
 fn main() {
     let file = OpenOptions::new()
         .read(true)
@@ -79,7 +83,20 @@ where
         .read_to_string(&mut json)
         .expect("Failed to read JSON");
 
-    let pet_owner: PetOwner = serde_json::from_str(&json)?;
+    let document: Value = serde_json::from_str(&json)?;
+    let schema = JsonSchema::compile(&pet_owner_schema()).expect("Invalid PetOwner schema");
+    let violations = schema.validate(&document);
+    if !violations.is_empty() {
+        for violation in &violations {
+            eprintln!("{}", violation);
+        }
+        return Err(de::Error::custom(format!(
+            "the document violates the PetOwner schema in {} places",
+            violations.len()
+        )));
+    }
+
+    let pet_owner: PetOwner = serde_json::from_value(document)?;
     println!("Pet owner profile:");
     println!(" Name: {}", pet_owner.name);
     println!(" Age: {}", pet_owner.age);
@@ -99,6 +116,34 @@ where
     Ok(())
 }
 
+/// 手写的schema，和下面的结构体一一对应
+/// * u8 对应 0 到 255 的整数
+/// * Option 对应可以是 null 的类型，并且不在 required 中，因为serde把缺少的 Option 字段当成 None
+/// * AllowedSpecies 的变体被序列化成同名的字符串，所以用 enum 列出
+fn pet_owner_schema() -> Value {
+    let pet = json!({
+        "type": "object",
+        "required": ["name", "species"],
+        "properties": {
+            "name": { "type": "string", "pattern": "^\\S" },
+            "species": { "enum": ["Dog", "Turtle", "Cat"] },
+            "age": { "type": ["integer", "null"], "minimum": 0, "maximum": 255 },
+            "colour": { "type": ["string", "null"] }
+        }
+    });
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "PetOwner",
+        "type": "object",
+        "required": ["name", "age", "pets"],
+        "properties": {
+            "name": { "type": "string", "pattern": "^\\S" },
+            "age": { "type": "integer", "minimum": 0, "maximum": 255 },
+            "pets": { "type": "array", "items": pet }
+        }
+    })
+}
+
 #[derive(Serialize, Deserialize)]
 struct PetOwner {
     name: String,
@@ -121,3 +166,50 @@ enum AllowedSpecies {
     Turtle,
     Cat,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn violations(document: Value) -> Vec<String> {
+        JsonSchema::compile(&pet_owner_schema())
+            .expect("Invalid PetOwner schema")
+            .validate(&document)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn written_documents_match_the_schema() {
+        let mut json = Vec::new();
+        write_json(&mut json).expect("Failed to write JSON");
+        let document = serde_json::from_slice(&json).expect("Failed to parse JSON");
+        assert!(violations(document).is_empty());
+    }
+
+    #[test]
+    fn reports_every_violation() {
+        let document = json!({
+            "name": "John",
+            "age": 300,
+            "pets": [
+                { "name": "Waldo", "species": "Dog", "age": 2, "colour": null },
+                { "name": " ", "species": "Fish", "age": -1 },
+                { "species": "Cat", "colour": 7 }
+            ]
+        });
+        assert_eq!(
+            vec![
+                "'/age': 300 is greater than the maximum of 255",
+                "'/pets/1/name': \" \" does not match '^\\S'",
+                "'/pets/1/species': \"Fish\" is not one of \"Dog\", \"Turtle\", \"Cat\"",
+                "'/pets/1/age': -1 is less than the minimum of 0",
+                "'/pets/2': missing required property 'name'",
+                "'/pets/2/colour': expected string or null, found integer",
+            ],
+            violations(document.clone())
+        );
+        assert!(serde_json::from_value::<PetOwner>(document).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use std::{env, fs, process};
+
+use chapter_four::{json_path::JsonPath, pointer::Pointer};
+use serde_json::Value;
+
+/// 在不定义结构体的情况下从JSON文件中取值
+/// * 以 $ 开头的表达式按JSONPath解析，可能得到多个值，例如 `$.pets[?(@.age > 10)].name`
+/// * 否则按JSON Pointer解析，最多得到一个值，例如 `/pets/0/name`
+///
+/// 每个结果前面打印它的JSON Pointer，可以直接用在 dynamic_json 之类需要精确位置的地方
+///
+/// 用法：json_query FILE EXPRESSION，例如 `json_query pet_owner.json '$..name'`
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 3 {
+        eprintln!("Usage: {} FILE (JSONPATH | POINTER)", args[0]);
+        process::exit(2);
+    }
+    let (path, expression) = (&args[1], &args[2]);
+
+    let json = fs::read_to_string(path).expect("Failed to read JSON file");
+    let document: Value = serde_json::from_str(&json).unwrap_or_else(|e| {
+        eprintln!("Failed to parse {}: {}", path, e);
+        process::exit(1)
+    });
+
+    let matches = if expression.starts_with('$') {
+        let json_path = expression.parse::<JsonPath>().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            eprintln!("  {}", expression);
+            eprintln!(
+                "  {}^",
+                " ".repeat(expression[..e.position].chars().count())
+            );
+            process::exit(2)
+        });
+        json_path
+            .query(&document)
+            .into_iter()
+            .map(|m| (m.pointer, m.value))
+            .collect()
+    } else {
+        let pointer: Pointer = expression.parse().unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(2)
+        });
+        match pointer.get(&document) {
+            Some(value) => vec![(pointer, value)],
+            None => Vec::new(),
+        }
+    };
+
+    if matches.is_empty() {
+        eprintln!("No match for '{}'", expression);
+        process::exit(1);
+    }
+    for (pointer, value) in matches {
+        println!("{}\t{}", pointer, value);
+    }
+}
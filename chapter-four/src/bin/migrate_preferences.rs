@@ -0,0 +1,75 @@
+use std::{fs, process};
+
+use chapter_four::{
+    migrate::{self, MigrationError},
+    preferences::{self, Preferences},
+};
+use chapter_one::cli::{Arg, Command};
+
+/// 把旧版本的 preferences.toml 升级到当前版本，见 chapter_four::migrate
+///
+/// 用法：migrate_preferences FILE [--write]
+/// 默认只打印需要执行的迁移和迁移后的内容；加上 --write 时改写文件，原来的文件保存为 FILE.v{版本}.bak
+fn main() {
+    let matches = Command::new("migrate_preferences")
+        .about("Upgrade a preferences.toml to the current version")
+        .arg(Arg::positional("file").required(true))
+        .arg(
+            Arg::flag("write")
+                .short('w')
+                .help("Rewrite the file and keep a backup of the original"),
+        )
+        .parse_env();
+    let path = matches.value("file").expect("file is required");
+    let write = matches.flag("write");
+
+    let migrations = preferences::migrations();
+    let migrated = match migrations.load_file::<Preferences, _>(path, write) {
+        Ok(migrated) => migrated,
+        Err(e @ MigrationError::FromTheFuture { .. }) => {
+            eprintln!("{}: {}", path, e);
+            eprintln!("Please upgrade this program instead of downgrading the file");
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            process::exit(1);
+        }
+    };
+
+    // 迁移只改变结构，旧文件中本来就不合理的值仍然需要手动修正
+    if let Err(invalid) = migrated.value.validate() {
+        eprintln!("{}: {}", path, invalid);
+    }
+
+    if migrated.applied.is_empty() {
+        println!("{} is already at version {}", path, migrations.current());
+        return;
+    }
+    println!(
+        "Migrating {} from version {} to {}:",
+        path,
+        migrated.from_version,
+        migrations.current()
+    );
+    for description in &migrated.applied {
+        println!(" - {}", description);
+    }
+    if write {
+        println!(
+            "Rewrote {}, the original is saved as {}.v{}.bak",
+            path, path, migrated.from_version
+        );
+    } else {
+        // 和 --write 写入的内容相同，注释都被保留
+        let output = fs::read_to_string(path)
+            .map_err(MigrationError::from)
+            .and_then(|input| migrate::write_back(&input, &migrated.document))
+            .unwrap_or_else(|e| {
+                eprintln!("{}: {}", path, e);
+                process::exit(1)
+            });
+        println!();
+        print!("{}", output);
+    }
+}
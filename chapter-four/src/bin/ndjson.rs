@@ -0,0 +1,99 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fs::{File, OpenOptions},
+    io::{BufReader, BufWriter},
+    process,
+};
+
+use chapter_four::ndjson::{NdjsonError, Reader, Writer};
+use serde::{Deserialize, Serialize};
+
+/// json.rs 先把整个文件读进一个 String 再解析，对几百万条记录的导出文件来说太浪费内存
+/// NDJSON每行一条记录，写入时追加，读取时流式处理，内存中始终只有一行
+///
+/// 用法：
+/// * ndjson write FILE COUNT：向 FILE 追加 COUNT 只宠物，可以多次运行
+/// * ndjson read FILE：逐行读取并统计每个物种的数量，损坏的行会报告行号然后跳过
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    match (args.get(1).map(String::as_str), args.len()) {
+        (Some("write"), 4) => {
+            let count = args[3].parse().unwrap_or_else(|_| {
+                eprintln!("COUNT must be a number, found '{}'", args[3]);
+                process::exit(2)
+            });
+            write_pets(&args[2], count)
+        }
+        (Some("read"), 3) => read_pets(&args[2]),
+        _ => {
+            eprintln!("Usage: {} (write FILE COUNT | read FILE)", args[0]);
+            process::exit(2)
+        }
+    }
+}
+
+fn write_pets(path: &str, count: usize) {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .expect("Failed to open NDJSON file");
+    let mut writer = Writer::new(BufWriter::new(file));
+    let species = [
+        AllowedSpecies::Dog,
+        AllowedSpecies::Turtle,
+        AllowedSpecies::Cat,
+    ];
+    for i in 0..count {
+        let pet = Pet {
+            name: format!("Pet {}", i),
+            species: species[i % species.len()],
+            age: (i % 4 != 0).then_some((i % 20) as u8),
+            colour: (i % 2 == 0).then(|| "Orange".to_string()),
+        };
+        writer.write(&pet).expect("Failed to write pet");
+    }
+    writer.flush().expect("Failed to flush NDJSON file");
+    println!("Appended {} pets to {}", count, path);
+}
+
+fn read_pets(path: &str) {
+    let file = File::open(path).expect("Failed to open NDJSON file");
+    let mut per_species = BTreeMap::new();
+    let mut invalid = 0;
+    for record in Reader::<_, Pet>::new(BufReader::new(file)) {
+        match record {
+            Ok(pet) => *per_species.entry(pet.species).or_insert(0) += 1,
+            Err(e @ NdjsonError::Parse { .. }) => {
+                eprintln!("Skipping {}", e);
+                invalid += 1;
+            }
+            Err(e) => {
+                eprintln!("Failed to read {}: {}", path, e);
+                process::exit(1)
+            }
+        }
+    }
+    for (species, count) in &per_species {
+        println!("{:?}: {}", species, count);
+    }
+    if invalid > 0 {
+        println!("{} malformed lines were skipped", invalid);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Pet {
+    name: String,
+    species: AllowedSpecies,
+    age: Option<u8>,
+    colour: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+enum AllowedSpecies {
+    Dog,
+    Turtle,
+    Cat,
+}
@@ -0,0 +1,76 @@
+use std::{collections::BTreeMap, env, process};
+
+use chapter_four::{
+    layered::{Layers, Source, user_config_path},
+    preferences::{self, Preferences},
+};
+use chapter_one::cli::{Arg, Command};
+
+/// toml.rs 只从一个文件读取 Preferences，实际的程序通常需要多层配置，见 chapter_four::layered
+///
+/// 用法：preferences [--set KEY=VALUE]...，例如
+/// `APP_PRIVACY__PUBLIC_EMAIL=false preferences --set language.display=de-CH`
+/// 打印最终的配置，以及每个值来自哪一层；配置不合理时打印每个问题和它来自哪一层
+fn main() {
+    let matches = Command::new("preferences")
+        .about("Print the layered preferences and where each value comes from")
+        .arg(
+            Arg::option("set")
+                .value_name("KEY=VALUE")
+                .multiple(true)
+                .help("Override a value, e.g. language.display=de-CH"),
+        )
+        .parse_env();
+    let overrides = matches.values("set").to_vec();
+
+    let mut layers = Layers::new(&Preferences::default())
+        .map(|l| l.migrations(preferences::migrations()))
+        .and_then(|l| l.system_file("/etc/app/preferences.toml"));
+    if let Some(path) = user_config_path("app", "preferences.toml") {
+        layers = layers.and_then(|l| l.user_file(path));
+    }
+    let loaded = layers
+        .and_then(|l| l.env("APP_", env::vars()))
+        .and_then(|l| l.overrides(overrides))
+        .and_then(Layers::load::<Preferences>)
+        .unwrap_or_else(|e| {
+            eprintln!("{}", e);
+            process::exit(1)
+        });
+
+    // 每一层单独看都可能是合法的，只有合并之后才能检查字段之间的约束
+    if let Err(invalid) = loaded.value.validate() {
+        for problem in &invalid.0 {
+            eprintln!(
+                "{} (from {})",
+                problem,
+                source_of(&loaded.provenance, &problem.key)
+            );
+        }
+        process::exit(1);
+    }
+
+    print!(
+        "{}",
+        toml::to_string(&loaded.value).expect("Failed to serialize preferences")
+    );
+    println!();
+    println!("Sources:");
+    for (key, source) in &loaded.provenance {
+        println!(" {} <- {}", key, source);
+    }
+}
+
+/// 问题的键可能是数组元素或者整个表，沿着路径向上找到记录了来源的键
+fn source_of(provenance: &BTreeMap<String, Source>, key: &str) -> String {
+    let mut key = key.split('[').next().unwrap_or(key);
+    loop {
+        if let Some(source) = provenance.get(key) {
+            return source.to_string();
+        }
+        match key.rsplit_once('.') {
+            Some((parent, _)) => key = parent,
+            None => return Source::Default.to_string(),
+        }
+    }
+}
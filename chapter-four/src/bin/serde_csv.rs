@@ -1,8 +1,10 @@
 use std::{
-    fs::OpenOptions,
+    env,
+    fs::{File, OpenOptions},
     io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
 };
 
+use chapter_four::validate::{Report, Validator};
 use serde::{Deserialize, Serialize};
 
 /// rust将serde作为所有序列化相关内容的事实标准（de facto）
@@ -15,7 +17,23 @@ use serde::{Deserialize, Serialize};
 /// 在任何允许的时候都应该使用Serde，它可以通过可读性和编译期的类型安全帮助你找到可能的错误
 ///
 /// Serde允许你通过注解成员来调整（反）序列化过程，例如 #[serde(default)] 注解的成员，如果没有被转换就会使用它的默认值。另一种有用的方式是修改名称大小写风格，例如Serde使用 snake_case，可以通过 #[serde(rename_all = "PascalCase")] 来修改（注解放到结构体上）
+///
+/// 使用 `--validate [FILE]` 运行时不会重写文件，而是检查每一行并报告所有问题，见 validate_records
 fn main() {
+    let mut args = env::args().skip(1);
+    if args.next().as_deref() == Some("--validate") {
+        let path = args
+            .next()
+            .unwrap_or_else(|| "solar_system_compared_to_earth.csv".to_string());
+        let file = File::open(&path).expect("failed to open csv file");
+        let report = validate_records(BufReader::new(file)).expect("Failed to read csv");
+        print!("{}", report);
+        if !report.is_valid() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let file = OpenOptions::new()
         .read(true)
         .write(true)
@@ -117,3 +135,27 @@ where
 
     Ok(())
 }
+
+/// read_records 遇到第一行坏数据就会通过 `?` 返回，validate_records 会检查所有行
+/// 除了类型错误，还会检查反序列化无法表达的规则
+fn validate_records<R>(reader: R) -> csv::Result<Report<Planet>>
+where
+    R: Read,
+{
+    Validator::new()
+        .rule("name", "name must not be empty", |p: &Planet| {
+            !p.name.trim().is_empty()
+        })
+        .rule("radius", "radius must be greater than 0", |p: &Planet| {
+            p.radius > 0.
+        })
+        .rule(
+            "distance_from_sun",
+            "distance_from_sun must be greater than 0",
+            |p: &Planet| p.distance_from_sun > 0.,
+        )
+        .rule("gravity", "gravity must not be negative", |p: &Planet| {
+            p.gravity >= 0.
+        })
+        .validate(reader)
+}
@@ -0,0 +1,47 @@
+use std::{env, fs, path::Path, process};
+
+use chapter_four::edit::{TomlEditor, parse_value};
+
+/// 修改TOML文件中的一个键，文件的其它部分（注释、空行、键的顺序）保持不变，见 chapter_four::edit
+///
+/// 用法：
+/// * toml-set FILE KEY VALUE：设置一个值，例如 `toml-set preferences.toml privacy.public_email false`
+/// * toml-set FILE KEY --push VALUE：在数组末尾追加，例如 `toml-set preferences.toml autocorrect.locales --push de-CH`
+/// * toml-set FILE KEY --remove：删除一个键
+///
+/// VALUE 能按TOML解析时使用解析出的类型，否则作为字符串
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    let usage = || -> ! {
+        eprintln!(
+            "Usage: {} FILE KEY (VALUE | --push VALUE | --remove)",
+            args[0]
+        );
+        process::exit(2)
+    };
+    if args.len() < 4 {
+        usage();
+    }
+    let (path, key) = (Path::new(&args[1]), &args[2]);
+
+    let toml = fs::read_to_string(path).expect("Failed to read TOML file");
+    let mut editor: TomlEditor = toml.parse().unwrap_or_else(|e| {
+        eprintln!("Failed to parse {}: {}", path.display(), e);
+        process::exit(1)
+    });
+    let result = match &args[3..] {
+        [flag, value] if flag == "--push" => editor.push(key, parse_value(value)),
+        [flag] if flag == "--remove" => editor.remove(key).map(drop),
+        [value] => editor.set(key, parse_value(value)),
+        _ => usage(),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+
+    // 先写入临时文件再重命名，写到一半失败时原来的文件不会损坏
+    let temp = path.with_extension("toml.tmp");
+    fs::write(&temp, editor.to_string()).expect("Failed to write TOML file");
+    fs::rename(&temp, path).expect("Failed to replace TOML file");
+}
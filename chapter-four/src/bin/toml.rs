@@ -3,7 +3,9 @@ use std::{
     io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
 };
 
-use serde::{Deserialize, Serialize};
+use chapter_four::preferences::{
+    Autocorrect, CURRENT_VERSION, Language, Person, Preferences, Privacy,
+};
 
 /// TOML是关于k-v对的
 /// message = "Hello World" -> 最简单的TOML文件
@@ -57,17 +59,21 @@ where
     W: Write,
 {
     let preferences = Preferences {
+        version: CURRENT_VERSION,
         person: Person {
             name: "Jan Nils Ferner".to_string(),
             email: "jn_ferner@hotmail.de".to_string(),
         },
         language: Language {
             display: "en-GB".to_string(),
-            autocorrect: Some(vec![
+        },
+        autocorrect: Autocorrect {
+            enabled: true,
+            locales: vec![
                 "en-GB".to_string(),
                 "en-US".to_string(),
                 "de-CH".to_string(),
-            ]),
+            ],
         },
         privacy: Privacy {
             share_anonymous_statistics: false,
@@ -104,7 +110,9 @@ where
     println!("\nLanguage prefereces:");
     let language = &preferences.language;
     println!(" Display language: {}", language.display);
-    println!(" Autocorrect priority: {:?}", language.autocorrect);
+    let autocorrect = &preferences.autocorrect;
+    println!(" Autocorrect enabled: {}", autocorrect.enabled);
+    println!(" Autocorrect priority: {:?}", autocorrect.locales);
 
     println!("\nPrivacy settings:");
     let privacy = &preferences.privacy;
@@ -117,29 +125,3 @@ where
 
     Ok(())
 }
-
-#[derive(Serialize, Deserialize)]
-struct Preferences {
-    person: Person,
-    language: Language,
-    privacy: Privacy,
-}
-
-#[derive(Serialize, Deserialize)]
-struct Person {
-    name: String,
-    email: String,
-}
-
-#[derive(Serialize, Deserialize)]
-struct Language {
-    display: String,
-    autocorrect: Option<Vec<String>>,
-}
-
-#[derive(Serialize, Deserialize)]
-struct Privacy {
-    share_anonymous_statistics: bool,
-    public_name: bool,
-    public_email: bool,
-}
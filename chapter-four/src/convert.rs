@@ -0,0 +1,400 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    path::Path,
+    result,
+    str::FromStr,
+};
+
+use serde_json::{Map, Number, Value};
+
+use crate::schema::{self, infer_schema};
+
+/// 三种格式通过 serde_json::Value 这个共同的中间表示互相转换：先把输入解析成 Value，再把 Value 渲染成目标格式
+/// 这样 n 种格式只需要 2n 个函数，而不是 n² 个
+///
+/// 中间表示能表达的东西比某些目标格式多：
+/// * TOML没有null，文档的顶层必须是表
+/// * CSV只能表示由标量组成的二维表格
+///
+/// 遇到这些情况时返回 ConvertError::Unrepresentable，并用JSON Pointer指出是哪个值出了问题
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// 根据文件扩展名判断格式
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        path.as_ref().extension()?.to_str()?.parse().ok()
+    }
+}
+
+impl FromStr for Format {
+    type Err = ConvertError;
+
+    fn from_str(s: &str) -> result::Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(Self::Csv),
+            "json" => Ok(Self::Json),
+            "toml" => Ok(Self::Toml),
+            _ => Err(ConvertError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Csv => write!(f, "CSV"),
+            Self::Json => write!(f, "JSON"),
+            Self::Toml => write!(f, "TOML"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConvertError {
+    UnknownFormat(String),
+    Csv(csv::Error),
+    Json(serde_json::Error),
+    TomlDe(toml::de::Error),
+    TomlSer(toml::ser::Error),
+    /// CSV 的某一行不符合推断出的 schema
+    Schema(schema::SchemaError),
+    /// 值无法用目标格式表示，path是这个值的JSON Pointer
+    Unrepresentable {
+        format: Format,
+        path: String,
+        reason: &'static str,
+    },
+}
+
+pub type Result<T> = result::Result<T, ConvertError>;
+
+impl error::Error for ConvertError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Csv(ref err) => Some(err),
+            Self::Json(ref err) => Some(err),
+            Self::TomlDe(ref err) => Some(err),
+            Self::TomlSer(ref err) => Some(err),
+            Self::Schema(ref err) => Some(err),
+            Self::UnknownFormat(_) | Self::Unrepresentable { .. } => None,
+        }
+    }
+}
+
+impl Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::UnknownFormat(ref format) => {
+                write!(f, "Unknown format '{}', expected csv, json or toml", format)
+            }
+            Self::Csv(ref err) => write!(f, "CSV error: {}", err),
+            Self::Json(ref err) => write!(f, "JSON error: {}", err),
+            Self::TomlDe(ref err) => write!(f, "TOML parse error: {}", err),
+            Self::TomlSer(ref err) => write!(f, "TOML serialize error: {}", err),
+            Self::Schema(ref err) => write!(f, "{}", err),
+            Self::Unrepresentable {
+                format,
+                ref path,
+                reason,
+            } => {
+                let path = if path.is_empty() { "(root)" } else { path };
+                write!(f, "Cannot represent {} in {}: {}", path, format, reason)
+            }
+        }
+    }
+}
+
+impl From<csv::Error> for ConvertError {
+    fn from(value: csv::Error) -> Self {
+        Self::Csv(value)
+    }
+}
+
+impl From<serde_json::Error> for ConvertError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+impl From<toml::de::Error> for ConvertError {
+    fn from(value: toml::de::Error) -> Self {
+        Self::TomlDe(value)
+    }
+}
+
+impl From<toml::ser::Error> for ConvertError {
+    fn from(value: toml::ser::Error) -> Self {
+        Self::TomlSer(value)
+    }
+}
+
+impl From<schema::SchemaError> for ConvertError {
+    fn from(value: schema::SchemaError) -> Self {
+        match value {
+            schema::SchemaError::Csv(err) => Self::Csv(err),
+            err @ schema::SchemaError::Type { .. } => Self::Schema(err),
+        }
+    }
+}
+
+pub fn convert(input: &str, from: Format, to: Format) -> Result<String> {
+    render(&parse(input, from)?, to)
+}
+
+pub fn parse(input: &str, format: Format) -> Result<Value> {
+    match format {
+        Format::Csv => parse_csv(input),
+        Format::Json => Ok(serde_json::from_str(input)?),
+        Format::Toml => Ok(toml_to_json(toml::from_str(input)?)),
+    }
+}
+
+pub fn render(value: &Value, format: Format) -> Result<String> {
+    match format {
+        Format::Csv => render_csv(value),
+        Format::Json => Ok(serde_json::to_string_pretty(value)?),
+        Format::Toml => match json_to_toml(value, "")? {
+            toml::Value::Table(table) => Ok(toml::to_string_pretty(&table)?),
+            _ => Err(ConvertError::Unrepresentable {
+                format: Format::Toml,
+                path: String::new(),
+                reason: "a TOML document must be a table, wrap the value in an object",
+            }),
+        },
+    }
+}
+
+/// CSV的每一行变成一个对象，借助schema推断把数字和布尔值还原成对应的JSON类型，空单元格变成null
+fn parse_csv(input: &str) -> Result<Value> {
+    let schema = infer_schema(input.as_bytes(), usize::MAX)?;
+    let rows = schema
+        .typed_rows(input.as_bytes())
+        .map(|row| {
+            let row = row?;
+            let object = schema
+                .columns
+                .iter()
+                .zip(row)
+                .map(|(column, value)| (column.name.clone(), typed_to_json(value)))
+                .collect();
+            Ok(Value::Object(object))
+        })
+        .collect::<Result<_>>()?;
+    Ok(Value::Array(rows))
+}
+
+fn typed_to_json(value: schema::Value) -> Value {
+    match value {
+        schema::Value::Null => Value::Null,
+        schema::Value::Int(v) => Value::from(v),
+        schema::Value::Float(v) => Value::from(v),
+        schema::Value::Bool(v) => Value::Bool(v),
+        schema::Value::Date(v) => Value::String(v.to_string()),
+        schema::Value::String(v) => Value::String(v),
+    }
+}
+
+/// 只接受由对象组成的数组（所有对象key的并集作为header）或者由数组组成的数组（没有header）
+fn render_csv(value: &Value) -> Result<String> {
+    let unrepresentable = |path: String, reason| ConvertError::Unrepresentable {
+        format: Format::Csv,
+        path,
+        reason,
+    };
+    let rows = value
+        .as_array()
+        .ok_or_else(|| unrepresentable(String::new(), "CSV needs an array of rows"))?;
+
+    // 第一行决定所有行的形状
+    let objects = matches!(rows.first(), Some(Value::Object(_)));
+    let mut headers: Vec<&str> = Vec::new();
+    for (i, row) in rows.iter().enumerate() {
+        match row {
+            Value::Object(object) if objects => {
+                for key in object.keys() {
+                    if !headers.contains(&key.as_str()) {
+                        headers.push(key);
+                    }
+                }
+            }
+            Value::Array(_) if !objects => {}
+            _ => {
+                return Err(unrepresentable(
+                    format!("/{}", i),
+                    "every row must be an object (or every row an array)",
+                ));
+            }
+        }
+    }
+
+    let mut wtr = csv::WriterBuilder::new()
+        .flexible(true)
+        .from_writer(Vec::new());
+    if !headers.is_empty() {
+        wtr.write_record(&headers)?;
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let cells: Vec<(String, Option<&Value>)> = match row {
+            Value::Object(object) => headers
+                .iter()
+                .map(|h| (format!("/{}/{}", i, escape_pointer(h)), object.get(*h)))
+                .collect(),
+            Value::Array(cells) => cells
+                .iter()
+                .enumerate()
+                .map(|(j, cell)| (format!("/{}/{}", i, j), Some(cell)))
+                .collect(),
+            _ => unreachable!("Rows were checked above"),
+        };
+        let record = cells
+            .into_iter()
+            .map(|(path, cell)| match cell {
+                None | Some(Value::Null) => Ok(String::new()),
+                Some(Value::String(s)) => Ok(s.clone()),
+                Some(Value::Bool(b)) => Ok(b.to_string()),
+                Some(Value::Number(n)) => Ok(n.to_string()),
+                Some(Value::Array(_)) | Some(Value::Object(_)) => Err(unrepresentable(
+                    path,
+                    "CSV cells cannot contain nested arrays or objects",
+                )),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        wtr.write_record(&record)?;
+    }
+
+    let bytes = wtr
+        .into_inner()
+        .map_err(|e| ConvertError::Csv(e.into_error().into()))?;
+    Ok(String::from_utf8(bytes).expect("CSV writer only received valid UTF-8"))
+}
+
+/// TOML的日期时间类型在JSON中没有对应，转换成字符串
+fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Number::from_f64(f).map_or(Value::Null, Value::Number),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(dt) => Value::String(dt.to_string()),
+        toml::Value::Array(array) => Value::Array(array.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(
+            table
+                .into_iter()
+                .map(|(k, v)| (k, toml_to_json(v)))
+                .collect::<Map<_, _>>(),
+        ),
+    }
+}
+
+fn json_to_toml(value: &Value, path: &str) -> Result<toml::Value> {
+    let unrepresentable = |reason| ConvertError::Unrepresentable {
+        format: Format::Toml,
+        path: path.to_string(),
+        reason,
+    };
+    Ok(match value {
+        Value::Null => return Err(unrepresentable("TOML has no null value")),
+        Value::Bool(b) => toml::Value::Boolean(*b),
+        Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+            (Some(i), _) => toml::Value::Integer(i),
+            (None, Some(_)) if n.is_u64() => {
+                return Err(unrepresentable(
+                    "TOML integers are limited to 64 bit signed",
+                ));
+            }
+            (None, Some(f)) => toml::Value::Float(f),
+            (None, None) => return Err(unrepresentable("number out of range")),
+        },
+        Value::String(s) => toml::Value::String(s.clone()),
+        Value::Array(array) => toml::Value::Array(
+            array
+                .iter()
+                .enumerate()
+                .map(|(i, v)| json_to_toml(v, &format!("{}/{}", path, i)))
+                .collect::<Result<_>>()?,
+        ),
+        Value::Object(object) => toml::Value::Table(
+            object
+                .iter()
+                .map(|(k, v)| {
+                    let path = format!("{}/{}", path, escape_pointer(k));
+                    Ok((k.clone(), json_to_toml(v, &path)?))
+                })
+                .collect::<Result<_>>()?,
+        ),
+    })
+}
+
+/// JSON Pointer（RFC 6901）中 ~ 和 / 需要转义
+fn escape_pointer(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn csv_rows_become_typed_objects() {
+        let csv = "name,radius,moons\nEarth,1,true\nMars,0.53,\n";
+        let value = parse(csv, Format::Csv).expect("Failed to parse CSV");
+        assert_eq!(
+            json!([
+                { "name": "Earth", "radius": 1.0, "moons": true },
+                { "name": "Mars", "radius": 0.53, "moons": null },
+            ]),
+            value
+        );
+        let back = render(&value, Format::Csv).expect("Failed to render CSV");
+        assert_eq!("name,radius,moons\nEarth,1.0,true\nMars,0.53,\n", back);
+    }
+
+    #[test]
+    fn json_becomes_toml_tables() {
+        let json = r#"{ "person": { "name": "Jan", "langs": ["en-GB", "de-CH"] } }"#;
+        let toml = convert(json, Format::Json, Format::Toml).expect("Failed to convert");
+        assert_eq!(
+            json!({ "person": { "name": "Jan", "langs": ["en-GB", "de-CH"] } }),
+            parse(&toml, Format::Toml).expect("Failed to parse TOML")
+        );
+    }
+
+    #[test]
+    fn reports_unrepresentable_values_with_their_path() {
+        let err = render(&json!({ "pets": [{ "age": null }] }), Format::Toml)
+            .expect_err("TOML has no null");
+        assert_eq!(
+            "Cannot represent /pets/0/age in TOML: TOML has no null value",
+            err.to_string()
+        );
+
+        let err = render(&json!([{ "name": "John", "pets": ["Waldo"] }]), Format::Csv)
+            .expect_err("CSV has no nested arrays");
+        assert_eq!(
+            "Cannot represent /0/pets in CSV: CSV cells cannot contain nested arrays or objects",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn rows_must_all_have_the_shape_of_the_first_row() {
+        for rows in [
+            json!([{ "name": "John" }, ["Jan"]]),
+            json!([["Jan"], { "name": "John" }]),
+        ] {
+            let err = render(&rows, Format::Csv).expect_err("Mixed rows are not a table");
+            assert_eq!(
+                "Cannot represent /1 in CSV: every row must be an object (or every row an array)",
+                err.to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    result,
+    str::FromStr,
+};
+
+use toml_edit::{DocumentMut, Item, Key, RawString, Table, TableLike, TomlError, Value};
+
+/// toml.rs 中的 write_toml 每次都从结构体重新生成 preferences.toml，用户添加的注释、空行和键的顺序都会丢失
+///
+/// toml crate 把文档解析成只包含数据的 toml::Value，而 toml_edit 保留了文档的格式：
+/// 每个键和值都带着它前后的空白和注释（decor），修改一个值时其它部分原样输出
+///
+/// 键是用 . 分隔的路径，和TOML中的点分隔键语法相同，所以带 . 的键需要加引号：`servers."alpha.example"`
+#[derive(Debug, Clone)]
+pub struct TomlEditor {
+    document: DocumentMut,
+}
+
+#[derive(Debug)]
+pub enum EditError {
+    Parse(TomlError),
+    InvalidKey {
+        key: String,
+        message: String,
+    },
+    /// 路径上的某个值不是表，无法在其中查找或者创建键
+    NotATable(String),
+    NotAnArray(String),
+    NotFound(String),
+}
+
+pub type Result<T> = result::Result<T, EditError>;
+
+impl error::Error for EditError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Parse(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Parse(ref err) => write!(f, "Invalid TOML: {}", err),
+            Self::InvalidKey {
+                ref key,
+                ref message,
+            } => write!(f, "Invalid key '{}': {}", key, message),
+            Self::NotATable(ref key) => write!(f, "'{}' is not a table", key),
+            Self::NotAnArray(ref key) => write!(f, "'{}' is not an array", key),
+            Self::NotFound(ref key) => write!(f, "'{}' does not exist", key),
+        }
+    }
+}
+
+impl From<TomlError> for EditError {
+    fn from(err: TomlError) -> Self {
+        Self::Parse(err)
+    }
+}
+
+impl FromStr for TomlEditor {
+    type Err = EditError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self {
+            document: s.parse()?,
+        })
+    }
+}
+
+/// 输出修改后的文档，没有修改的部分和输入逐字节相同
+impl Display for TomlEditor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.document)
+    }
+}
+
+impl TomlEditor {
+    pub fn get(&self, key: &str) -> Result<Option<&Item>> {
+        let keys = parse_key(key)?;
+        let (last, parents) = keys.split_last().expect("A key has at least one segment");
+        let mut current: &dyn TableLike = self.document.as_table();
+        for (depth, parent) in parents.iter().enumerate() {
+            current = match current.get(parent.get()) {
+                Some(item) => item
+                    .as_table_like()
+                    .ok_or_else(|| EditError::NotATable(join(&keys[..=depth])))?,
+                None => return Ok(None),
+            };
+        }
+        Ok(current.get(last.get()))
+    }
+
+    /// 设置一个值，不存在的表会被创建
+    /// 替换已有的值时保留它原来的注释，例如 `public_email = true # shown on the profile`
+    pub fn set(&mut self, key: &str, value: Value) -> Result<()> {
+        let keys = parse_key(key)?;
+        let (last, parents) = keys.split_last().expect("A key has at least one segment");
+        let table = self.table_mut(&keys, parents.len())?;
+        let mut value = value;
+        match table.get_mut(last.get()) {
+            Some(Item::Value(old)) => {
+                *value.decor_mut() = old.decor().clone();
+                *old = value;
+            }
+            Some(Item::None) | None => {
+                value.decor_mut().clear();
+                table.insert(last.get(), Item::Value(value));
+            }
+            Some(_) => {
+                return Err(EditError::InvalidKey {
+                    key: key.to_string(),
+                    message: "cannot replace a table with a value".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// 在数组末尾追加一个值，不存在的数组会被创建
+    /// 新元素沿用最后一个元素的缩进，所以多行数组仍然是每行一个元素
+    pub fn push(&mut self, key: &str, value: Value) -> Result<()> {
+        let keys = parse_key(key)?;
+        let (last, parents) = keys.split_last().expect("A key has at least one segment");
+        let table = self.table_mut(&keys, parents.len())?;
+        let item = table
+            .entry(last.get())
+            .or_insert_with(|| Item::Value(Value::Array(Default::default())));
+        let array = item
+            .as_array_mut()
+            .ok_or_else(|| EditError::NotAnArray(key.to_string()))?;
+        let mut value = value;
+        match array.iter().last() {
+            Some(previous) => {
+                *value.decor_mut() = previous.decor().clone();
+                // 多行数组中最后一个元素后面的注释保存在数组的 trailing 中，直接追加会让它跑到新元素后面
+                // 所以把 trailing 中最后一个换行之前的部分移到新元素的前缀中
+                let prefix = previous.decor().prefix().and_then(RawString::as_str);
+                let prefix = prefix.unwrap_or("").to_string();
+                let trailing = array.trailing().as_str().unwrap_or("").to_string();
+                match (prefix.rfind('\n'), trailing.rfind('\n')) {
+                    (Some(indent), Some(newline)) => {
+                        let prefix = format!("{}{}", &trailing[..=newline], &prefix[indent + 1..]);
+                        value.decor_mut().set_prefix(prefix);
+                        array.set_trailing(&trailing[newline..]);
+                    }
+                    // 单行数组的第一个元素前面通常没有空格，后面的元素前面有一个
+                    (None, _) if array.len() == 1 => value.decor_mut().set_prefix(" "),
+                    _ => {}
+                }
+            }
+            None => value.decor_mut().clear(),
+        }
+        array.push_formatted(value);
+        if array.len() == 1 {
+            array.fmt();
+        }
+        Ok(())
+    }
+
+    /// 删除一个键并返回它原来的值
+    pub fn remove(&mut self, key: &str) -> Result<Item> {
+        let keys = parse_key(key)?;
+        let (last, parents) = keys.split_last().expect("A key has at least one segment");
+        let table = self.table_mut(&keys, parents.len())?;
+        table
+            .remove(last.get())
+            .ok_or_else(|| EditError::NotFound(key.to_string()))
+    }
+
+    /// keys 的前 depth 段指向的表，不存在的表会被创建
+    /// 新建的表是隐式的：如果它只包含子表，就不会输出一个空的 [header]
+    fn table_mut(&mut self, keys: &[Key], depth: usize) -> Result<&mut dyn TableLike> {
+        let mut current: &mut dyn TableLike = self.document.as_table_mut();
+        for (i, key) in keys[..depth].iter().enumerate() {
+            if current.get(key.get()).is_none() {
+                let mut table = Table::new();
+                table.set_implicit(true);
+                current.insert(key.get(), Item::Table(table));
+            }
+            current = current
+                .get_mut(key.get())
+                .and_then(Item::as_table_like_mut)
+                .ok_or_else(|| EditError::NotATable(join(&keys[..=i])))?;
+        }
+        Ok(current)
+    }
+}
+
+fn parse_key(key: &str) -> Result<Vec<Key>> {
+    let keys = Key::parse(key).map_err(|err| EditError::InvalidKey {
+        key: key.to_string(),
+        message: err.to_string().trim().to_string(),
+    })?;
+    if keys.is_empty() {
+        return Err(EditError::InvalidKey {
+            key: key.to_string(),
+            message: "the key is empty".to_string(),
+        });
+    }
+    Ok(keys)
+}
+
+fn join(keys: &[Key]) -> String {
+    keys.iter()
+        .map(|key| key.display_repr().into_owned())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// 把命令行中的字符串转换成TOML的值：能按TOML解析的就按TOML解析，否则作为字符串
+/// 所以 false、42、["en-GB"] 和 "quoted" 都能得到对应的类型，而 Jan 会被当成字符串
+pub fn parse_value(raw: &str) -> Value {
+    raw.parse().unwrap_or_else(|_| Value::from(raw))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PREFERENCES: &str = r#"# Preferences for Jan
+[person]
+name = "Jan Nils Ferner"   # full name
+email = "jn_ferner@hotmail.de"
+
+[language]
+display = "en-GB"
+autocorrect = [
+    "en-GB",
+    "en-US", # fallback
+]
+
+[privacy]
+# shown on the profile
+public_email = true # for now
+"#;
+
+    fn editor() -> TomlEditor {
+        PREFERENCES.parse().expect("Failed to parse preferences")
+    }
+
+    #[test]
+    fn set_keeps_comments_and_order() {
+        let mut editor = editor();
+        editor
+            .set("privacy.public_email", parse_value("false"))
+            .unwrap();
+        editor.set("person.name", parse_value("Jan")).unwrap();
+        editor
+            .set("privacy.public_name", parse_value("true"))
+            .unwrap();
+        assert_eq!(
+            PREFERENCES
+                .replace("name = \"Jan Nils Ferner\"   #", "name = \"Jan\"   #")
+                .replace("public_email = true #", "public_email = false #")
+                + "public_name = true\n",
+            editor.to_string()
+        );
+        assert_eq!(
+            Some("Jan"),
+            editor.get("person.name").unwrap().and_then(Item::as_str)
+        );
+    }
+
+    #[test]
+    fn push_follows_the_array_layout() {
+        let mut editor = editor();
+        editor
+            .push("language.autocorrect", parse_value("de-CH"))
+            .unwrap();
+        editor
+            .push("servers.alpha.ports", parse_value("8080"))
+            .unwrap();
+        assert_eq!(
+            PREFERENCES.replace(
+                "    \"en-US\", # fallback\n",
+                "    \"en-US\", # fallback\n    \"de-CH\",\n"
+            ) + "\n[servers.alpha]\nports = [8080]\n",
+            editor.to_string()
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        let mut editor = editor();
+        assert!(matches!(
+            editor.set("person.name.first", parse_value("Jan")),
+            Err(EditError::NotATable(key)) if key == "person.name"
+        ));
+        assert!(matches!(
+            editor.push("language.display", parse_value("de-CH")),
+            Err(EditError::NotAnArray(_))
+        ));
+        assert!(matches!(
+            editor.remove("privacy.missing"),
+            Err(EditError::NotFound(_))
+        ));
+        assert!(matches!(
+            editor.set("person.", parse_value("x")),
+            Err(EditError::InvalidKey { .. })
+        ));
+        editor.remove("privacy.public_email").unwrap();
+        assert!(!editor.to_string().contains("public_email"));
+    }
+}
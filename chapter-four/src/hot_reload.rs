@@ -0,0 +1,321 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    result,
+    sync::{Arc, Mutex, PoisonError, RwLock},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use chapter_three::watcher::{Event, WatchHandle, Watcher};
+use log::{error, info, warn};
+
+/// 不重启程序就能生效的配置
+///
+/// 读者通过 get 得到当前配置的一个 Arc 快照，和 chapter-five 的 lazy_static.rs 中的 CLIENTS 一样由 RwLock 保护，
+/// 但是锁只在复制 Arc 的一瞬间被持有，读者拿到快照之后就不会阻塞重新加载，重新加载也不会改变读者手中的快照
+///
+/// 后台线程使用 chapter-three 的 Watcher 监视配置文件所在的目录，文件变化时重新读取并调用 load 解析和验证：
+/// * 成功时把新配置换进去，然后依次调用注册的回调
+/// * 失败时保留旧的配置并记录一条错误日志，所以写错一个字符不会让正在运行的服务崩溃
+pub struct HotConfig<T> {
+    shared: Arc<Shared<T>>,
+    watch: Option<WatchHandle>,
+    thread: Option<JoinHandle<()>>,
+}
+
+type Loader<T> = Box<dyn Fn(&str) -> result::Result<T, String> + Send + Sync>;
+type Callback<T> = Arc<dyn Fn(&T, &T) + Send + Sync>;
+
+struct Shared<T> {
+    path: PathBuf,
+    current: RwLock<Arc<T>>,
+    load: Loader<T>,
+    callbacks: Mutex<Vec<Callback<T>>>,
+    /// 监视线程和手动的 reload 可能同时重新加载，读取和替换必须一起完成，
+    /// 否则先读到的旧内容可能在后读到的新内容之后被换进去
+    reloading: Mutex<()>,
+}
+
+#[derive(Debug)]
+pub enum ReloadError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// load 返回的错误信息
+    Invalid {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+pub type Result<T> = result::Result<T, ReloadError>;
+
+impl error::Error for ReloadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Io { ref source, .. } => Some(source),
+            Self::Invalid { .. } => None,
+        }
+    }
+}
+
+impl Display for ReloadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Io {
+                ref path,
+                ref source,
+            } => write!(f, "Failed to read {}: {}", path.display(), source),
+            Self::Invalid {
+                ref path,
+                ref message,
+            } => write!(
+                f,
+                "Invalid configuration in {}: {}",
+                path.display(),
+                message
+            ),
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> HotConfig<T> {
+    /// 读取配置文件并开始监视它，第一次加载失败时直接返回错误，因为此时还没有可以保留的旧配置
+    ///
+    /// 文件每隔 interval 被检查一次；为了不读到写了一半的文件，变化之后要再安静 interval 这么久才会重新加载
+    pub fn watch<P, F, E>(path: P, interval: Duration, load: F) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        F: Fn(&str) -> result::Result<T, E> + Send + Sync + 'static,
+        E: Display,
+    {
+        let path = path.as_ref().to_path_buf();
+        // 监视整个目录而不是文件本身：很多编辑器保存时先写一个临时文件再重命名，原来的文件会消失
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let file_name = path.file_name().map(ToOwned::to_owned);
+        // 先开始监视再读取，这样读取之后的任何修改都不会被漏掉
+        let (events, watch) = Watcher::new(directory)
+            .max_depth(1)
+            .interval(interval)
+            .debounce(interval)
+            .spawn();
+
+        let load: Loader<T> = Box::new(move |input| load(input).map_err(|e| e.to_string()));
+        let initial = read(&path, &load)?;
+        let shared = Arc::new(Shared {
+            path,
+            current: RwLock::new(Arc::new(initial)),
+            load,
+            callbacks: Mutex::new(Vec::new()),
+            reloading: Mutex::new(()),
+        });
+        let thread = {
+            let shared = Arc::clone(&shared);
+            thread::spawn(move || {
+                // 监视器停止时会关闭管道，这个循环随之结束
+                for event in events {
+                    let path = match event {
+                        Event::Created(path) | Event::Modified(path) => path,
+                        Event::Renamed { to, .. } => to,
+                        Event::Removed(path) => {
+                            if path.file_name() == file_name.as_deref() {
+                                warn!(
+                                    "{} was removed, keeping the current configuration",
+                                    shared.path.display()
+                                );
+                            }
+                            continue;
+                        }
+                    };
+                    if path.file_name() != file_name.as_deref() {
+                        continue;
+                    }
+                    match shared.reload() {
+                        Ok(()) => info!("Reloaded {}", shared.path.display()),
+                        Err(e) => error!("Keeping the current configuration: {}", e),
+                    }
+                }
+            })
+        };
+
+        Ok(Self {
+            shared,
+            watch: Some(watch),
+            thread: Some(thread),
+        })
+    }
+
+    /// 当前配置的快照
+    pub fn get(&self) -> Arc<T> {
+        Arc::clone(
+            &self
+                .shared
+                .current
+                .read()
+                .expect("Failed to lock the configuration for reading"),
+        )
+    }
+
+    /// 注册一个回调，每次成功重新加载之后以 (旧配置, 新配置) 调用，回调在监视线程中执行
+    /// 回调被调用时不持有任何锁，所以可以在回调中调用 get、on_change 甚至 reload
+    pub fn on_change<F>(&self, callback: F)
+    where
+        F: Fn(&T, &T) + Send + Sync + 'static,
+    {
+        self.shared
+            .callbacks
+            .lock()
+            .expect("Failed to lock the callbacks")
+            .push(Arc::new(callback));
+    }
+
+    /// 立刻重新加载，不等待监视器发现变化，例如收到 SIGHUP 的时候
+    pub fn reload(&self) -> Result<()> {
+        self.shared.reload()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.shared.path
+    }
+}
+
+impl<T> Shared<T> {
+    fn reload(&self) -> Result<()> {
+        let (old, new) = {
+            let _reloading = self
+                .reloading
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            let new = Arc::new(read(&self.path, &self.load)?);
+            let old = std::mem::replace(
+                &mut *self
+                    .current
+                    .write()
+                    .expect("Failed to lock the configuration for writing"),
+                Arc::clone(&new),
+            );
+            (old, new)
+        };
+        // 复制回调列表之后立刻释放锁，回调中调用 on_change 不会死锁
+        let callbacks = self
+            .callbacks
+            .lock()
+            .expect("Failed to lock the callbacks")
+            .clone();
+        for callback in callbacks {
+            callback(&old, &new);
+        }
+        Ok(())
+    }
+}
+
+fn read<T>(path: &Path, load: &Loader<T>) -> Result<T> {
+    let input = fs::read_to_string(path).map_err(|source| ReloadError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    load(&input).map_err(|message| ReloadError::Invalid {
+        path: path.to_path_buf(),
+        message,
+    })
+}
+
+/// 先停止监视器，它会关闭管道，然后等待处理事件的线程退出
+impl<T> Drop for HotConfig<T> {
+    fn drop(&mut self) {
+        if let Some(watch) = self.watch.take() {
+            watch.stop();
+        }
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("The reload thread panicked");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, sync::mpsc::channel};
+
+    use super::*;
+    use crate::preferences::Preferences;
+
+    #[test]
+    fn reloads_valid_changes_and_keeps_the_old_config_otherwise() {
+        let dir = env::temp_dir().join(format!("hot-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("preferences.toml");
+        let mut preferences = Preferences::default();
+        fs::write(&path, toml::to_string(&preferences).unwrap()).unwrap();
+
+        let config = HotConfig::watch(&path, Duration::from_millis(20), |input| {
+            toml::from_str::<Preferences>(input)
+        })
+        .expect("Failed to load initial preferences");
+        let (tx, rx) = channel();
+        config.on_change(move |old: &Preferences, new: &Preferences| {
+            tx.send((old.person.name.clone(), new.person.name.clone()))
+                .unwrap()
+        });
+        let before = config.get();
+
+        preferences.person.name = "Jan Nils Ferner".to_string();
+        fs::write(&path, toml::to_string(&preferences).unwrap()).unwrap();
+        let change = rx
+            .recv_timeout(Duration::from_secs(5))
+            .expect("The change was not noticed");
+        assert_eq!((String::new(), "Jan Nils Ferner".to_string()), change);
+        assert_eq!("Jan Nils Ferner", config.get().person.name);
+        // 旧的快照不受影响
+        assert_eq!("", before.person.name);
+
+        fs::write(&path, "[person]\nname = ").unwrap();
+        assert!(matches!(config.reload(), Err(ReloadError::Invalid { .. })));
+        assert_eq!("Jan Nils Ferner", config.get().person.name);
+        assert!(rx.try_recv().is_err());
+
+        drop(config);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn callbacks_can_register_callbacks() {
+        let dir = env::temp_dir().join(format!("hot-reload-callbacks-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("name.txt");
+        fs::write(&path, "Nils").unwrap();
+
+        // 文件之后不再变化，所以只有下面手动的 reload 会调用回调
+        let config = Arc::new(
+            HotConfig::watch(&path, Duration::from_millis(20), |input| {
+                Ok::<_, String>(input.to_string())
+            })
+            .expect("Failed to load initial name"),
+        );
+        let (tx, rx) = channel();
+        let handle = Arc::downgrade(&config);
+        config.on_change(move |_: &String, _: &String| {
+            let tx = tx.clone();
+            if let Some(config) = handle.upgrade() {
+                config.on_change(move |_: &String, new: &String| tx.send(new.clone()).unwrap());
+            }
+        });
+
+        config.reload().expect("Failed to reload");
+        config.reload().expect("Failed to reload");
+        assert_eq!(
+            "Nils",
+            rx.recv_timeout(Duration::from_secs(5))
+                .expect("The registered callback was not called")
+        );
+
+        drop(config);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
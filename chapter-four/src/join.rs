@@ -0,0 +1,596 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap},
+    env, error,
+    fmt::{self, Display},
+    fs::{self, File, OpenOptions},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::PathBuf,
+    process, result,
+    sync::atomic::{AtomicUsize, Ordering as AtomicOrdering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use csv::StringRecord;
+
+/// 按照一个或多个key列连接两个CSV文件
+///
+/// 两种策略
+/// * Hash：把右边的文件整个读进 HashMap，然后流式读取左边的文件逐行查找。右边的文件要能放进内存，输出顺序和左边的文件一致
+/// * SortMerge：先对两边分别做外部排序（每 chunk_rows 行排序一次写进临时文件，再用堆做多路归并），然后像拉链一样同时前进两边。
+///   内存中只需要保存每个临时文件的当前行，以及右边一组key相同的行，输出按key排序
+///
+/// 输出的列是左边的所有列，加上右边除key之外的列。两边有同名的列时，分别加上 left_prefix 和 right_prefix
+/// 全外连接中只存在于右边的行，左边的key列会用右边的key值填充
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    FullOuter,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    Hash,
+    SortMerge { chunk_rows: usize },
+}
+
+#[derive(Debug)]
+pub enum JoinError {
+    Csv(csv::Error),
+    Io(io::Error),
+    UnknownColumn(String),
+    KeyCountMismatch { left: usize, right: usize },
+}
+
+pub type Result<T> = result::Result<T, JoinError>;
+
+impl error::Error for JoinError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Csv(ref err) => Some(err),
+            Self::Io(ref err) => Some(err),
+            Self::UnknownColumn(_) | Self::KeyCountMismatch { .. } => None,
+        }
+    }
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Csv(ref err) => write!(f, "CSV error: {}", err),
+            Self::Io(ref err) => write!(f, "IO error: {}", err),
+            Self::UnknownColumn(ref column) => write!(f, "Unknown key column '{}'", column),
+            Self::KeyCountMismatch { left, right } => write!(
+                f,
+                "Both sides need the same number of key columns, got {} and {}",
+                left, right
+            ),
+        }
+    }
+}
+
+impl From<csv::Error> for JoinError {
+    fn from(value: csv::Error) -> Self {
+        Self::Csv(value)
+    }
+}
+
+impl From<io::Error> for JoinError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+/// 描述一次连接的builder
+pub struct Join {
+    kind: JoinKind,
+    strategy: Strategy,
+    left_keys: Vec<String>,
+    right_keys: Vec<String>,
+    left_prefix: String,
+    right_prefix: String,
+}
+
+impl Join {
+    /// 两边key列名相同时使用
+    pub fn on(keys: &[&str]) -> Self {
+        Self::on_columns(keys, keys)
+    }
+
+    pub fn on_columns(left_keys: &[&str], right_keys: &[&str]) -> Self {
+        Self {
+            kind: JoinKind::Inner,
+            strategy: Strategy::Hash,
+            left_keys: left_keys.iter().map(|k| k.to_string()).collect(),
+            right_keys: right_keys.iter().map(|k| k.to_string()).collect(),
+            left_prefix: "left.".to_string(),
+            right_prefix: "right.".to_string(),
+        }
+    }
+
+    pub fn kind(mut self, kind: JoinKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn prefixes(mut self, left: &str, right: &str) -> Self {
+        self.left_prefix = left.to_string();
+        self.right_prefix = right.to_string();
+        self
+    }
+
+    /// 执行连接，返回写出的行数
+    pub fn run<L: Read, R: Read, W: Write>(&self, left: L, right: R, out: W) -> Result<usize> {
+        if self.left_keys.len() != self.right_keys.len() {
+            return Err(JoinError::KeyCountMismatch {
+                left: self.left_keys.len(),
+                right: self.right_keys.len(),
+            });
+        }
+        let mut left = csv::Reader::from_reader(left);
+        let mut right = csv::Reader::from_reader(right);
+        let layout = Layout::new(
+            left.headers()?.clone(),
+            right.headers()?.clone(),
+            &self.left_keys,
+            &self.right_keys,
+        )?;
+
+        let mut wtr = csv::Writer::from_writer(out);
+        wtr.write_record(layout.headers(&self.left_prefix, &self.right_prefix))?;
+        let mut emit = |l: Option<&StringRecord>, r: Option<&StringRecord>| -> Result<()> {
+            Ok(wtr.write_record(layout.row(l, r))?)
+        };
+        let rows = match self.strategy {
+            Strategy::Hash => self.hash_join(&layout, left, right, &mut emit)?,
+            Strategy::SortMerge { chunk_rows } => {
+                let left = sorted(left, &layout.left_keys, chunk_rows)?;
+                let right = sorted(right, &layout.right_keys, chunk_rows)?;
+                self.merge_join(left, right, &mut emit)?
+            }
+        };
+        wtr.flush()?;
+        Ok(rows)
+    }
+
+    fn hash_join<L: Read, R: Read>(
+        &self,
+        layout: &Layout,
+        mut left: csv::Reader<L>,
+        mut right: csv::Reader<R>,
+        emit: &mut impl FnMut(Option<&StringRecord>, Option<&StringRecord>) -> Result<()>,
+    ) -> Result<usize> {
+        let mut right_rows = Vec::new();
+        let mut index: HashMap<Vec<String>, Vec<usize>> = HashMap::new();
+        for record in right.records() {
+            let record = record?;
+            index
+                .entry(key_of(&record, &layout.right_keys))
+                .or_default()
+                .push(right_rows.len());
+            right_rows.push(record);
+        }
+        let mut matched = vec![false; right_rows.len()];
+
+        let mut rows = 0;
+        for record in left.records() {
+            let record = record?;
+            match index.get(&key_of(&record, &layout.left_keys)) {
+                Some(matches) => {
+                    for &i in matches {
+                        matched[i] = true;
+                        emit(Some(&record), Some(&right_rows[i]))?;
+                        rows += 1;
+                    }
+                }
+                None if self.kind != JoinKind::Inner => {
+                    emit(Some(&record), None)?;
+                    rows += 1;
+                }
+                None => {}
+            }
+        }
+        if self.kind == JoinKind::FullOuter {
+            for (record, _) in right_rows.iter().zip(matched).filter(|(_, m)| !m) {
+                emit(None, Some(record))?;
+                rows += 1;
+            }
+        }
+        Ok(rows)
+    }
+
+    fn merge_join(
+        &self,
+        mut left: impl Iterator<Item = Result<Keyed>>,
+        mut right: impl Iterator<Item = Result<Keyed>>,
+        emit: &mut impl FnMut(Option<&StringRecord>, Option<&StringRecord>) -> Result<()>,
+    ) -> Result<usize> {
+        let mut rows = 0;
+        let mut l = left.next().transpose()?;
+        let mut r = right.next().transpose()?;
+        loop {
+            let ordering = match (&l, &r) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(l), Some(r)) => l.key.cmp(&r.key),
+            };
+            match ordering {
+                Ordering::Less => {
+                    if self.kind != JoinKind::Inner {
+                        emit(l.as_ref().map(|l| &l.record), None)?;
+                        rows += 1;
+                    }
+                    l = left.next().transpose()?;
+                }
+                Ordering::Greater => {
+                    if self.kind == JoinKind::FullOuter {
+                        emit(None, r.as_ref().map(|r| &r.record))?;
+                        rows += 1;
+                    }
+                    r = right.next().transpose()?;
+                }
+                Ordering::Equal => {
+                    // 把右边key相同的一组行读进内存，和左边key相同的每一行配对
+                    let first = r.take().expect("Both sides are present");
+                    let mut group = vec![first.record];
+                    r = loop {
+                        match right.next().transpose()? {
+                            Some(next) if next.key == first.key => group.push(next.record),
+                            next => break next,
+                        }
+                    };
+                    while let Some(current) = l.take_if(|l| l.key == first.key) {
+                        for record in &group {
+                            emit(Some(&current.record), Some(record))?;
+                            rows += 1;
+                        }
+                        l = left.next().transpose()?;
+                    }
+                }
+            }
+        }
+        Ok(rows)
+    }
+}
+
+/// 输入和输出列之间的对应关系
+struct Layout {
+    left_headers: StringRecord,
+    right_headers: StringRecord,
+    left_keys: Vec<usize>,
+    right_keys: Vec<usize>,
+    /// 右边除key之外的列
+    right_rest: Vec<usize>,
+}
+
+impl Layout {
+    fn new(
+        left_headers: StringRecord,
+        right_headers: StringRecord,
+        left_keys: &[String],
+        right_keys: &[String],
+    ) -> Result<Self> {
+        let position = |headers: &StringRecord, key: &String| {
+            headers
+                .iter()
+                .position(|h| h == key)
+                .ok_or_else(|| JoinError::UnknownColumn(key.clone()))
+        };
+        let left_keys = left_keys
+            .iter()
+            .map(|k| position(&left_headers, k))
+            .collect::<Result<Vec<_>>>()?;
+        let right_keys = right_keys
+            .iter()
+            .map(|k| position(&right_headers, k))
+            .collect::<Result<Vec<_>>>()?;
+        let right_rest = (0..right_headers.len())
+            .filter(|i| !right_keys.contains(i))
+            .collect();
+        Ok(Self {
+            left_headers,
+            right_headers,
+            left_keys,
+            right_keys,
+            right_rest,
+        })
+    }
+
+    fn headers(&self, left_prefix: &str, right_prefix: &str) -> Vec<String> {
+        let right: Vec<_> = self
+            .right_rest
+            .iter()
+            .map(|i| &self.right_headers[*i])
+            .collect();
+        let left: Vec<_> = self.left_headers.iter().collect();
+        let mut headers: Vec<String> = left
+            .iter()
+            .map(|h| match right.contains(h) {
+                true => format!("{}{}", left_prefix, h),
+                false => h.to_string(),
+            })
+            .collect();
+        headers.extend(right.iter().map(|h| match left.contains(h) {
+            true => format!("{}{}", right_prefix, h),
+            false => h.to_string(),
+        }));
+        headers
+    }
+
+    fn row(&self, left: Option<&StringRecord>, right: Option<&StringRecord>) -> Vec<String> {
+        let mut row: Vec<String> = (0..self.left_headers.len())
+            .map(|i| match (left, right) {
+                (Some(left), _) => left.get(i).unwrap_or("").to_string(),
+                (None, Some(right)) => self
+                    .left_keys
+                    .iter()
+                    .position(|k| *k == i)
+                    .and_then(|k| right.get(self.right_keys[k]))
+                    .unwrap_or("")
+                    .to_string(),
+                (None, None) => String::new(),
+            })
+            .collect();
+        row.extend(
+            self.right_rest
+                .iter()
+                .map(|i| right.and_then(|r| r.get(*i)).unwrap_or("").to_string()),
+        );
+        row
+    }
+}
+
+fn key_of(record: &StringRecord, keys: &[usize]) -> Vec<String> {
+    keys.iter()
+        .map(|i| record.get(*i).unwrap_or("").to_string())
+        .collect()
+}
+
+/// 带着排序key的一行
+struct Keyed {
+    key: Vec<String>,
+    record: StringRecord,
+}
+
+/// 外部排序：返回按key排序的迭代器
+/// 输入不超过 chunk_rows 行时直接在内存中排序，否则每 chunk_rows 行写一个有序的临时文件，最后多路归并
+fn sorted<R: Read>(
+    mut rdr: csv::Reader<R>,
+    keys: &[usize],
+    chunk_rows: usize,
+) -> Result<Box<dyn Iterator<Item = Result<Keyed>>>> {
+    let chunk_rows = chunk_rows.max(1);
+    let mut runs = Vec::new();
+    let mut chunk = Vec::new();
+    for record in rdr.records() {
+        let record = record?;
+        chunk.push(Keyed {
+            key: key_of(&record, keys),
+            record,
+        });
+        if chunk.len() == chunk_rows {
+            runs.push(write_run(&mut chunk)?);
+        }
+    }
+    if runs.is_empty() {
+        chunk.sort_by(|a, b| a.key.cmp(&b.key));
+        return Ok(Box::new(chunk.into_iter().map(Ok)));
+    }
+    if !chunk.is_empty() {
+        runs.push(write_run(&mut chunk)?);
+    }
+
+    let mut merge = Merge {
+        readers: Vec::new(),
+        heap: BinaryHeap::new(),
+        keys: keys.to_vec(),
+        _runs: runs,
+    };
+    for run in &merge._runs {
+        let rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(BufReader::new(File::open(&run.0)?));
+        merge.readers.push(rdr);
+    }
+    for i in 0..merge.readers.len() {
+        merge.advance(i)?;
+    }
+    Ok(Box::new(merge))
+}
+
+fn write_run(chunk: &mut Vec<Keyed>) -> Result<TempFile> {
+    chunk.sort_by(|a, b| a.key.cmp(&b.key));
+    let (file, out) = TempFile::create()?;
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(BufWriter::new(out));
+    for keyed in chunk.drain(..) {
+        wtr.write_record(&keyed.record)?;
+    }
+    wtr.flush()?;
+    Ok(file)
+}
+
+/// 被丢弃时自动删除的临时文件
+struct TempFile(PathBuf);
+
+impl TempFile {
+    /// 临时目录是所有用户共享的，别人可以提前在我们要用的路径上放一个文件或者指向其它文件的符号链接
+    /// 所以用 create_new 创建：路径已经存在时失败而不是打开它，然后换一个名字重试
+    fn create() -> io::Result<(Self, File)> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        for _ in 0..100 {
+            let n = COUNTER.fetch_add(1, AtomicOrdering::SeqCst);
+            // 加上纳秒让名字更难预测
+            let nanos = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.subsec_nanos());
+            let path = env::temp_dir().join(format!(
+                "csv-join-{}-{}-{:08x}.csv",
+                process::id(),
+                n,
+                nanos
+            ));
+            match options.open(&path) {
+                Ok(file) => return Ok((Self(path), file)),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "Failed to find an unused temporary file name",
+        ))
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// BinaryHeap是最大堆，所以把比较结果反过来得到最小堆
+struct HeapEntry {
+    keyed: Keyed,
+    run: usize,
+}
+
+impl PartialEq for HeapEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for HeapEntry {}
+
+impl PartialOrd for HeapEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for HeapEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (&other.keyed.key, other.run).cmp(&(&self.keyed.key, self.run))
+    }
+}
+
+struct Merge {
+    readers: Vec<csv::Reader<BufReader<File>>>,
+    heap: BinaryHeap<HeapEntry>,
+    keys: Vec<usize>,
+    // 临时文件需要活得和读取它们的reader一样久
+    _runs: Vec<TempFile>,
+}
+
+impl Merge {
+    fn advance(&mut self, run: usize) -> Result<()> {
+        let mut record = StringRecord::new();
+        if self.readers[run].read_record(&mut record)? {
+            let key = key_of(&record, &self.keys);
+            self.heap.push(HeapEntry {
+                keyed: Keyed { key, record },
+                run,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Merge {
+    type Item = Result<Keyed>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.heap.pop()?;
+        Some(self.advance(entry.run).map(|_| entry.keyed))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLANETS: &str = "name,radius\nMercury,0.38\nEarth,1\nMars,0.53\nVenus,0.95\n";
+    const MOONS: &str = "name,moon,radius\nEarth,Moon,0.27\nMars,Phobos,0.002\nMars,Deimos,0.001\nPluto,Charon,0.09\n";
+
+    fn run(join: Join) -> String {
+        let mut out = Vec::new();
+        join.run(PLANETS.as_bytes(), MOONS.as_bytes(), &mut out)
+            .expect("Failed to join");
+        String::from_utf8(out).expect("Output is not UTF-8")
+    }
+
+    #[test]
+    fn hash_join_keeps_left_order() {
+        assert_eq!(
+            "name,left.radius,moon,right.radius\n\
+             Earth,1,Moon,0.27\n\
+             Mars,0.53,Phobos,0.002\n\
+             Mars,0.53,Deimos,0.001\n",
+            run(Join::on(&["name"]))
+        );
+        assert_eq!(
+            "name,l_radius,moon,r_radius\n\
+             Mercury,0.38,,\n\
+             Earth,1,Moon,0.27\n\
+             Mars,0.53,Phobos,0.002\n\
+             Mars,0.53,Deimos,0.001\n\
+             Venus,0.95,,\n\
+             Pluto,,Charon,0.09\n",
+            run(Join::on(&["name"])
+                .kind(JoinKind::FullOuter)
+                .prefixes("l_", "r_"))
+        );
+    }
+
+    #[test]
+    fn sort_merge_join_spills_to_disk() {
+        let sorted = |chunk_rows| {
+            run(Join::on(&["name"])
+                .kind(JoinKind::FullOuter)
+                .strategy(Strategy::SortMerge { chunk_rows }))
+        };
+        let expected = "name,left.radius,moon,right.radius\n\
+                        Earth,1,Moon,0.27\n\
+                        Mars,0.53,Phobos,0.002\n\
+                        Mars,0.53,Deimos,0.001\n\
+                        Mercury,0.38,,\n\
+                        Pluto,,Charon,0.09\n\
+                        Venus,0.95,,\n";
+        assert_eq!(expected, sorted(1000));
+        assert_eq!(expected, sorted(1));
+    }
+
+    #[test]
+    fn left_join_with_different_key_names() {
+        let mut out = Vec::new();
+        Join::on_columns(&["planet"], &["name"])
+            .kind(JoinKind::Left)
+            .strategy(Strategy::SortMerge { chunk_rows: 2 })
+            .run(
+                "planet\nVenus\nMars\n".as_bytes(),
+                MOONS.as_bytes(),
+                &mut out,
+            )
+            .expect("Failed to join");
+        assert_eq!(
+            "planet,moon,radius\nMars,Phobos,0.002\nMars,Deimos,0.001\nVenus,,\n",
+            String::from_utf8(out).expect("Output is not UTF-8")
+        );
+    }
+}
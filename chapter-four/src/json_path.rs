@@ -0,0 +1,651 @@
+use std::{
+    cmp::Ordering,
+    error,
+    fmt::{self, Display},
+    result,
+    str::FromStr,
+};
+
+use serde_json::Value;
+
+use crate::pointer::Pointer;
+
+/// JSONPath的一个实用子集，在不定义结构体的情况下从文档中取出一组值
+///
+/// | 语法 | 含义 |
+/// | --- | --- |
+/// | `$` | 根节点 |
+/// | `.name` `['name']` | 对象成员 |
+/// | `[0]` `[-1]` | 数组下标，负数从末尾开始 |
+/// | `[1:3]` `[::2]` | 数组切片 |
+/// | `.*` `[*]` | 所有子节点 |
+/// | `[0,2]` `['a','b']` | 多个选择器的并集 |
+/// | `..name` `..*` | 递归下降，在所有后代中选择 |
+/// | `[?(@.age > 10)]` | 过滤，支持 == != < <= > >= && \|\| ! 和括号，只写 `@.colour` 表示存在 |
+///
+/// 每个结果都带着它的JSON Pointer，方便接着用 pointer 模块修改它
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath(Vec<Segment>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Child(Selector),
+    Descendant(Selector),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Selector {
+    Name(String),
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, i64),
+    Wildcard,
+    Union(Vec<Selector>),
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    Exists(Operand),
+    Compare(Operand, CompareOp, Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    /// `@` 开头的路径，相对于当前节点
+    Current(Vec<Selector>),
+    /// `$` 开头的路径，相对于根节点
+    Root(Vec<Selector>),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PathError {
+    pub position: usize,
+    pub message: String,
+}
+
+pub type Result<T> = result::Result<T, PathError>;
+
+impl error::Error for PathError {}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Invalid JSONPath at position {}: {}",
+            self.position, self.message
+        )
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: &str) -> Result<T> {
+        Err(PathError {
+            position: self.pos,
+            message: message.to_string(),
+        })
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.input.len() - trimmed.len();
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        self.skip_whitespace();
+        let found = self.rest().starts_with(s);
+        if found {
+            self.pos += s.len();
+        }
+        found
+    }
+
+    fn expect(&mut self, s: &str) -> Result<()> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", s))
+        }
+    }
+
+    fn take_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let start = self.pos;
+        let len = self.rest().find(|c| !f(c)).unwrap_or(self.rest().len());
+        self.pos += len;
+        &self.input[start..start + len]
+    }
+
+    fn path(&mut self) -> Result<Vec<Segment>> {
+        self.expect("$")?;
+        let mut segments = Vec::new();
+        loop {
+            if self.rest().starts_with("..") {
+                self.pos += 2;
+                let selector = if self.rest().starts_with('[') {
+                    self.bracket()?
+                } else {
+                    self.dot_selector()?
+                };
+                segments.push(Segment::Descendant(selector));
+            } else if self.rest().starts_with('.') {
+                self.pos += 1;
+                segments.push(Segment::Child(self.dot_selector()?));
+            } else if self.rest().starts_with('[') {
+                segments.push(Segment::Child(self.bracket()?));
+            } else if self.rest().trim().is_empty() {
+                return Ok(segments);
+            } else {
+                return self.error("expected '.', '..' or '['");
+            }
+        }
+    }
+
+    fn dot_selector(&mut self) -> Result<Selector> {
+        if self.rest().starts_with('*') {
+            self.pos += 1;
+            return Ok(Selector::Wildcard);
+        }
+        let name = self.take_while(|c| c.is_alphanumeric() || c == '_' || c == '-');
+        if name.is_empty() {
+            return self.error("expected a member name");
+        }
+        Ok(Selector::Name(name.to_string()))
+    }
+
+    fn bracket(&mut self) -> Result<Selector> {
+        self.expect("[")?;
+        if self.eat("?") {
+            self.expect("(")?;
+            let filter = self.or()?;
+            self.expect(")")?;
+            self.expect("]")?;
+            return Ok(Selector::Filter(filter));
+        }
+        let mut selectors = vec![self.bracket_item()?];
+        while self.eat(",") {
+            selectors.push(self.bracket_item()?);
+        }
+        self.expect("]")?;
+        Ok(match selectors.len() {
+            1 => selectors.remove(0),
+            _ => Selector::Union(selectors),
+        })
+    }
+
+    fn bracket_item(&mut self) -> Result<Selector> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('\'') | Some('"') => Ok(Selector::Name(self.string()?)),
+            _ => {
+                let start = self.integer()?;
+                if !self.eat(":") {
+                    return match start {
+                        Some(i) => Ok(Selector::Index(i)),
+                        None => self.error("expected an index, a name or '*'"),
+                    };
+                }
+                let end = self.integer()?;
+                let step = if self.eat(":") { self.integer()? } else { None };
+                match step {
+                    Some(0) => self.error("slice step cannot be 0"),
+                    step => Ok(Selector::Slice(start, end, step.unwrap_or(1))),
+                }
+            }
+        }
+    }
+
+    fn integer(&mut self) -> Result<Option<i64>> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if self.rest().starts_with('-') {
+            self.pos += 1;
+        }
+        let digits = self.take_while(|c| c.is_ascii_digit());
+        if digits.is_empty() {
+            self.pos = start;
+            return Ok(None);
+        }
+        match self.input[start..self.pos].parse() {
+            Ok(i) => Ok(Some(i)),
+            Err(_) => self.error("integer out of range"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let quote = self.peek().expect("Called on a quote");
+        self.pos += 1;
+        let mut value = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => value.push(escaped),
+                    None => break,
+                },
+                c if c == quote => {
+                    self.pos += i + 1;
+                    return Ok(value);
+                }
+                c => value.push(c),
+            }
+        }
+        self.error("unterminated string")
+    }
+
+    fn or(&mut self) -> Result<Filter> {
+        let mut filter = self.and()?;
+        while self.eat("||") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter> {
+        let mut filter = self.unary()?;
+        while self.eat("&&") {
+            filter = Filter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<Filter> {
+        if self.eat("!") {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        if self.eat("(") {
+            let filter = self.or()?;
+            self.expect(")")?;
+            return Ok(filter);
+        }
+        let left = self.operand()?;
+        // 注意先匹配两个字符的运算符
+        let ops = [
+            ("==", CompareOp::Eq),
+            ("!=", CompareOp::Ne),
+            ("<=", CompareOp::Le),
+            (">=", CompareOp::Ge),
+            ("<", CompareOp::Lt),
+            (">", CompareOp::Gt),
+        ];
+        match ops.iter().find(|(s, _)| self.eat(s)) {
+            Some(&(_, op)) => Ok(Filter::Compare(left, op, self.operand()?)),
+            None => Ok(Filter::Exists(left)),
+        }
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('@') => {
+                self.pos += 1;
+                Ok(Operand::Current(self.relative_path()?))
+            }
+            Some('$') => {
+                self.pos += 1;
+                Ok(Operand::Root(self.relative_path()?))
+            }
+            Some('\'') | Some('"') => Ok(Operand::Literal(Value::String(self.string()?))),
+            _ => {
+                let literal = self.take_while(|c| c.is_alphanumeric() || "-+.".contains(c));
+                match serde_json::from_str(literal) {
+                    Ok(value) if !literal.is_empty() => Ok(Operand::Literal(value)),
+                    _ => self.error("expected '@', '$' or a literal"),
+                }
+            }
+        }
+    }
+
+    /// 过滤器中的路径只支持成员名和下标，每一步最多得到一个值
+    fn relative_path(&mut self) -> Result<Vec<Selector>> {
+        let mut selectors = Vec::new();
+        loop {
+            if self.rest().starts_with('.') {
+                self.pos += 1;
+                match self.dot_selector()? {
+                    Selector::Wildcard => {
+                        return self.error("wildcards are not allowed in filters");
+                    }
+                    selector => selectors.push(selector),
+                }
+            } else if self.rest().starts_with('[') {
+                match self.bracket()? {
+                    selector @ (Selector::Name(_) | Selector::Index(_)) => selectors.push(selector),
+                    _ => return self.error("only names and indexes are allowed in filters"),
+                }
+            } else {
+                return Ok(selectors);
+            }
+        }
+    }
+}
+
+impl FromStr for JsonPath {
+    type Err = PathError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser { input: s, pos: 0 };
+        parser.skip_whitespace();
+        parser.path().map(Self)
+    }
+}
+
+/// 一个匹配结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'a> {
+    pub pointer: Pointer,
+    pub value: &'a Value,
+}
+
+impl JsonPath {
+    pub fn query<'a>(&self, document: &'a Value) -> Vec<Match<'a>> {
+        let mut nodes = vec![Match {
+            pointer: Pointer::root(),
+            value: document,
+        }];
+        for segment in &self.0 {
+            let mut next = Vec::new();
+            for node in nodes {
+                match segment {
+                    Segment::Child(selector) => select(selector, &node, document, &mut next),
+                    Segment::Descendant(selector) => {
+                        for descendant in descendants(node) {
+                            select(selector, &descendant, document, &mut next);
+                        }
+                    }
+                }
+            }
+            nodes = next;
+        }
+        nodes
+    }
+}
+
+/// 节点本身和它的所有后代，先序遍历
+fn descendants(node: Match<'_>) -> Vec<Match<'_>> {
+    let mut result = Vec::new();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        let children = children(&node);
+        result.push(node);
+        stack.extend(children.into_iter().rev());
+    }
+    result
+}
+
+fn children<'a>(node: &Match<'a>) -> Vec<Match<'a>> {
+    match node.value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(k, v)| Match {
+                pointer: node.pointer.join(k),
+                value: v,
+            })
+            .collect(),
+        Value::Array(array) => array
+            .iter()
+            .enumerate()
+            .map(|(i, v)| Match {
+                pointer: node.pointer.join(i),
+                value: v,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn select<'a>(selector: &Selector, node: &Match<'a>, root: &'a Value, out: &mut Vec<Match<'a>>) {
+    match (selector, node.value) {
+        (Selector::Name(name), Value::Object(map)) => {
+            if let Some(value) = map.get(name) {
+                out.push(Match {
+                    pointer: node.pointer.join(name),
+                    value,
+                });
+            }
+        }
+        (Selector::Index(i), Value::Array(array)) => {
+            let i = if *i < 0 { array.len() as i64 + i } else { *i };
+            if let Some(value) = usize::try_from(i).ok().and_then(|i| array.get(i)) {
+                out.push(Match {
+                    pointer: node.pointer.join(i),
+                    value,
+                });
+            }
+        }
+        (Selector::Slice(start, end, step), Value::Array(array)) => {
+            let len = array.len() as i64;
+            let normalize = |i: i64| if i < 0 { (len + i).max(0) } else { i.min(len) };
+            let indexes: Vec<i64> = if *step > 0 {
+                let (start, end) = (normalize(start.unwrap_or(0)), normalize(end.unwrap_or(len)));
+                (start..end).step_by(*step as usize).collect()
+            } else {
+                // RFC 9535：步长为负时下界是 -1 而不是 0，这样 end 越过开头时仍然包含下标 0，
+                // start 越过开头时则什么也不选
+                let bound = |i: i64| {
+                    let i = if i < 0 { len + i } else { i };
+                    i.max(-1).min(len - 1)
+                };
+                let start = start.map_or(len - 1, bound);
+                let end = end.map_or(-1, bound);
+                (end + 1..=start)
+                    .rev()
+                    .step_by(step.unsigned_abs() as usize)
+                    .collect()
+            };
+            for i in indexes {
+                out.push(Match {
+                    pointer: node.pointer.join(i),
+                    value: &array[i as usize],
+                });
+            }
+        }
+        (Selector::Wildcard, _) => out.extend(children(node)),
+        (Selector::Union(selectors), _) => {
+            for selector in selectors {
+                select(selector, node, root, out);
+            }
+        }
+        (Selector::Filter(filter), _) => out.extend(
+            children(node)
+                .into_iter()
+                .filter(|child| filter.matches(child.value, root)),
+        ),
+        _ => {}
+    }
+}
+
+impl Filter {
+    fn matches(&self, current: &Value, root: &Value) -> bool {
+        match self {
+            Self::And(a, b) => a.matches(current, root) && b.matches(current, root),
+            Self::Or(a, b) => a.matches(current, root) || b.matches(current, root),
+            Self::Not(f) => !f.matches(current, root),
+            Self::Exists(operand) => operand.resolve(current, root).is_some(),
+            Self::Compare(a, op, b) => {
+                match (a.resolve(current, root), b.resolve(current, root)) {
+                    (Some(a), Some(b)) => compare(a, *op, b),
+                    // 不存在的值只和不等比较成立
+                    (a, b) => *op == CompareOp::Ne && a != b,
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn resolve<'a>(&'a self, current: &'a Value, root: &'a Value) -> Option<&'a Value> {
+        let (start, selectors) = match self {
+            Self::Current(selectors) => (current, selectors),
+            Self::Root(selectors) => (root, selectors),
+            Self::Literal(value) => return Some(value),
+        };
+        selectors
+            .iter()
+            .try_fold(start, |value, selector| match (selector, value) {
+                (Selector::Name(name), Value::Object(map)) => map.get(name),
+                (Selector::Index(i), Value::Array(array)) => {
+                    let i = if *i < 0 { array.len() as i64 + i } else { *i };
+                    usize::try_from(i).ok().and_then(|i| array.get(i))
+                }
+                _ => None,
+            })
+    }
+}
+
+/// 数字按数值比较，字符串按字典序比较，其它类型只支持相等比较
+fn compare(a: &Value, op: CompareOp, b: &Value) -> bool {
+    let ordering = match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ if a == b => Some(Ordering::Equal),
+        _ => None,
+    };
+    match (op, ordering) {
+        (CompareOp::Eq, o) => o == Some(Ordering::Equal),
+        (CompareOp::Ne, o) => o != Some(Ordering::Equal),
+        (CompareOp::Lt, Some(o)) => o == Ordering::Less,
+        (CompareOp::Le, Some(o)) => o != Ordering::Greater,
+        (CompareOp::Gt, Some(o)) => o == Ordering::Greater,
+        (CompareOp::Ge, Some(o)) => o != Ordering::Less,
+        (_, None) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pet_owner() -> Value {
+        json!({
+            "name": "John",
+            "age": 23,
+            "pets": [
+                { "name": "Waldo", "species": "Dog", "age": 2, "colour": null },
+                { "name": "Speedy", "species": "Turtle", "age": 47, "colour": "Green" },
+                { "name": "Meows", "species": "Cat", "age": null, "colour": "Orange" },
+            ]
+        })
+    }
+
+    fn query(path: &str) -> Vec<(String, Value)> {
+        let document = pet_owner();
+        let path: JsonPath = path.parse().expect("Failed to parse path");
+        path.query(&document)
+            .into_iter()
+            .map(|m| (m.pointer.to_string(), m.value.clone()))
+            .collect()
+    }
+
+    fn values(path: &str) -> Vec<Value> {
+        query(path).into_iter().map(|(_, v)| v).collect()
+    }
+
+    #[test]
+    fn selects_children() {
+        assert_eq!(
+            vec![
+                ("/pets/0/name".to_string(), json!("Waldo")),
+                ("/pets/1/name".to_string(), json!("Speedy")),
+                ("/pets/2/name".to_string(), json!("Meows")),
+            ],
+            query("$.pets[*].name")
+        );
+        assert_eq!(vec![json!("Meows")], values("$['pets'][-1].name"));
+        assert_eq!(
+            vec![json!("Waldo"), json!("Meows")],
+            values("$.pets[0,2].name")
+        );
+        assert_eq!(
+            vec![json!("Waldo"), json!("Meows")],
+            values("$.pets[::2].name")
+        );
+        assert_eq!(
+            vec![json!("Meows"), json!("Speedy")],
+            values("$.pets[2:0:-1].name")
+        );
+    }
+
+    #[test]
+    fn negative_steps_clamp_to_before_the_first_element() {
+        assert_eq!(
+            vec![json!("Meows"), json!("Speedy"), json!("Waldo")],
+            values("$.pets[2:-10:-1].name")
+        );
+        assert_eq!(Vec::<Value>::new(), values("$.pets[-10::-1].name"));
+        assert_eq!(
+            vec![json!("Meows"), json!("Speedy"), json!("Waldo")],
+            values("$.pets[10::-1].name")
+        );
+    }
+
+    #[test]
+    fn descends_recursively() {
+        assert_eq!(
+            vec![
+                json!("John"),
+                json!("Waldo"),
+                json!("Speedy"),
+                json!("Meows")
+            ],
+            values("$..name")
+        );
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(vec![json!("Speedy")], values("$.pets[?(@.age > 10)].name"));
+        assert_eq!(
+            vec![json!("Waldo"), json!("Meows")],
+            values("$.pets[?(@.species == 'Dog' || !(@.age <= 100))].name")
+        );
+        assert_eq!(
+            vec![json!("Waldo")],
+            values("$.pets[?(@.age < $.age && @.age)].name")
+        );
+        assert_eq!(
+            vec![json!("Speedy"), json!("Meows")],
+            values("$.pets[?(@.colour != null)].name")
+        );
+    }
+
+    #[test]
+    fn reports_syntax_errors() {
+        let err = "$.pets[?(@.age >)]"
+            .parse::<JsonPath>()
+            .expect_err("Invalid path");
+        assert_eq!(16, err.position);
+        assert!("pets".parse::<JsonPath>().is_err());
+    }
+}
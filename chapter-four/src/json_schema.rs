@@ -0,0 +1,404 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    result,
+};
+
+use regex::Regex;
+use serde_json::Value;
+
+use crate::pointer::Pointer;
+
+/// JSON Schema（draft 2020-12）的一个子集，用一个JSON文档描述另一个JSON文档应该是什么样子
+///
+/// 反序列化失败时serde只报告遇到的第一个错误，而且错误信息基于行列号；
+/// 先用schema验证 Value 可以一次列出所有问题，每个问题都带着它的JSON Pointer
+///
+/// 支持的关键字
+/// * `type`：字符串或者字符串数组，integer 表示没有小数部分的数字
+/// * `properties`、`required`：只对对象生效
+/// * `items`：数组的每个元素都要符合的schema
+/// * `enum`：值必须是其中之一
+/// * `minimum`、`maximum`：只对数字生效，包含边界
+/// * `pattern`：只对字符串生效，正则表达式不是默认锚定的，需要完整匹配时加上 ^ 和 $
+///
+/// 其它关键字（`$schema`、`title`、`description` 等）被忽略
+#[derive(Debug, Clone, Default)]
+pub struct JsonSchema {
+    types: Option<Vec<Type>>,
+    properties: Vec<(String, JsonSchema)>,
+    required: Vec<String>,
+    items: Option<Box<JsonSchema>>,
+    allowed: Option<Vec<Value>>,
+    minimum: Option<f64>,
+    maximum: Option<f64>,
+    pattern: Option<Regex>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+/// schema本身不合法，path 是出错的关键字在schema中的位置
+#[derive(Debug)]
+pub enum SchemaError {
+    Keyword { path: Pointer, message: String },
+    Pattern { path: Pointer, source: regex::Error },
+}
+
+pub type Result<T> = result::Result<T, SchemaError>;
+
+impl error::Error for SchemaError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Keyword { .. } => None,
+            Self::Pattern { ref source, .. } => Some(source),
+        }
+    }
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Keyword {
+                ref path,
+                ref message,
+            } => write!(f, "Invalid schema at '{}': {}", path, message),
+            Self::Pattern {
+                ref path,
+                ref source,
+            } => write!(f, "Invalid pattern at '{}': {}", path, source),
+        }
+    }
+}
+
+/// 文档中不符合schema的一处，path 是它在被验证的文档中的位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub path: Pointer,
+    pub keyword: &'static str,
+    pub message: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}': {}", self.path, self.message)
+    }
+}
+
+impl Type {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "null" => Some(Self::Null),
+            "boolean" => Some(Self::Boolean),
+            "integer" => Some(Self::Integer),
+            "number" => Some(Self::Number),
+            "string" => Some(Self::String),
+            "array" => Some(Self::Array),
+            "object" => Some(Self::Object),
+            _ => None,
+        }
+    }
+
+    fn of(value: &Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(_) => Self::Boolean,
+            Value::Number(n) if n.is_i64() || n.is_u64() => Self::Integer,
+            Value::Number(n) if n.as_f64().is_some_and(|f| f.fract() == 0.0) => Self::Integer,
+            Value::Number(_) => Self::Number,
+            Value::String(_) => Self::String,
+            Value::Array(_) => Self::Array,
+            Value::Object(_) => Self::Object,
+        }
+    }
+
+    /// 整数也是 number
+    fn accepts(self, value: &Value) -> bool {
+        let actual = Self::of(value);
+        actual == self || (self == Self::Number && actual == Self::Integer)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Null => "null",
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::Number => "number",
+            Self::String => "string",
+            Self::Array => "array",
+            Self::Object => "object",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl JsonSchema {
+    /// 检查schema并把它编译成便于验证的结构，例如提前编译正则表达式
+    pub fn compile(schema: &Value) -> Result<Self> {
+        compile(schema, &Pointer::root())
+    }
+
+    pub fn validate(&self, instance: &Value) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        self.validate_at(instance, &Pointer::root(), &mut violations);
+        violations
+    }
+
+    pub fn is_valid(&self, instance: &Value) -> bool {
+        self.validate(instance).is_empty()
+    }
+
+    /// 每个关键字独立检查，所以类型不对的值也会报告其它关键字的问题，只要那个关键字适用于它的实际类型
+    fn validate_at(&self, instance: &Value, path: &Pointer, violations: &mut Vec<Violation>) {
+        let mut violation = |keyword, message| {
+            violations.push(Violation {
+                path: path.clone(),
+                keyword,
+                message,
+            })
+        };
+
+        if let Some(ref types) = self.types
+            && !types.iter().any(|ty| ty.accepts(instance))
+        {
+            let expected: Vec<_> = types.iter().map(Type::to_string).collect();
+            violation(
+                "type",
+                format!(
+                    "expected {}, found {}",
+                    expected.join(" or "),
+                    Type::of(instance)
+                ),
+            );
+        }
+        if let Some(ref allowed) = self.allowed
+            && !allowed.contains(instance)
+        {
+            let allowed: Vec<_> = allowed.iter().map(Value::to_string).collect();
+            violation(
+                "enum",
+                format!("{} is not one of {}", instance, allowed.join(", ")),
+            );
+        }
+
+        match instance {
+            Value::Number(n) => {
+                let n = n.as_f64().expect("serde_json numbers are finite");
+                if let Some(minimum) = self.minimum.filter(|&minimum| n < minimum) {
+                    violation(
+                        "minimum",
+                        format!("{} is less than the minimum of {}", instance, minimum),
+                    );
+                }
+                if let Some(maximum) = self.maximum.filter(|&maximum| n > maximum) {
+                    violation(
+                        "maximum",
+                        format!("{} is greater than the maximum of {}", instance, maximum),
+                    );
+                }
+            }
+            Value::String(s) => {
+                if let Some(pattern) = self.pattern.as_ref().filter(|p| !p.is_match(s)) {
+                    violation(
+                        "pattern",
+                        format!("{} does not match '{}'", instance, pattern),
+                    );
+                }
+            }
+            Value::Array(array) => {
+                if let Some(ref items) = self.items {
+                    for (i, item) in array.iter().enumerate() {
+                        items.validate_at(item, &path.join(i), violations);
+                    }
+                }
+            }
+            Value::Object(map) => {
+                for name in self.required.iter().filter(|name| !map.contains_key(*name)) {
+                    violation("required", format!("missing required property '{}'", name));
+                }
+                for (name, schema) in &self.properties {
+                    if let Some(value) = map.get(name) {
+                        schema.validate_at(value, &path.join(name), violations);
+                    }
+                }
+            }
+            Value::Null | Value::Bool(_) => {}
+        }
+    }
+}
+
+fn compile(schema: &Value, path: &Pointer) -> Result<JsonSchema> {
+    let invalid = |keyword: &str, message: &str| SchemaError::Keyword {
+        path: path.join(keyword),
+        message: message.to_string(),
+    };
+    let Value::Object(schema) = schema else {
+        return Err(SchemaError::Keyword {
+            path: path.clone(),
+            message: "a schema must be an object".to_string(),
+        });
+    };
+
+    let mut compiled = JsonSchema::default();
+    for (keyword, value) in schema {
+        match keyword.as_str() {
+            "type" => {
+                let names =
+                    match value {
+                        Value::String(name) => vec![name.as_str()],
+                        Value::Array(names) => names
+                            .iter()
+                            .map(Value::as_str)
+                            .collect::<Option<_>>()
+                            .ok_or_else(|| invalid(keyword, "type names must be strings"))?,
+                        _ => return Err(invalid(keyword, "expected a string or an array")),
+                    };
+                let types = names
+                    .into_iter()
+                    .map(|name| {
+                        Type::parse(name)
+                            .ok_or_else(|| invalid(keyword, &format!("unknown type '{}'", name)))
+                    })
+                    .collect::<Result<_>>()?;
+                compiled.types = Some(types);
+            }
+            "properties" => {
+                let properties = value
+                    .as_object()
+                    .ok_or_else(|| invalid(keyword, "expected an object"))?;
+                compiled.properties = properties
+                    .iter()
+                    .map(|(name, schema)| {
+                        let schema = compile(schema, &path.join(keyword).join(name))?;
+                        Ok((name.clone(), schema))
+                    })
+                    .collect::<Result<_>>()?;
+            }
+            "required" => {
+                compiled.required = value
+                    .as_array()
+                    .and_then(|names| {
+                        names
+                            .iter()
+                            .map(|name| name.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .ok_or_else(|| invalid(keyword, "expected an array of strings"))?;
+            }
+            "items" => {
+                compiled.items = Some(Box::new(compile(value, &path.join(keyword))?));
+            }
+            "enum" => {
+                let allowed = value
+                    .as_array()
+                    .ok_or_else(|| invalid(keyword, "expected an array"))?;
+                compiled.allowed = Some(allowed.clone());
+            }
+            "minimum" | "maximum" => {
+                let bound = value
+                    .as_f64()
+                    .ok_or_else(|| invalid(keyword, "expected a number"))?;
+                match keyword.as_str() {
+                    "minimum" => compiled.minimum = Some(bound),
+                    _ => compiled.maximum = Some(bound),
+                }
+            }
+            "pattern" => {
+                let pattern = value
+                    .as_str()
+                    .ok_or_else(|| invalid(keyword, "expected a string"))?;
+                let regex = Regex::new(pattern).map_err(|source| SchemaError::Pattern {
+                    path: path.join(keyword),
+                    source,
+                })?;
+                compiled.pattern = Some(regex);
+            }
+            _ => {}
+        }
+    }
+    Ok(compiled)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn violations(schema: Value, instance: Value) -> Vec<String> {
+        JsonSchema::compile(&schema)
+            .expect("Failed to compile schema")
+            .validate(&instance)
+            .iter()
+            .map(Violation::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn checks_each_keyword() {
+        let schema = json!({
+            "type": "object",
+            "required": ["id", "tags"],
+            "properties": {
+                "id": { "type": "integer", "minimum": 1 },
+                "score": { "type": ["number", "null"], "maximum": 10 },
+                "code": { "type": "string", "pattern": "^[A-Z]{3}$" },
+                "tags": { "type": "array", "items": { "enum": ["a", "b"] } }
+            }
+        });
+        assert!(
+            violations(
+                schema.clone(),
+                json!({ "id": 1, "tags": [], "score": null })
+            )
+            .is_empty()
+        );
+        assert_eq!(
+            vec![
+                "'': missing required property 'tags'",
+                "'/id': expected integer, found number",
+                "'/score': 10.5 is greater than the maximum of 10",
+                "'/code': \"abc\" does not match '^[A-Z]{3}$'",
+            ],
+            violations(
+                schema.clone(),
+                json!({ "id": 1.5, "score": 10.5, "code": "abc" })
+            )
+        );
+        assert_eq!(
+            vec![
+                "'/id': 0 is less than the minimum of 1",
+                "'/tags/1': \"c\" is not one of \"a\", \"b\"",
+            ],
+            violations(schema, json!({ "id": 0, "tags": ["a", "c"] }))
+        );
+        assert_eq!(
+            vec!["'': expected object, found array"],
+            violations(json!({ "type": "object", "required": ["id"] }), json!([]))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_schemas() {
+        let err = JsonSchema::compile(&json!({ "properties": { "id": { "type": "int" } } }))
+            .expect_err("Unknown type");
+        assert_eq!(
+            "Invalid schema at '/properties/id/type': unknown type 'int'",
+            err.to_string()
+        );
+        assert!(matches!(
+            JsonSchema::compile(&json!({ "items": { "pattern": "(" } })),
+            Err(SchemaError::Pattern { .. })
+        ));
+    }
+}
@@ -0,0 +1,488 @@
+use std::{
+    collections::BTreeMap,
+    error,
+    fmt::{self, Display},
+    fs, io,
+    path::{Path, PathBuf},
+    result,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+use toml::{Table, Value};
+
+use crate::migrate::{MigrationError, Migrations};
+
+/// 分层配置：后面的层覆盖前面的层
+/// 1. 编译进程序的默认值
+/// 2. 系统配置文件，例如 /etc/app/preferences.toml
+/// 3. 用户配置文件，例如 ~/.config/app/preferences.toml
+/// 4. 环境变量，例如 `APP_PRIVACY__PUBLIC_EMAIL=false`，见 chapter-one 的 env_vars.rs 中关于Twelve-Factor App的说明
+/// 5. 命令行参数，例如 `--set privacy.public_email=false`
+///
+/// 表会被递归合并，所以一层只需要写出它想改变的键；数组和其它值则整个被替换
+/// 每个键最后由哪一层设置被记录下来，这样出现意外的值时可以知道该去改哪里
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    System(PathBuf),
+    User(PathBuf),
+    Env(String),
+    Cli,
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Default => write!(f, "default"),
+            Self::System(ref path) => write!(f, "system file {}", path.display()),
+            Self::User(ref path) => write!(f, "user file {}", path.display()),
+            Self::Env(ref name) => write!(f, "environment variable {}", name),
+            Self::Cli => write!(f, "command line"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LayerError {
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// 某一层不是合法的TOML
+    Parse {
+        source: Source,
+        error: toml::de::Error,
+    },
+    /// 配置文件的格式太旧或者太新，无法迁移到当前版本
+    /// MigrationError 比其它变体大得多，装箱之后每个 Result 都不必为它预留空间
+    Migrate {
+        source: Source,
+        error: Box<MigrationError>,
+    },
+    /// 命令行参数不是 KEY=VALUE 的形式，或者键不合法
+    Override(String),
+    Serialize(toml::ser::Error),
+    /// 合并后的结果无法反序列化成目标类型，例如某一层把布尔值写成了字符串
+    Deserialize(toml::de::Error),
+}
+
+pub type Result<T> = result::Result<T, LayerError>;
+
+impl error::Error for LayerError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Io { ref source, .. } => Some(source),
+            Self::Parse { ref error, .. } => Some(error),
+            Self::Migrate { ref error, .. } => Some(error),
+            Self::Override(_) => None,
+            Self::Serialize(ref err) => Some(err),
+            Self::Deserialize(ref err) => Some(err),
+        }
+    }
+}
+
+impl Display for LayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Io {
+                ref path,
+                ref source,
+            } => write!(f, "Failed to read {}: {}", path.display(), source),
+            Self::Parse {
+                ref source,
+                ref error,
+            } => write!(f, "Invalid TOML in {}: {}", source, error),
+            Self::Migrate {
+                ref source,
+                ref error,
+            } => write!(f, "Failed to migrate {}: {}", source, error),
+            Self::Override(ref msg) => write!(f, "Invalid override: {}", msg),
+            Self::Serialize(ref err) => write!(f, "Failed to serialize defaults: {}", err),
+            Self::Deserialize(ref err) => write!(f, "Invalid configuration: {}", err),
+        }
+    }
+}
+
+/// 按顺序叠加各层，最后用 load 得到结果
+///
+/// ```ignore
+/// let loaded = Layers::new(&Preferences::default())?
+///     .migrations(preferences::migrations())
+///     .system_file("/etc/app/preferences.toml")?
+///     .env("APP_", env::vars())?
+///     .overrides(["privacy.public_email=false"])?
+///     .load::<Preferences>()?;
+/// ```
+#[derive(Debug, Clone)]
+pub struct Layers {
+    merged: Table,
+    provenance: BTreeMap<String, Source>,
+    migrations: Option<Migrations>,
+}
+
+/// 加载的结果，provenance 记录了每个叶子键（用 . 连接的路径）来自哪一层
+#[derive(Debug, Clone)]
+pub struct Loaded<T> {
+    pub value: T,
+    pub provenance: BTreeMap<String, Source>,
+}
+
+impl Layers {
+    pub fn new<T: Serialize>(defaults: &T) -> Result<Self> {
+        let defaults = Table::try_from(defaults).map_err(LayerError::Serialize)?;
+        let mut layers = Self {
+            merged: Table::new(),
+            provenance: BTreeMap::new(),
+            migrations: None,
+        };
+        layers.merge(defaults, &Source::Default);
+        Ok(layers)
+    }
+
+    /// 之后叠加的每个文件在合并之前先升级到当前版本，所以旧格式的系统或用户配置文件仍然可以使用
+    ///
+    /// 和 migrate.rs 一样，没有 version 键的文件被当成版本1，所以迁移必须能处理只写了几个键的文件：
+    /// 文件中没有的键不能被补上，否则这一层会覆盖下面的层
+    pub fn migrations(mut self, migrations: Migrations) -> Self {
+        self.migrations = Some(migrations);
+        self
+    }
+
+    /// 叠加系统配置文件，文件不存在时跳过这一层
+    pub fn system_file<P: AsRef<Path>>(self, path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        self.file(path.clone(), Source::System(path))
+    }
+
+    /// 叠加用户配置文件，文件不存在时跳过这一层
+    pub fn user_file<P: AsRef<Path>>(self, path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        self.file(path.clone(), Source::User(path))
+    }
+
+    /// 系统和用户配置都是可选的，所以文件不存在不是错误
+    fn file(mut self, path: PathBuf, source: Source) -> Result<Self> {
+        let toml = match fs::read_to_string(&path) {
+            Ok(toml) => toml,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(self),
+            Err(source) => return Err(LayerError::Io { path, source }),
+        };
+        let mut table = toml::from_str(&toml).map_err(|error| LayerError::Parse {
+            source: source.clone(),
+            error,
+        })?;
+        if let Some(ref migrations) = self.migrations {
+            migrations
+                .migrate(&mut table)
+                .map_err(|error| LayerError::Migrate {
+                    source: source.clone(),
+                    error: Box::new(error),
+                })?;
+        }
+        self.merge(table, &source);
+        Ok(self)
+    }
+
+    /// 叠加以 prefix 开头的环境变量：去掉前缀后按 `__` 分成路径的各段，再转换成小写
+    /// 所以 `APP_PRIVACY__PUBLIC_EMAIL` 对应 `privacy.public_email`
+    ///
+    /// 名字正好是前缀本身的变量（例如 `APP_`）没有对应的键，会被忽略
+    ///
+    /// 参数不直接读取 env::vars()，这样测试时不需要修改进程的环境变量
+    pub fn env<I>(mut self, prefix: &str, vars: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut vars: Vec<_> = vars
+            .into_iter()
+            .filter_map(|(name, value)| {
+                let key = name.strip_prefix(prefix)?.to_lowercase();
+                if key.is_empty() {
+                    return None;
+                }
+                Some((name, key.split("__").map(str::to_string).collect(), value))
+            })
+            .collect();
+        // env::vars() 的顺序是不确定的，排序后同一个键的冲突总是以相同的方式解决
+        vars.sort();
+        for (name, path, value) in vars {
+            self.set(path, &value, &Source::Env(name))?;
+        }
+        Ok(self)
+    }
+
+    /// 叠加 `KEY=VALUE` 形式的命令行参数，KEY 是用 . 分隔的路径
+    pub fn overrides<I, S>(mut self, overrides: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for item in overrides {
+            let item = item.as_ref();
+            let (key, value) = item.split_once('=').ok_or_else(|| {
+                LayerError::Override(format!("expected KEY=VALUE, found '{}'", item))
+            })?;
+            let path = key.trim().split('.').map(str::to_string).collect();
+            self.set(path, value.trim(), &Source::Cli)?;
+        }
+        Ok(self)
+    }
+
+    pub fn load<T: DeserializeOwned>(self) -> Result<Loaded<T>> {
+        let value = Value::Table(self.merged)
+            .try_into()
+            .map_err(LayerError::Deserialize)?;
+        Ok(Loaded {
+            value,
+            provenance: self.provenance,
+        })
+    }
+
+    /// 环境变量和命令行参数的值都是字符串，需要猜测它的类型
+    /// * 原来的值是字符串时保持字符串，所以 `APP_PERSON__NAME=true` 不会变成布尔值
+    /// * 否则按TOML的值解析，所以 false、42、["en-GB", "de-CH"] 都能得到正确的类型
+    /// * 解析失败时作为字符串，让反序列化报告类型错误
+    fn set(&mut self, path: Vec<String>, raw: &str, source: &Source) -> Result<()> {
+        if path.iter().any(|segment| segment.is_empty()) {
+            return Err(LayerError::Override(format!(
+                "invalid key '{}' from {}",
+                path.join("."),
+                source
+            )));
+        }
+        let (last, parents) = path.split_last().expect("A key has at least one segment");
+        let current_is_string = parents
+            .iter()
+            .try_fold(&self.merged, |table, segment| {
+                table.get(segment)?.as_table()
+            })
+            .and_then(|table| table.get(last))
+            .is_some_and(Value::is_str);
+        let value = match current_is_string {
+            true => Value::String(raw.to_string()),
+            false => parse_value(raw).unwrap_or_else(|| Value::String(raw.to_string())),
+        };
+
+        // 把 a.b.c = value 包装成 { a = { b = { c = value } } } 再合并
+        let mut layer = Table::new();
+        layer.insert(last.clone(), value);
+        for parent in parents.iter().rev() {
+            let mut table = Table::new();
+            table.insert(parent.clone(), Value::Table(layer));
+            layer = table;
+        }
+        self.merge(layer, source);
+        Ok(())
+    }
+
+    fn merge(&mut self, layer: Table, source: &Source) {
+        merge_into(&mut self.merged, layer, "", source, &mut self.provenance);
+    }
+}
+
+fn parse_value(raw: &str) -> Option<Value> {
+    let mut table: Table = toml::from_str(&format!("value = {}", raw)).ok()?;
+    match table.len() {
+        1 => table.remove("value"),
+        _ => None,
+    }
+}
+
+fn merge_into(
+    target: &mut Table,
+    layer: Table,
+    prefix: &str,
+    source: &Source,
+    provenance: &mut BTreeMap<String, Source>,
+) {
+    for (key, value) in layer {
+        let path = match prefix {
+            "" => key.clone(),
+            prefix => format!("{}.{}", prefix, key),
+        };
+        match (target.get_mut(&key), value) {
+            (Some(Value::Table(existing)), Value::Table(table)) => {
+                merge_into(existing, table, &path, source, provenance)
+            }
+            (_, value) => {
+                // 被替换掉的值可能是一个表，它下面的键的来源已经没有意义了
+                let nested = format!("{}.", path);
+                provenance.retain(|key, _| !key.starts_with(&nested));
+                record(&value, &path, source, provenance);
+                target.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record(value: &Value, path: &str, source: &Source, provenance: &mut BTreeMap<String, Source>) {
+    match value {
+        Value::Table(table) => {
+            for (key, value) in table {
+                record(value, &format!("{}.{}", path, key), source, provenance);
+            }
+        }
+        _ => {
+            provenance.insert(path.to_string(), source.clone());
+        }
+    }
+}
+
+/// 用户配置文件的位置：$XDG_CONFIG_HOME/{app}/{file}，没有设置时使用 $HOME/.config/{app}/{file}
+pub fn user_config_path(app: &str, file: &str) -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+    Some(base.join(app).join(file))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+    use crate::preferences::{self, Preferences};
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn later_layers_win_and_are_recorded() {
+        let dir = env::temp_dir().join(format!("layered-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let system = dir.join("system.toml");
+        let user = dir.join("user.toml");
+        fs::write(
+            &system,
+            "[person]\nname = \"Jan\"\n[privacy]\npublic_email = true\npublic_name = true\n",
+        )
+        .unwrap();
+        fs::write(&user, "[autocorrect]\nlocales = [\"de-CH\"]\n").unwrap();
+
+        let loaded = Layers::new(&Preferences::default())
+            .map(|l| l.migrations(preferences::migrations()))
+            .and_then(|l| l.system_file(&system))
+            .and_then(|l| l.user_file(&user))
+            .and_then(|l| l.user_file(dir.join("missing.toml")))
+            .and_then(|l| {
+                l.env(
+                    "APP_",
+                    vars(&[
+                        ("APP_PRIVACY__PUBLIC_EMAIL", "false"),
+                        ("APP_PERSON__EMAIL", "42"),
+                        ("APP_", "ignored"),
+                        ("HOME", "/root"),
+                    ]),
+                )
+            })
+            .and_then(|l| l.overrides(["language.display = de-CH"]))
+            .and_then(Layers::load::<Preferences>)
+            .expect("Failed to load layers");
+        fs::remove_dir_all(&dir).unwrap();
+
+        let preferences = loaded.value;
+        assert_eq!("Jan", preferences.person.name);
+        assert_eq!("42", preferences.person.email);
+        assert_eq!("de-CH", preferences.language.display);
+        assert_eq!(vec!["de-CH"], preferences.autocorrect.locales);
+        assert!(!preferences.privacy.public_email);
+        assert!(preferences.privacy.public_name);
+
+        let source = |key: &str| loaded.provenance[key].to_string();
+        assert_eq!(
+            format!("system file {}", system.display()),
+            source("person.name")
+        );
+        assert_eq!(
+            format!("user file {}", user.display()),
+            source("autocorrect.locales")
+        );
+        assert_eq!(
+            "environment variable APP_PRIVACY__PUBLIC_EMAIL",
+            source("privacy.public_email")
+        );
+        assert_eq!("command line", source("language.display"));
+        assert_eq!("default", source("privacy.share_anonymous_statistics"));
+    }
+
+    #[test]
+    fn migrates_old_files_before_merging() {
+        let dir = env::temp_dir().join(format!("layered-migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let user = dir.join("user.toml");
+        fs::write(&user, "[language]\nautocorrect = [\"de-CH\", \"en-GB\"]\n").unwrap();
+        let future = dir.join("future.toml");
+        fs::write(&future, "version = 3\n").unwrap();
+
+        let layers = Layers::new(&Preferences::default())
+            .unwrap()
+            .migrations(preferences::migrations());
+        let loaded = layers
+            .clone()
+            .user_file(&user)
+            .and_then(Layers::load::<Preferences>)
+            .expect("Failed to load a version 1 file");
+        let future = layers.system_file(&future);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let preferences = loaded.value;
+        assert_eq!(preferences::CURRENT_VERSION, preferences.version);
+        assert!(preferences.autocorrect.enabled);
+        assert_eq!(vec!["de-CH", "en-GB"], preferences.autocorrect.locales);
+        assert_eq!(
+            format!("user file {}", user.display()),
+            loaded.provenance["autocorrect.locales"].to_string()
+        );
+        assert!(matches!(
+            future,
+            Err(LayerError::Migrate {
+                source: Source::System(_),
+                error,
+            }) if matches!(*error, MigrationError::FromTheFuture { .. })
+        ));
+    }
+
+    #[test]
+    fn unversioned_partial_files_keep_the_lower_layers() {
+        let dir = env::temp_dir().join(format!("layered-partial-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let user = dir.join("user.toml");
+        fs::write(&user, "[privacy]\npublic_email = false\n").unwrap();
+
+        let loaded = Layers::new(&Preferences::default())
+            .map(|l| l.migrations(preferences::migrations()))
+            .and_then(|l| l.user_file(&user))
+            .and_then(Layers::load::<Preferences>)
+            .expect("Failed to load a partial file");
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(Preferences::default().autocorrect, loaded.value.autocorrect);
+        assert!(!loaded.value.privacy.public_email);
+        assert_eq!(
+            "default",
+            loaded.provenance["autocorrect.enabled"].to_string()
+        );
+        assert_eq!(
+            "default",
+            loaded.provenance["autocorrect.locales"].to_string()
+        );
+    }
+
+    #[test]
+    fn reports_bad_layers() {
+        let defaults = || Layers::new(&Preferences::default()).unwrap();
+        assert!(matches!(
+            defaults().overrides(["privacy.public_email"]),
+            Err(LayerError::Override(_))
+        ));
+        assert!(matches!(
+            defaults()
+                .env("APP_", vars(&[("APP_PRIVACY__PUBLIC_NAME", "maybe")]))
+                .and_then(Layers::load::<Preferences>),
+            Err(LayerError::Deserialize(_))
+        ));
+    }
+}
@@ -0,0 +1,15 @@
+pub mod convert;
+pub mod edit;
+pub mod hot_reload;
+pub mod join;
+pub mod json_path;
+pub mod json_schema;
+pub mod layered;
+pub mod migrate;
+pub mod ndjson;
+pub mod patch;
+pub mod pointer;
+pub mod preferences;
+pub mod query;
+pub mod schema;
+pub mod validate;
@@ -0,0 +1,398 @@
+use std::{
+    collections::BTreeMap,
+    error,
+    fmt::{self, Display},
+    fs, io,
+    path::Path,
+    result,
+};
+
+use serde::de::DeserializeOwned;
+use toml::{Table, Value};
+use toml_edit::Key;
+
+use crate::edit::{EditError, TomlEditor};
+
+/// 配置的结构会随着程序演进，例如把一个键移到新的表中，旧的配置文件就无法再反序列化了
+///
+/// 解决办法是在文件中记录格式版本，并为每个版本注册一个升级到下一个版本的迁移：
+/// * 迁移在 toml::Table 这一层进行，这样不需要为每个旧版本保留一套结构体
+/// * 版本 n 的文件依次经过 n -> n+1 -> ... -> current 的迁移，每个迁移只需要关心相邻的两个版本
+/// * 没有 version 键的文件被当成版本1，也就是引入版本号之前的格式
+/// * 版本号比程序支持的更新的文件会被拒绝，而不是猜测它的含义，这通常意味着用新版本的程序写入过这个文件
+pub type Migration = fn(&mut Table) -> result::Result<(), String>;
+
+#[derive(Debug, Clone)]
+pub struct Migrations {
+    current: u32,
+    /// 键是迁移前的版本
+    steps: BTreeMap<u32, (&'static str, Migration)>,
+}
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// 无法把迁移的结果写回原来的文档
+    Edit(EditError),
+    InvalidVersion(String),
+    /// 文件来自更新版本的程序
+    FromTheFuture {
+        version: u32,
+        supported: u32,
+    },
+    /// 注册表中缺少从这个版本开始的迁移
+    MissingMigration(u32),
+    Failed {
+        from: u32,
+        message: String,
+    },
+    /// 迁移后的文档仍然无法反序列化
+    Deserialize(toml::de::Error),
+}
+
+pub type Result<T> = result::Result<T, MigrationError>;
+
+impl error::Error for MigrationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Io(ref err) => Some(err),
+            Self::Parse(ref err) => Some(err),
+            Self::Edit(ref err) => Some(err),
+            Self::Deserialize(ref err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Io(ref err) => write!(f, "IO error: {}", err),
+            Self::Parse(ref err) => write!(f, "Invalid TOML: {}", err),
+            Self::Edit(ref err) => write!(f, "Failed to rewrite TOML: {}", err),
+            Self::InvalidVersion(ref found) => {
+                write!(f, "'version' must be a positive integer, found {}", found)
+            }
+            Self::FromTheFuture { version, supported } => write!(
+                f,
+                "The file has version {}, but this program only supports up to version {}",
+                version, supported
+            ),
+            Self::MissingMigration(from) => write!(f, "No migration from version {}", from),
+            Self::Failed { from, ref message } => write!(
+                f,
+                "Migration from version {} to {} failed: {}",
+                from,
+                from + 1,
+                message
+            ),
+            Self::Deserialize(ref err) => write!(f, "Invalid configuration: {}", err),
+        }
+    }
+}
+
+impl From<io::Error> for MigrationError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<EditError> for MigrationError {
+    fn from(err: EditError) -> Self {
+        Self::Edit(err)
+    }
+}
+
+/// 迁移的结果，applied 是实际执行的迁移的描述，为空表示文件已经是最新版本
+#[derive(Debug, Clone)]
+pub struct Migrated<T> {
+    pub value: T,
+    pub document: Table,
+    pub from_version: u32,
+    pub applied: Vec<&'static str>,
+}
+
+impl Migrations {
+    pub fn new(current: u32) -> Self {
+        Self {
+            current,
+            steps: BTreeMap::new(),
+        }
+    }
+
+    /// 注册从 from 升级到 from + 1 的迁移
+    pub fn register(mut self, from: u32, description: &'static str, migration: Migration) -> Self {
+        assert!(
+            from < self.current,
+            "A migration from version {} is never needed, the current version is {}",
+            from,
+            self.current
+        );
+        self.steps.insert(from, (description, migration));
+        self
+    }
+
+    pub fn current(&self) -> u32 {
+        self.current
+    }
+
+    /// 把文档升级到当前版本，返回原来的版本和执行过的迁移
+    pub fn migrate(&self, document: &mut Table) -> Result<(u32, Vec<&'static str>)> {
+        let from_version = version(document)?;
+        if from_version > self.current {
+            return Err(MigrationError::FromTheFuture {
+                version: from_version,
+                supported: self.current,
+            });
+        }
+        let mut applied = Vec::new();
+        for version in from_version..self.current {
+            let (description, migration) = self
+                .steps
+                .get(&version)
+                .ok_or(MigrationError::MissingMigration(version))?;
+            migration(document).map_err(|message| MigrationError::Failed {
+                from: version,
+                message,
+            })?;
+            document.insert(
+                "version".to_string(),
+                Value::Integer(i64::from(version) + 1),
+            );
+            applied.push(*description);
+        }
+        Ok((from_version, applied))
+    }
+
+    /// 解析、迁移，然后反序列化
+    pub fn load<T: DeserializeOwned>(&self, input: &str) -> Result<Migrated<T>> {
+        let mut document: Table = toml::from_str(input).map_err(MigrationError::Parse)?;
+        let (from_version, applied) = self.migrate(&mut document)?;
+        let value = Value::Table(document.clone())
+            .try_into()
+            .map_err(MigrationError::Deserialize)?;
+        Ok(Migrated {
+            value,
+            document,
+            from_version,
+            applied,
+        })
+    }
+
+    /// 读取并迁移一个文件，rewrite 为 true 且执行了迁移时用 [`write_back`] 把结果写回文件
+    ///
+    /// 旧版本的程序无法读取迁移后的文件，所以原来的文件先被复制到 `{path}.v{version}.bak`
+    pub fn load_file<T, P>(&self, path: P, rewrite: bool) -> Result<Migrated<T>>
+    where
+        T: DeserializeOwned,
+        P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let input = fs::read_to_string(path)?;
+        let migrated = self.load(&input)?;
+        if rewrite && !migrated.applied.is_empty() {
+            let mut backup = path.as_os_str().to_owned();
+            backup.push(format!(".v{}.bak", migrated.from_version));
+            fs::write(&backup, &input)?;
+            let output = write_back(&input, &migrated.document)?;
+            fs::write(path, output)?;
+        }
+        Ok(migrated)
+    }
+}
+
+/// 把迁移后的文档写回原来的文本
+///
+/// 迁移在 toml::Table 上进行，直接用 toml::to_string 输出会丢失用户写的注释、空行和键的顺序
+/// 这里比较迁移前后的两个表，只通过 edit.rs 中的 TomlEditor 删除消失的键、设置变化了的值，
+/// 所以没有被迁移碰到的部分原样保留，被替换的值也保留它原来的注释
+pub fn write_back(input: &str, migrated: &Table) -> Result<String> {
+    let original: Table = toml::from_str(input).map_err(MigrationError::Parse)?;
+    let mut editor: TomlEditor = input.parse()?;
+    apply_changes(&mut editor, "", &original, migrated)?;
+    Ok(editor.to_string())
+}
+
+/// 先删除再设置，这样一个键从值变成表或者从表变成值时不会冲突
+fn apply_changes(editor: &mut TomlEditor, prefix: &str, old: &Table, new: &Table) -> Result<()> {
+    let path = |key: &str| {
+        let key = Key::new(key);
+        match prefix {
+            "" => key.display_repr().into_owned(),
+            prefix => format!("{}.{}", prefix, key.display_repr()),
+        }
+    };
+    for key in old.keys().filter(|key| !new.contains_key(*key)) {
+        editor.remove(&path(key))?;
+    }
+    for (key, value) in new {
+        let path = path(key);
+        match (old.get(key), value) {
+            (Some(old), value) if old == value => {}
+            (Some(Value::Table(old)), Value::Table(new)) => apply_changes(editor, &path, old, new)?,
+            (old, Value::Table(new)) => {
+                if old.is_some() {
+                    editor.remove(&path)?;
+                }
+                apply_changes(editor, &path, &Table::new(), new)?
+            }
+            (old, value) => {
+                if let Some(Value::Table(_)) = old {
+                    editor.remove(&path)?;
+                }
+                let value = value
+                    .to_string()
+                    .parse()
+                    .expect("A TOML value is valid TOML");
+                editor.set(&path, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn version(document: &Table) -> Result<u32> {
+    match document.get("version") {
+        None => Ok(1),
+        Some(Value::Integer(version)) => u32::try_from(*version)
+            .ok()
+            .filter(|&version| version > 0)
+            .ok_or_else(|| MigrationError::InvalidVersion(version.to_string())),
+        Some(other) => Err(MigrationError::InvalidVersion(other.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preferences::{self, Preferences};
+
+    const VERSION_1: &str = r#"
+[person]
+name = "Jan Nils Ferner"
+email = "jn_ferner@hotmail.de"
+
+[language]
+display = "en-GB"
+autocorrect = ["en-GB", "de-CH"]
+
+[privacy]
+share_anonymous_statistics = false
+public_name = true
+public_email = true
+"#;
+
+    #[test]
+    fn upgrades_old_preferences() {
+        let migrated = preferences::migrations()
+            .load::<Preferences>(VERSION_1)
+            .expect("Failed to migrate preferences");
+        assert_eq!(1, migrated.from_version);
+        assert_eq!(1, migrated.applied.len());
+        let preferences = migrated.value;
+        assert_eq!(preferences::CURRENT_VERSION, preferences.version);
+        assert!(preferences.autocorrect.enabled);
+        assert_eq!(vec!["en-GB", "de-CH"], preferences.autocorrect.locales);
+
+        // 版本1中没有 language.autocorrect 表示关闭自动更正
+        let without = VERSION_1.replace("autocorrect = [\"en-GB\", \"de-CH\"]\n", "");
+        let migrated = preferences::migrations()
+            .load::<Preferences>(&without)
+            .expect("Failed to migrate preferences without autocorrect");
+        assert!(!migrated.value.autocorrect.enabled);
+        assert!(migrated.value.autocorrect.locales.is_empty());
+
+        // 已经是最新版本的文件不需要迁移
+        let current = toml::to_string(&preferences).unwrap();
+        let migrated = preferences::migrations()
+            .load::<Preferences>(&current)
+            .unwrap();
+        assert!(migrated.applied.is_empty());
+        assert_eq!(preferences, migrated.value);
+    }
+
+    #[test]
+    fn rewriting_keeps_comments() {
+        let input = format!("# Preferences for Jan\n{}", VERSION_1.trim_start()).replace(
+            "name = \"Jan Nils Ferner\"",
+            "name = \"Jan Nils Ferner\" # full name",
+        );
+        let dir = std::env::temp_dir().join(format!("migrate-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("preferences.toml");
+        fs::write(&path, &input).unwrap();
+
+        let migrated = preferences::migrations()
+            .load_file::<Preferences, _>(&path, true)
+            .expect("Failed to migrate preferences");
+        let output = fs::read_to_string(&path).unwrap();
+        let backup = fs::read_to_string(dir.join("preferences.toml.v1.bak")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(input, backup);
+        assert!(output.contains("# Preferences for Jan\n"));
+        assert!(output.contains("name = \"Jan Nils Ferner\" # full name\n"));
+        assert!(!output.contains("autocorrect = ["));
+        assert_eq!(migrated.document, toml::from_str::<Table>(&output).unwrap());
+        assert_eq!(
+            migrated.value,
+            toml::from_str::<Preferences>(&output).unwrap()
+        );
+    }
+
+    #[test]
+    fn runs_each_step_in_order() {
+        fn rename(document: &mut Table) -> result::Result<(), String> {
+            let name = document.remove("name").ok_or("'name' is missing")?;
+            document.insert("title".to_string(), name);
+            Ok(())
+        }
+        fn uppercase(document: &mut Table) -> result::Result<(), String> {
+            if let Some(Value::String(title)) = document.get_mut("title") {
+                *title = title.to_uppercase();
+            }
+            Ok(())
+        }
+        let migrations = Migrations::new(3)
+            .register(2, "uppercase the title", uppercase)
+            .register(1, "rename name to title", rename);
+
+        let mut document: Table = toml::from_str("name = \"waldo\"").unwrap();
+        let (from, applied) = migrations.migrate(&mut document).unwrap();
+        assert_eq!(1, from);
+        assert_eq!(vec!["rename name to title", "uppercase the title"], applied);
+        assert_eq!(
+            toml::from_str::<Table>("version = 3\ntitle = \"WALDO\"").unwrap(),
+            document
+        );
+
+        let mut document: Table = toml::from_str("title = 'x'").unwrap();
+        assert!(matches!(
+            migrations.migrate(&mut document),
+            Err(MigrationError::Failed { from: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn refuses_files_from_the_future() {
+        let mut document: Table = toml::from_str("version = 3").unwrap();
+        assert!(matches!(
+            preferences::migrations().migrate(&mut document),
+            Err(MigrationError::FromTheFuture {
+                version: 3,
+                supported: 2
+            })
+        ));
+        let mut document: Table = toml::from_str("version = \"2\"").unwrap();
+        assert!(matches!(
+            preferences::migrations().migrate(&mut document),
+            Err(MigrationError::InvalidVersion(_))
+        ));
+        let mut document = Table::new();
+        assert!(matches!(
+            Migrations::new(2).migrate(&mut document),
+            Err(MigrationError::MissingMigration(1))
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    io::{self, BufRead, Write},
+    marker::PhantomData,
+    result,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+/// NDJSON（newline-delimited JSON）每行是一个完整的JSON值，例如
+/// ```text
+/// {"name":"Waldo","species":"Dog","age":2}
+/// {"name":"Speedy","species":"Turtle","age":47}
+/// ```
+/// 和一个巨大的JSON数组相比
+/// * 写入时可以直接追加到文件末尾，不需要先读出整个数组
+/// * 读取时一次只需要一行在内存中，适合几百万条记录的导出文件
+/// * 一行损坏只影响这一条记录，读取者可以跳过它继续读后面的行
+#[derive(Debug)]
+pub enum NdjsonError {
+    Io(io::Error),
+    /// 记录无法表示成JSON，例如键不是字符串的 HashMap
+    Serialize(serde_json::Error),
+    /// line 从1开始，是出错的记录所在的行
+    Parse {
+        line: usize,
+        source: serde_json::Error,
+    },
+}
+
+pub type Result<T> = result::Result<T, NdjsonError>;
+
+impl error::Error for NdjsonError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Io(ref err) => Some(err),
+            Self::Serialize(ref err) => Some(err),
+            Self::Parse { ref source, .. } => Some(source),
+        }
+    }
+}
+
+impl Display for NdjsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Io(ref err) => write!(f, "IO error: {}", err),
+            Self::Serialize(ref err) => write!(f, "Failed to serialize record: {}", err),
+            Self::Parse { line, ref source } => {
+                // serde_json 的错误信息以 "at line 1 column N" 结尾，这里的行号总是1，换成文件中的行号
+                let message = source.to_string();
+                let message = message
+                    .rsplit_once(" at line ")
+                    .map_or(message.as_str(), |(message, _)| message);
+                write!(f, "Line {}, column {}: {}", line, source.column(), message)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for NdjsonError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// 每次写入一条记录和一个换行符，需要时用 BufWriter 包装
+/// serde_json 的紧凑格式保证字符串中的换行会被转义，所以一条记录永远只占一行
+pub struct Writer<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// 以追加模式打开的文件可以在每次运行时继续添加记录
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// 先把整行序列化到内存中再一次写出，序列化失败时不会在文件中留下半行
+    pub fn write<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        let mut line = serde_json::to_vec(record).map_err(NdjsonError::Serialize)?;
+        line.push(b'\n');
+        self.writer.write_all(&line)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// 逐行读取记录的迭代器，每一项都是一个 Result
+/// * 一行解析失败时返回带行号的错误，下一次调用 next 会从下一行继续，所以只需要跳过 Err 就能读完剩下的记录
+/// * 空行被忽略
+/// * 读取本身失败（IO错误）时无法知道下一行从哪里开始，迭代结束
+pub struct Reader<R, T> {
+    reader: R,
+    line: usize,
+    buffer: Vec<u8>,
+    done: bool,
+    record: PhantomData<T>,
+}
+
+impl<R: BufRead, T: DeserializeOwned> Reader<R, T> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: 0,
+            buffer: Vec::new(),
+            done: false,
+            record: PhantomData,
+        }
+    }
+
+    /// 最后读取的一行的行号
+    pub fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl<R: BufRead, T: DeserializeOwned> Iterator for Reader<R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.done {
+            self.buffer.clear();
+            // 按字节读取而不是 read_line，这样一行中的非法UTF-8只是这一行的解析错误，不会中断整个读取
+            match self.reader.read_until(b'\n', &mut self.buffer) {
+                Ok(0) => self.done = true,
+                Ok(_) => {
+                    self.line += 1;
+                    if self.buffer.trim_ascii().is_empty() {
+                        continue;
+                    }
+                    return Some(serde_json::from_slice(&self.buffer).map_err(|source| {
+                        NdjsonError::Parse {
+                            line: self.line,
+                            source,
+                        }
+                    }));
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err.into()));
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Pet {
+        name: String,
+        age: Option<u8>,
+    }
+
+    fn pet(name: &str, age: Option<u8>) -> Pet {
+        Pet {
+            name: name.to_string(),
+            age,
+        }
+    }
+
+    #[test]
+    fn writes_one_record_per_line() {
+        let mut writer = Writer::new(Vec::new());
+        writer.write(&pet("Waldo", Some(2))).unwrap();
+        writer.write(&pet("Multi\nline", None)).unwrap();
+        let output = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(
+            "{\"name\":\"Waldo\",\"age\":2}\n{\"name\":\"Multi\\nline\",\"age\":null}\n",
+            output
+        );
+
+        let pets: Vec<Pet> = Reader::new(output.as_bytes())
+            .collect::<Result<_>>()
+            .expect("Failed to read pets");
+        assert_eq!(vec![pet("Waldo", Some(2)), pet("Multi\nline", None)], pets);
+    }
+
+    #[test]
+    fn resumes_after_malformed_lines() {
+        let input = b"{\"name\":\"Waldo\",\"age\":2}\n\
+            {\"name\":\"Speedy\",\n\
+            \n\
+            {\"name\":\"Meows\",\"age\":300}\n\
+            \xff\xfe\n\
+            {\"name\":\"Rex\",\"age\":null}";
+        let mut reader = Reader::<_, Pet>::new(&input[..]);
+        let mut errors = Vec::new();
+        let mut pets = Vec::new();
+        for record in reader.by_ref() {
+            match record {
+                Ok(pet) => pets.push(pet),
+                Err(NdjsonError::Parse { line, .. }) => errors.push(line),
+                Err(err) => panic!("Unexpected error: {}", err),
+            }
+        }
+        assert_eq!(vec![pet("Waldo", Some(2)), pet("Rex", None)], pets);
+        assert_eq!(vec![2, 4, 5], errors);
+        assert_eq!(6, reader.line());
+
+        let mut reader = Reader::<_, Pet>::new(&b"\n{\"name\": ?}"[..]);
+        let err = reader
+            .next()
+            .expect("One record")
+            .expect_err("Malformed record");
+        assert_eq!("Line 2, column 10: expected value", err.to_string());
+        assert_eq!(2, reader.line());
+    }
+}
@@ -0,0 +1,438 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    result,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::pointer::{Pointer, PointerError};
+
+/// JSON Patch（RFC 6902）把两个文档之间的差异描述成一串操作，它本身也是一个JSON文档：
+/// `[{"op": "replace", "path": "/pets/0/age", "value": 3}]`
+///
+/// 借助 `#[serde(tag = "op")]`，每个操作的 op 字段决定了它是枚举的哪个变体
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Add {
+        path: Pointer,
+        value: Value,
+    },
+    Remove {
+        path: Pointer,
+    },
+    Replace {
+        path: Pointer,
+        value: Value,
+    },
+    Move {
+        from: Pointer,
+        path: Pointer,
+    },
+    Copy {
+        from: Pointer,
+        path: Pointer,
+    },
+    /// 值不相等时整个补丁失败，用来确认补丁是基于预期的文档生成的
+    Test {
+        path: Pointer,
+        value: Value,
+    },
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Patch(pub Vec<Operation>);
+
+/// operation 是出错的操作在补丁中的下标，Value 比较大，所以放在 Box 中
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatchError {
+    Pointer {
+        operation: usize,
+        source: PointerError,
+    },
+    TestFailed {
+        operation: usize,
+        path: Pointer,
+        expected: Box<Value>,
+        actual: Option<Box<Value>>,
+    },
+    MoveIntoChild {
+        operation: usize,
+        from: Pointer,
+        path: Pointer,
+    },
+}
+
+pub type Result<T> = result::Result<T, PatchError>;
+
+impl error::Error for PatchError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Pointer { ref source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Pointer {
+                operation,
+                ref source,
+            } => write!(f, "Operation {} failed: {}", operation, source),
+            Self::TestFailed {
+                operation,
+                ref path,
+                ref expected,
+                ref actual,
+            } => match actual {
+                Some(actual) => write!(
+                    f,
+                    "Test operation {} failed: expected {} at '{}', found {}",
+                    operation, expected, path, actual
+                ),
+                None => write!(
+                    f,
+                    "Test operation {} failed: expected {} at '{}', found nothing",
+                    operation, expected, path
+                ),
+            },
+            Self::MoveIntoChild {
+                operation,
+                ref from,
+                ref path,
+            } => write!(
+                f,
+                "Operation {} cannot move '{}' into its own child '{}'",
+                operation, from, path
+            ),
+        }
+    }
+}
+
+impl Patch {
+    /// 依次应用所有操作。补丁是原子的：任何一个操作失败时文档保持原样
+    pub fn apply(&self, document: &mut Value) -> Result<()> {
+        let mut patched = document.clone();
+        for (i, operation) in self.0.iter().enumerate() {
+            operation.apply(&mut patched, i)?;
+        }
+        *document = patched;
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Operation {
+    fn apply(&self, document: &mut Value, index: usize) -> Result<()> {
+        let pointer_error = |source| PatchError::Pointer {
+            operation: index,
+            source,
+        };
+        match self {
+            Self::Add { path, value } => path.add(document, value.clone()).map_err(pointer_error),
+            Self::Remove { path } => path.remove(document).map(drop).map_err(pointer_error),
+            Self::Replace { path, value } => {
+                *path.get_mut(document).map_err(pointer_error)? = value.clone();
+                Ok(())
+            }
+            Self::Move { from, path } => {
+                if from == path {
+                    return Ok(());
+                }
+                if path.tokens().starts_with(from.tokens()) {
+                    return Err(PatchError::MoveIntoChild {
+                        operation: index,
+                        from: from.clone(),
+                        path: path.clone(),
+                    });
+                }
+                let value = from.remove(document).map_err(pointer_error)?;
+                path.add(document, value).map_err(pointer_error)
+            }
+            Self::Copy { from, path } => {
+                let value = from
+                    .get(document)
+                    .cloned()
+                    .ok_or_else(|| pointer_error(PointerError::NotFound(from.to_string())))?;
+                path.add(document, value).map_err(pointer_error)
+            }
+            Self::Test { path, value } => match path.get(document) {
+                Some(actual) if equal(actual, value) => Ok(()),
+                actual => Err(PatchError::TestFailed {
+                    operation: index,
+                    path: path.clone(),
+                    expected: Box::new(value.clone()),
+                    actual: actual.cloned().map(Box::new),
+                }),
+            },
+        }
+    }
+}
+
+/// RFC 6902 规定数字按数值比较，所以 1 和 1.0 相等，而 Value 的 == 认为它们不同
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => a == b || a.as_f64() == b.as_f64(),
+        (Value::Array(a), Value::Array(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+        }
+        (Value::Object(a), Value::Object(b)) => {
+            a.len() == b.len() && a.iter().all(|(k, v)| b.get(k).is_some_and(|w| equal(v, w)))
+        }
+        (a, b) => a == b,
+    }
+}
+
+/// 生成把 from 变成 to 的补丁
+/// * 对象按键比较，递归进入两边都有的成员
+/// * 数组用最长公共子序列对齐，所以在中间插入一个元素只会生成一个 add，而不是替换后面所有元素
+/// * 其它情况直接 replace
+pub fn diff(from: &Value, to: &Value) -> Patch {
+    let mut operations = Vec::new();
+    diff_into(from, to, &Pointer::root(), &mut operations);
+    Patch(operations)
+}
+
+fn diff_into(from: &Value, to: &Value, path: &Pointer, operations: &mut Vec<Operation>) {
+    if from == to {
+        return;
+    }
+    match (from, to) {
+        (Value::Object(from), Value::Object(to)) => {
+            for (key, value) in from {
+                match to.get(key) {
+                    Some(new) => diff_into(value, new, &path.join(key), operations),
+                    None => operations.push(Operation::Remove {
+                        path: path.join(key),
+                    }),
+                }
+            }
+            for (key, value) in to.iter().filter(|(key, _)| !from.contains_key(*key)) {
+                operations.push(Operation::Add {
+                    path: path.join(key),
+                    value: value.clone(),
+                });
+            }
+        }
+        (Value::Array(from), Value::Array(to)) => diff_arrays(from, to, path, operations),
+        _ => operations.push(Operation::Replace {
+            path: path.clone(),
+            value: to.clone(),
+        }),
+    }
+}
+
+fn diff_arrays(from: &[Value], to: &[Value], path: &Pointer, operations: &mut Vec<Operation>) {
+    let (n, m) = (from.len(), to.len());
+    // lcs[i][j] 是 from[i..] 和 to[j..] 的最长公共子序列的长度
+    let mut lcs = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if from[i] == to[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    // k 是当前元素在已经应用了前面操作的数组中的下标
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    while i < n || j < m {
+        if i < n && j < m && from[i] == to[j] {
+            (i, j, k) = (i + 1, j + 1, k + 1);
+        } else if i < n && j < m && lcs[i + 1][j + 1] == lcs[i][j] {
+            // 两个元素都不在公共子序列中，当成同一个元素的修改，这样只会报告真正改变的字段
+            diff_into(&from[i], &to[j], &path.join(k), operations);
+            (i, j, k) = (i + 1, j + 1, k + 1);
+        } else if j < m && (i == n || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            operations.push(Operation::Add {
+                path: path.join(k),
+                value: to[j].clone(),
+            });
+            (j, k) = (j + 1, k + 1);
+        } else {
+            operations.push(Operation::Remove { path: path.join(k) });
+            i += 1;
+        }
+    }
+}
+
+/// JSON Merge Patch（RFC 7386）是一个和目标形状相同的文档：
+/// 对象递归合并，null 表示删除这个成员，其它值直接替换，包括数组
+///
+/// 它比JSON Patch简单易读，代价是无法把一个成员设为 null，也无法只修改数组中的一个元素
+pub fn merge(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    let Value::Object(target) = target else {
+        unreachable!("The target has just been made an object")
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            target.shift_remove(key);
+        } else {
+            merge(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// 生成把 from 变成 to 的merge patch，to 中值为 null 的成员无法表示，会被当成删除
+pub fn merge_diff(from: &Value, to: &Value) -> Value {
+    let (Value::Object(from), Value::Object(to)) = (from, to) else {
+        return to.clone();
+    };
+    let mut patch = Map::new();
+    for key in from.keys().filter(|key| !to.contains_key(*key)) {
+        patch.insert(key.clone(), Value::Null);
+    }
+    for (key, value) in to {
+        match from.get(key) {
+            Some(old) if old == value => {}
+            Some(old) => {
+                patch.insert(key.clone(), merge_diff(old, value));
+            }
+            None => {
+                patch.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Value::Object(patch)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pet_owner() -> Value {
+        json!({
+            "name": "John",
+            "age": 23,
+            "pets": [
+                { "name": "Waldo", "species": "Dog", "age": 2 },
+                { "name": "Speedy", "species": "Turtle", "age": 47 },
+            ]
+        })
+    }
+
+    #[test]
+    fn diffs_into_minimal_operations() {
+        let from = pet_owner();
+        let mut to = pet_owner();
+        to["age"] = json!(24);
+        to["pets"][0]["age"] = json!(3);
+        to["pets"]
+            .as_array_mut()
+            .unwrap()
+            .insert(1, json!({ "name": "Meows", "species": "Cat" }));
+        to.as_object_mut().unwrap().shift_remove("name");
+
+        let patch = diff(&from, &to);
+        let expected: Patch = serde_json::from_value(json!([
+            { "op": "remove", "path": "/name" },
+            { "op": "replace", "path": "/age", "value": 24 },
+            { "op": "replace", "path": "/pets/0/age", "value": 3 },
+            { "op": "add", "path": "/pets/1", "value": { "name": "Meows", "species": "Cat" } },
+        ]))
+        .expect("Failed to parse patch");
+        assert_eq!(expected, patch);
+
+        let mut patched = from.clone();
+        patch.apply(&mut patched).expect("Failed to apply patch");
+        assert_eq!(to, patched);
+        assert!(diff(&to, &patched).is_empty());
+    }
+
+    #[test]
+    fn applies_rfc_6902_operations() {
+        let mut document = json!({ "foo": { "bar": "baz", "waldo": "fred" }, "qux": [1, 2] });
+        let patch: Patch = serde_json::from_str(
+            r#"[
+                { "op": "test", "path": "/qux/1", "value": 2.0 },
+                { "op": "add", "path": "/qux/1", "value": 5 },
+                { "op": "move", "from": "/foo/waldo", "path": "/qux/-" },
+                { "op": "copy", "from": "/foo", "path": "/corge" },
+                { "op": "remove", "path": "/foo/bar" }
+            ]"#,
+        )
+        .expect("Failed to parse patch");
+        patch.apply(&mut document).expect("Failed to apply patch");
+        assert_eq!(
+            json!({ "foo": {}, "qux": [1, 5, 2, "fred"], "corge": { "bar": "baz" } }),
+            document
+        );
+    }
+
+    #[test]
+    fn failed_patches_leave_the_document_unchanged() {
+        let mut document = pet_owner();
+        let patch: Patch = serde_json::from_value(json!([
+            { "op": "replace", "path": "/age", "value": 30 },
+            { "op": "test", "path": "/pets/0/name", "value": "Speedy" },
+        ]))
+        .expect("Failed to parse patch");
+        assert_eq!(
+            Err(PatchError::TestFailed {
+                operation: 1,
+                path: "/pets/0/name".parse().unwrap(),
+                expected: Box::new(json!("Speedy")),
+                actual: Some(Box::new(json!("Waldo"))),
+            }),
+            patch.apply(&mut document)
+        );
+        assert_eq!(pet_owner(), document);
+
+        let patch = Patch(vec![Operation::Move {
+            from: "/pets".parse().unwrap(),
+            path: "/pets/0/friends".parse().unwrap(),
+        }]);
+        assert!(matches!(
+            patch.apply(&mut document),
+            Err(PatchError::MoveIntoChild { operation: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn merges_rfc_7386_patches() {
+        let mut document = json!({
+            "title": "Goodbye!",
+            "author": { "givenName": "John", "familyName": "Doe" },
+            "tags": ["example", "sample"],
+            "content": "This will be unchanged"
+        });
+        let original = document.clone();
+        let patch = json!({
+            "title": "Hello!",
+            "phoneNumber": "+01-123-456-7890",
+            "author": { "familyName": null },
+            "tags": ["example"]
+        });
+        merge(&mut document, &patch);
+        assert_eq!(
+            json!({
+                "title": "Hello!",
+                "author": { "givenName": "John" },
+                "tags": ["example"],
+                "content": "This will be unchanged",
+                "phoneNumber": "+01-123-456-7890"
+            }),
+            document
+        );
+        assert_eq!(patch, merge_diff(&original, &document));
+    }
+}
@@ -0,0 +1,305 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    result,
+    str::FromStr,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use serde_json::Value;
+
+/// JSON Pointer（RFC 6901）用一个字符串定位文档中的一个值，例如 `/pets/0/name`
+/// * 空字符串表示整个文档，否则每一段都以 / 开头
+/// * 段中的 ~ 写作 ~0，/ 写作 ~1
+/// * 对数组来说段是下标（不允许前导0），`-` 表示数组末尾之后的位置，只能用来追加
+///
+/// serde_json 自带的 Value::pointer 只能读取，这里补上修改和删除
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Pointer(Vec<String>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PointerError {
+    Syntax(String),
+    /// 路径上的某个值不存在，内容是到这个值为止的pointer
+    NotFound(String),
+    /// 数组的段不是合法的下标，或者下标越界
+    InvalidIndex(String),
+    /// 试图在一个标量中查找子节点
+    NotAContainer(String),
+}
+
+pub type Result<T> = result::Result<T, PointerError>;
+
+impl error::Error for PointerError {}
+
+impl Display for PointerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Syntax(ref msg) => write!(f, "Invalid JSON pointer: {}", msg),
+            Self::NotFound(ref at) => write!(f, "No value at '{}'", at),
+            Self::InvalidIndex(ref at) => write!(f, "Invalid array index at '{}'", at),
+            Self::NotAContainer(ref at) => {
+                write!(f, "'{}' is neither an object nor an array", at)
+            }
+        }
+    }
+}
+
+impl FromStr for Pointer {
+    type Err = PointerError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            return Ok(Self::root());
+        }
+        let rest = s
+            .strip_prefix('/')
+            .ok_or_else(|| PointerError::Syntax(format!("'{}' must start with '/'", s)))?;
+        rest.split('/')
+            .map(|token| {
+                let mut unescaped = String::with_capacity(token.len());
+                let mut chars = token.chars();
+                while let Some(c) = chars.next() {
+                    if c != '~' {
+                        unescaped.push(c);
+                        continue;
+                    }
+                    match chars.next() {
+                        Some('0') => unescaped.push('~'),
+                        Some('1') => unescaped.push('/'),
+                        _ => {
+                            return Err(PointerError::Syntax(format!(
+                                "'~' must be followed by '0' or '1' in '{}'",
+                                s
+                            )));
+                        }
+                    }
+                }
+                Ok(unescaped)
+            })
+            .collect::<Result<_>>()
+            .map(Self)
+    }
+}
+
+impl Display for Pointer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for token in &self.0 {
+            write!(f, "/{}", token.replace('~', "~0").replace('/', "~1"))?;
+        }
+        Ok(())
+    }
+}
+
+/// 在JSON Patch之类的文档中，pointer 以字符串的形式出现
+impl Serialize for Pointer {
+    fn serialize<S: Serializer>(&self, serializer: S) -> result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Pointer {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Pointer {
+    pub fn root() -> Self {
+        Self(Vec::new())
+    }
+
+    pub fn tokens(&self) -> &[String] {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// 返回追加了一段之后的新pointer
+    pub fn join<T: ToString>(&self, token: T) -> Self {
+        let mut tokens = self.0.clone();
+        tokens.push(token.to_string());
+        Self(tokens)
+    }
+
+    /// 拆成父节点和最后一段，根节点没有父节点
+    pub fn split_last(&self) -> Option<(Self, &str)> {
+        let (last, parent) = self.0.split_last()?;
+        Some((Self(parent.to_vec()), last))
+    }
+
+    /// 前 n 段组成的pointer，用于错误信息
+    fn prefix(&self, n: usize) -> String {
+        Self(self.0[..n].to_vec()).to_string()
+    }
+
+    pub fn get<'a>(&self, document: &'a Value) -> Option<&'a Value> {
+        self.0
+            .iter()
+            .try_fold(document, |current, token| match current {
+                Value::Object(map) => map.get(token),
+                Value::Array(array) => parse_index(token).and_then(|i| array.get(i)),
+                _ => None,
+            })
+    }
+
+    pub fn get_mut<'a>(&self, document: &'a mut Value) -> Result<&'a mut Value> {
+        let mut current = document;
+        for (depth, token) in self.0.iter().enumerate() {
+            let at = || self.prefix(depth + 1);
+            current = match current {
+                Value::Object(map) => map
+                    .get_mut(token)
+                    .ok_or_else(|| PointerError::NotFound(at()))?,
+                Value::Array(array) => {
+                    let i = parse_index(token).ok_or_else(|| PointerError::InvalidIndex(at()))?;
+                    array
+                        .get_mut(i)
+                        .ok_or_else(|| PointerError::NotFound(at()))?
+                }
+                _ => return Err(PointerError::NotAContainer(self.prefix(depth))),
+            };
+        }
+        Ok(current)
+    }
+
+    /// 设置一个值，父节点必须已经存在
+    /// 对象中的成员会被新增或替换；数组中已有的下标会被替换，等于长度的下标或者 `-` 会追加
+    /// 返回被替换掉的旧值
+    pub fn set(&self, document: &mut Value, value: Value) -> Result<Option<Value>> {
+        let Some((parent, last)) = self.split_last() else {
+            return Ok(Some(std::mem::replace(document, value)));
+        };
+        match parent.get_mut(document)? {
+            Value::Object(map) => Ok(map.insert(last.to_string(), value)),
+            Value::Array(array) => match array_position(last, array.len(), true) {
+                Some(i) if i == array.len() => {
+                    array.push(value);
+                    Ok(None)
+                }
+                Some(i) => Ok(Some(std::mem::replace(&mut array[i], value))),
+                None => Err(PointerError::InvalidIndex(self.to_string())),
+            },
+            _ => Err(PointerError::NotAContainer(parent.to_string())),
+        }
+    }
+
+    /// 和 set 的区别在于数组：新值插入到下标所在的位置，后面的元素依次后移
+    /// 这是JSON Patch中 add 操作的语义
+    pub fn add(&self, document: &mut Value, value: Value) -> Result<()> {
+        let Some((parent, last)) = self.split_last() else {
+            *document = value;
+            return Ok(());
+        };
+        match parent.get_mut(document)? {
+            Value::Object(map) => {
+                map.insert(last.to_string(), value);
+                Ok(())
+            }
+            Value::Array(array) => match array_position(last, array.len(), true) {
+                Some(i) => {
+                    array.insert(i, value);
+                    Ok(())
+                }
+                None => Err(PointerError::InvalidIndex(self.to_string())),
+            },
+            _ => Err(PointerError::NotAContainer(parent.to_string())),
+        }
+    }
+
+    /// 删除并返回一个值，不能删除整个文档
+    pub fn remove(&self, document: &mut Value) -> Result<Value> {
+        let (parent, last) = self
+            .split_last()
+            .ok_or_else(|| PointerError::Syntax("cannot remove the whole document".to_string()))?;
+        match parent.get_mut(document)? {
+            Value::Object(map) => map
+                .shift_remove(last)
+                .ok_or_else(|| PointerError::NotFound(self.to_string())),
+            Value::Array(array) => match array_position(last, array.len(), false) {
+                Some(i) => Ok(array.remove(i)),
+                None => Err(PointerError::InvalidIndex(self.to_string())),
+            },
+            _ => Err(PointerError::NotAContainer(parent.to_string())),
+        }
+    }
+}
+
+/// RFC 6901 中的数组下标：0 或者不以 0 开头的数字
+pub(crate) fn parse_index(token: &str) -> Option<usize> {
+    let valid =
+        token == "0" || (!token.starts_with('0') && token.bytes().all(|b| b.is_ascii_digit()));
+    if valid { token.parse().ok() } else { None }
+}
+
+/// 下标在数组中的位置，allow_end 为true时允许 `-` 和等于长度的下标（指向末尾之后）
+pub(crate) fn array_position(token: &str, len: usize, allow_end: bool) -> Option<usize> {
+    let i = match token {
+        "-" => len,
+        token => parse_index(token)?,
+    };
+    match (i < len, i == len && allow_end) {
+        (true, _) | (_, true) => Some(i),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn pointer(s: &str) -> Pointer {
+        s.parse().expect("Failed to parse pointer")
+    }
+
+    #[test]
+    fn follows_rfc_6901_examples() {
+        let document = json!({
+            "foo": ["bar", "baz"],
+            "": 0,
+            "a/b": 1,
+            "m~n": 8,
+        });
+        assert_eq!(Some(&document), pointer("").get(&document));
+        assert_eq!(Some(&json!("baz")), pointer("/foo/1").get(&document));
+        assert_eq!(Some(&json!(0)), pointer("/").get(&document));
+        assert_eq!(Some(&json!(1)), pointer("/a~1b").get(&document));
+        assert_eq!(Some(&json!(8)), pointer("/m~0n").get(&document));
+        assert_eq!(None, pointer("/foo/01").get(&document));
+        assert_eq!("/a~1b/m~0n", pointer("/a~1b/m~0n").to_string());
+        assert!("foo".parse::<Pointer>().is_err());
+        assert!("/~2".parse::<Pointer>().is_err());
+    }
+
+    #[test]
+    fn sets_and_removes() {
+        let mut document = json!({ "pets": [{ "name": "Waldo" }] });
+        pointer("/pets/-")
+            .set(&mut document, json!({ "name": "Speedy" }))
+            .expect("Failed to append");
+        let old = pointer("/pets/0/name")
+            .set(&mut document, json!("Meows"))
+            .expect("Failed to replace");
+        assert_eq!(Some(json!("Waldo")), old);
+        assert_eq!(
+            Ok(json!({ "name": "Meows" })),
+            pointer("/pets/0").remove(&mut document)
+        );
+        assert_eq!(json!({ "pets": [{ "name": "Speedy" }] }), document);
+
+        assert_eq!(
+            Err(PointerError::NotFound("/owner".to_string())),
+            pointer("/owner/name").set(&mut document, json!("John"))
+        );
+        assert_eq!(
+            Err(PointerError::InvalidIndex("/pets/5".to_string())),
+            pointer("/pets/5").set(&mut document, json!(null))
+        );
+    }
+}
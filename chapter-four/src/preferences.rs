@@ -0,0 +1,360 @@
+use std::{
+    collections::HashSet,
+    error,
+    fmt::{self, Display},
+};
+
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::migrate::Migrations;
+
+/// 当前的配置格式版本，每次添加迁移时加一
+pub const CURRENT_VERSION: u32 = 2;
+
+/// toml.rs 中读写的用户偏好设置，放在库中供配置相关的模块共用
+///
+/// version 记录文件使用的格式，旧格式的文件先经过 migrations 中的迁移再反序列化
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preferences {
+    pub version: u32,
+    pub person: Person,
+    pub language: Language,
+    /// 没有 [autocorrect] 表的文件关闭自动更正，和版本1中没有 language.autocorrect 的含义相同
+    #[serde(default)]
+    pub autocorrect: Autocorrect,
+    pub privacy: Privacy,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Person {
+    pub name: String,
+    pub email: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Language {
+    pub display: String,
+}
+
+/// 版本1中是 language.autocorrect，一个可选的语言列表
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Autocorrect {
+    pub enabled: bool,
+    /// 按优先级排列的语言
+    pub locales: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Privacy {
+    pub share_anonymous_statistics: bool,
+    pub public_name: bool,
+    pub public_email: bool,
+}
+
+/// 编译进程序的默认值，是分层配置的最底层
+/// 隐私相关的选项默认全部关闭
+impl Default for Preferences {
+    fn default() -> Self {
+        Self {
+            version: CURRENT_VERSION,
+            person: Person {
+                name: String::new(),
+                email: String::new(),
+            },
+            language: Language {
+                display: "en-US".to_string(),
+            },
+            autocorrect: Autocorrect {
+                enabled: true,
+                locales: vec!["en-US".to_string()],
+            },
+            privacy: Privacy {
+                share_anonymous_statistics: false,
+                public_name: false,
+                public_email: false,
+            },
+        }
+    }
+}
+
+/// preferences.toml 的所有迁移
+pub fn migrations() -> Migrations {
+    Migrations::new(CURRENT_VERSION).register(
+        1,
+        "move language.autocorrect into its own [autocorrect] table",
+        autocorrect_table,
+    )
+}
+
+/// 版本1：`language.autocorrect = ["en-GB"]`，没有这个键表示关闭自动更正
+/// 版本2：`[autocorrect]` 表，包含 enabled 和 locales
+///
+/// 没有 language.autocorrect 时什么都不做：分层配置中的文件通常只写了几个键，
+/// 在这里补上一个关闭的 [autocorrect] 会覆盖下面的层；完整的文件缺少这个表时由 Autocorrect 的默认值关闭自动更正
+fn autocorrect_table(document: &mut Table) -> Result<(), String> {
+    let old = match document.get_mut("language") {
+        Some(Value::Table(language)) => language.remove("autocorrect"),
+        Some(_) => return Err("'language' is not a table".to_string()),
+        None => None,
+    };
+    let locales = match old {
+        Some(Value::Array(locales)) => locales,
+        Some(_) => return Err("'language.autocorrect' is not an array".to_string()),
+        None => return Ok(()),
+    };
+    let mut autocorrect = Table::new();
+    autocorrect.insert("enabled".to_string(), Value::Boolean(true));
+    autocorrect.insert("locales".to_string(), Value::Array(locales));
+    document.insert("autocorrect".to_string(), Value::Table(autocorrect));
+    Ok(())
+}
+
+/// 一个不符合规则的值，key 是它在 preferences.toml 中的路径，数组元素带上下标，例如 `autocorrect.locales[1]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub key: String,
+    pub message: String,
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+/// validate 发现的所有问题，至少有一个
+#[derive(Debug, Clone, PartialEq)]
+pub struct Invalid(pub Vec<Problem>);
+
+impl error::Error for Invalid {}
+
+impl Display for Invalid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} invalid preference(s)", self.0.len())?;
+        for problem in &self.0 {
+            write!(f, "\n  {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+impl Preferences {
+    /// 反序列化只保证类型正确，任何字符串都能放进 email 和 display 中
+    /// 这里检查值本身是否合理，以及字段之间的约束，并且一次报告所有问题而不是停在第一个上
+    pub fn validate(&self) -> Result<(), Invalid> {
+        let mut problems = Vec::new();
+        let mut problem = |key: String, message: String| problems.push(Problem { key, message });
+
+        if !self.person.email.is_empty() && !is_email(&self.person.email) {
+            problem(
+                "person.email".to_string(),
+                format!("'{}' is not an email address", self.person.email),
+            );
+        }
+        if !is_language_tag(&self.language.display) {
+            problem(
+                "language.display".to_string(),
+                format!("'{}' is not a BCP 47 language tag", self.language.display),
+            );
+        }
+
+        // 语言标签不区分大小写，en-GB 和 en-gb 是同一个
+        let mut seen = HashSet::new();
+        for (i, locale) in self.autocorrect.locales.iter().enumerate() {
+            let key = format!("autocorrect.locales[{}]", i);
+            if !is_language_tag(locale) {
+                problem(key, format!("'{}' is not a BCP 47 language tag", locale));
+            } else if !seen.insert(locale.to_ascii_lowercase()) {
+                problem(key, format!("'{}' is listed more than once", locale));
+            }
+        }
+        if self.autocorrect.enabled && self.autocorrect.locales.is_empty() {
+            problem(
+                "autocorrect.locales".to_string(),
+                "autocorrect is enabled but no locale is given".to_string(),
+            );
+        }
+
+        if self.privacy.public_name && self.person.name.trim().is_empty() {
+            problem(
+                "privacy.public_name".to_string(),
+                "cannot show an empty name publicly, set person.name first".to_string(),
+            );
+        }
+        if self.privacy.public_email && self.person.email.is_empty() {
+            problem(
+                "privacy.public_email".to_string(),
+                "cannot show an empty email publicly, set person.email first".to_string(),
+            );
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(Invalid(problems))
+        }
+    }
+}
+
+/// 只做基本的形式检查：local@domain，domain 至少有两段，不允许空白
+/// 完整的RFC 5322语法过于宽松，真正确认一个地址的唯一办法是给它发一封邮件
+fn is_email(email: &str) -> bool {
+    let Some((local, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+    let labels: Vec<&str> = domain.split('.').collect();
+    !local.is_empty()
+        && !local.contains('@')
+        && !email.chars().any(|c| c.is_whitespace() || c.is_control())
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// BCP 47（RFC 5646）中的 langtag 语法，不检查子标签是否在IANA的注册表中：
+///
+/// language["-" script]["-" region]*("-" variant)*("-" extension)["-" privateuse]
+/// * language：2-3个字母，后面最多三个3个字母的扩展语言，或者4-8个字母
+/// * script：4个字母，例如 Hant
+/// * region：2个字母或者3个数字，例如 CH、419
+/// * variant：5-8个字母数字，或者数字开头的4个字母数字，例如 1996
+/// * extension：除 x 以外的单个字母数字，后面是一个或多个2-8个字母数字的子标签
+/// * privateuse：x 后面是一个或多个1-8个字母数字的子标签，也可以单独构成一个标签，例如 x-klingon
+pub fn is_language_tag(tag: &str) -> bool {
+    let subtags: Vec<&str> = tag.split('-').collect();
+    if subtags
+        .iter()
+        .any(|s| s.is_empty() || s.len() > 8 || !s.chars().all(|c| c.is_ascii_alphanumeric()))
+    {
+        return false;
+    }
+    let alpha = |s: &str| s.chars().all(|c| c.is_ascii_alphabetic());
+    let digit = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    let private_use =
+        |rest: &[&str]| rest.first().is_some_and(|s| s.eq_ignore_ascii_case("x")) && rest.len() > 1;
+
+    if private_use(&subtags) {
+        return true;
+    }
+    let mut rest = &subtags[..];
+    match rest.first() {
+        Some(language) if alpha(language) && (2..=3).contains(&language.len()) => {
+            rest = &rest[1..];
+            let mut extlangs = 0;
+            while extlangs < 3 && rest.first().is_some_and(|s| s.len() == 3 && alpha(s)) {
+                rest = &rest[1..];
+                extlangs += 1;
+            }
+        }
+        Some(language) if alpha(language) && (4..=8).contains(&language.len()) => {
+            rest = &rest[1..];
+        }
+        _ => return false,
+    }
+    if rest.first().is_some_and(|s| s.len() == 4 && alpha(s)) {
+        rest = &rest[1..];
+    }
+    if rest
+        .first()
+        .is_some_and(|s| (s.len() == 2 && alpha(s)) || (s.len() == 3 && digit(s)))
+    {
+        rest = &rest[1..];
+    }
+    while rest.first().is_some_and(|s| {
+        (5..=8).contains(&s.len()) || (s.len() == 4 && s.starts_with(|c: char| c.is_ascii_digit()))
+    }) {
+        rest = &rest[1..];
+    }
+    while let Some(singleton) = rest.first()
+        && singleton.len() == 1
+        && !singleton.eq_ignore_ascii_case("x")
+    {
+        let count = rest[1..].iter().take_while(|s| s.len() >= 2).count();
+        if count == 0 {
+            return false;
+        }
+        rest = &rest[1 + count..];
+    }
+    rest.is_empty() || private_use(rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn language_tags() {
+        for tag in [
+            "en",
+            "en-GB",
+            "de-CH",
+            "zh-Hant-TW",
+            "es-419",
+            "sl-rozaj-biske",
+            "de-CH-1996",
+            "en-US-u-ca-gregory",
+            "x-klingon",
+            "en-x-private",
+        ] {
+            assert!(is_language_tag(tag), "{} should be valid", tag);
+        }
+        for tag in [
+            "",
+            "e",
+            "en_GB",
+            "en-",
+            "english-is-not-a-tag",
+            "en-GB-u",
+            "de-419-CH",
+            "x",
+            "123",
+        ] {
+            assert!(!is_language_tag(tag), "{} should be invalid", tag);
+        }
+    }
+
+    #[test]
+    fn reports_every_problem_with_its_key() {
+        assert_eq!(Ok(()), Preferences::default().validate());
+
+        let mut preferences = Preferences::default();
+        preferences.person.email = "jn_ferner at hotmail.de".to_string();
+        preferences.language.display = "en_GB".to_string();
+        preferences.autocorrect.locales = vec![
+            "en-GB".to_string(),
+            "EN-gb".to_string(),
+            "Swiss German".to_string(),
+        ];
+        preferences.privacy.public_name = true;
+        let Err(Invalid(problems)) = preferences.validate() else {
+            panic!("The preferences should be invalid");
+        };
+        let keys: Vec<&str> = problems.iter().map(|p| p.key.as_str()).collect();
+        assert_eq!(
+            vec![
+                "person.email",
+                "language.display",
+                "autocorrect.locales[1]",
+                "autocorrect.locales[2]",
+                "privacy.public_name",
+            ],
+            keys
+        );
+
+        preferences = Preferences::default();
+        preferences.privacy.public_email = true;
+        preferences.autocorrect.locales.clear();
+        let Err(Invalid(problems)) = preferences.validate() else {
+            panic!("The preferences should be invalid");
+        };
+        assert_eq!(
+            vec!["autocorrect.locales", "privacy.public_email"],
+            problems.iter().map(|p| &p.key).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,983 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    error,
+    fmt::{self, Display},
+    io::{self, Read, Write},
+    result,
+    str::FromStr,
+};
+
+/// 一个面向CSV的迷你查询语言，子句可以按任意顺序出现，每种最多一次
+///
+/// ```text
+/// select name, gravity
+/// where gravity > 1 and name != "Earth"
+/// group by X agg avg(Y), count() as n
+/// sort by radius desc, name
+/// limit 3
+/// ```
+///
+/// * 比较时如果两边都能解析成数字就按数字比较，否则按字符串比较
+/// * 列名中有空格或者和关键字冲突时，用反引号包起来：`` `distance from sun` ``
+/// * 聚合函数：count()、sum(X)、avg(X)、min(X)、max(X)，结果列名默认是函数本身，例如 `avg(gravity)`，可以用 as 重命名
+///
+/// 只有 where/select/limit 时是流式处理的，每读一行就输出一行；sort 需要缓存所有符合条件的行，group by 只需要为每个分组保存累加器
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    pub select: Option<Vec<String>>,
+    pub filter: Option<Expr>,
+    pub group: Option<GroupBy>,
+    pub sort: Vec<SortKey>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Operand, CompareOp, Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Column(String),
+    Literal(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupBy {
+    pub columns: Vec<String>,
+    pub aggregates: Vec<Aggregate>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aggregate {
+    pub function: AggregateFn,
+    pub column: Option<String>,
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFn {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SortKey {
+    pub column: String,
+    pub descending: bool,
+}
+
+/// 结果的输出方式，Table需要先缓存所有结果行来计算列宽
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Output {
+    Csv,
+    Table,
+}
+
+#[derive(Debug)]
+pub enum QueryError {
+    Parse { position: usize, message: String },
+    UnknownColumn(String),
+    Csv(csv::Error),
+    Io(io::Error),
+}
+
+pub type Result<T> = result::Result<T, QueryError>;
+
+impl error::Error for QueryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Csv(ref err) => Some(err),
+            Self::Io(ref err) => Some(err),
+            Self::Parse { .. } | Self::UnknownColumn(_) => None,
+        }
+    }
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Parse {
+                position,
+                ref message,
+            } => write!(f, "Syntax error at position {}: {}", position, message),
+            Self::UnknownColumn(ref column) => write!(f, "Unknown column '{}'", column),
+            Self::Csv(ref err) => write!(f, "CSV error: {}", err),
+            Self::Io(ref err) => write!(f, "IO error: {}", err),
+        }
+    }
+}
+
+impl From<csv::Error> for QueryError {
+    fn from(value: csv::Error) -> Self {
+        Self::Csv(value)
+    }
+}
+
+impl From<io::Error> for QueryError {
+    fn from(value: io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl Display for AggregateFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Count => "count",
+            Self::Sum => "sum",
+            Self::Avg => "avg",
+            Self::Min => "min",
+            Self::Max => "max",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Aggregate {
+    /// 结果中这一列的名称
+    pub fn name(&self) -> String {
+        match self.alias {
+            Some(ref alias) => alias.clone(),
+            None => format!(
+                "{}({})",
+                self.function,
+                self.column.as_deref().unwrap_or("")
+            ),
+        }
+    }
+}
+
+// ---------- 词法分析 ----------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    /// 反引号包起来的标识符，永远不会被当作关键字
+    Quoted(String),
+    Number(String),
+    Str(String),
+    Op(CompareOp),
+    LParen,
+    RParen,
+    Comma,
+}
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(pos, c)) = chars.peek() {
+        let error = |message: &str| QueryError::Parse {
+            position: pos,
+            message: message.to_string(),
+        };
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' | ')' | ',' => {
+                chars.next();
+                match c {
+                    '(' => Token::LParen,
+                    ')' => Token::RParen,
+                    _ => Token::Comma,
+                }
+            }
+            '=' | '!' | '<' | '>' => {
+                chars.next();
+                let followed_by_eq = chars.next_if(|&(_, c)| c == '=').is_some();
+                match (c, followed_by_eq) {
+                    ('=', _) => Token::Op(CompareOp::Eq),
+                    ('!', true) => Token::Op(CompareOp::Ne),
+                    ('<', false) => Token::Op(CompareOp::Lt),
+                    ('<', true) => Token::Op(CompareOp::Le),
+                    ('>', false) => Token::Op(CompareOp::Gt),
+                    ('>', true) => Token::Op(CompareOp::Ge),
+                    _ => return Err(error("expected '!='")),
+                }
+            }
+            '"' | '\'' | '`' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => return Err(error("unterminated string")),
+                        },
+                        Some((_, end)) if end == c => break,
+                        Some((_, other)) => value.push(other),
+                        None => return Err(error("unterminated string")),
+                    }
+                }
+                if c == '`' {
+                    Token::Quoted(value)
+                } else {
+                    Token::Str(value)
+                }
+            }
+            c if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut value = String::new();
+                while let Some((_, c)) =
+                    chars.next_if(|&(_, c)| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+                {
+                    value.push(c);
+                }
+                if value.parse::<f64>().is_err() {
+                    return Err(error(&format!("invalid number '{}'", value)));
+                }
+                Token::Number(value)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let mut value = String::new();
+                while let Some((_, c)) = chars.next_if(|&(_, c)| c.is_alphanumeric() || c == '_') {
+                    value.push(c);
+                }
+                Token::Ident(value)
+            }
+            _ => return Err(error(&format!("unexpected character '{}'", c))),
+        };
+        tokens.push((pos, token));
+    }
+    Ok(tokens)
+}
+
+// ---------- 语法分析 ----------
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.end, |(p, _)| *p)
+    }
+
+    fn error<T>(&self, message: &str) -> Result<T> {
+        Err(QueryError::Parse {
+            position: self.position(),
+            message: message.to_string(),
+        })
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(i)) if i.eq_ignore_ascii_case(keyword))
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = self.is_keyword(keyword);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            self.error(&format!("expected '{}'", keyword))
+        }
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.peek() == Some(token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, token: &Token, what: &str) -> Result<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            self.error(&format!("expected {}", what))
+        }
+    }
+
+    fn column(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(Token::Ident(i)) if !is_reserved(i) => {}
+            Some(Token::Quoted(_)) => {}
+            _ => return self.error("expected a column name"),
+        }
+        match self.next() {
+            Some(Token::Ident(name)) | Some(Token::Quoted(name)) => Ok(name),
+            _ => unreachable!("Checked by peek"),
+        }
+    }
+
+    fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        let mut items = vec![item(self)?];
+        while self.eat(&Token::Comma) {
+            items.push(item(self)?);
+        }
+        Ok(items)
+    }
+
+    fn query(&mut self) -> Result<Query> {
+        let mut query = Query {
+            select: None,
+            filter: None,
+            group: None,
+            sort: Vec::new(),
+            limit: None,
+        };
+        while self.peek().is_some() {
+            let duplicate =
+                |parser: &Self, clause| parser.error(&format!("duplicate '{}'", clause));
+            if self.eat_keyword("select") {
+                if query.select.is_some() {
+                    return duplicate(self, "select");
+                }
+                query.select = Some(self.list(Self::column)?);
+            } else if self.eat_keyword("where") {
+                if query.filter.is_some() {
+                    return duplicate(self, "where");
+                }
+                query.filter = Some(self.or()?);
+            } else if self.eat_keyword("group") {
+                if query.group.is_some() {
+                    return duplicate(self, "group by");
+                }
+                self.expect_keyword("by")?;
+                let columns = self.list(Self::column)?;
+                let aggregates = if self.eat_keyword("agg") {
+                    self.list(Self::aggregate)?
+                } else {
+                    Vec::new()
+                };
+                query.group = Some(GroupBy {
+                    columns,
+                    aggregates,
+                });
+            } else if self.eat_keyword("sort") {
+                if !query.sort.is_empty() {
+                    return duplicate(self, "sort by");
+                }
+                self.expect_keyword("by")?;
+                query.sort = self.list(|p| {
+                    let column = p.sort_column()?;
+                    let descending = if p.eat_keyword("desc") {
+                        true
+                    } else {
+                        p.eat_keyword("asc");
+                        false
+                    };
+                    Ok(SortKey { column, descending })
+                })?;
+            } else if self.eat_keyword("limit") {
+                if query.limit.is_some() {
+                    return duplicate(self, "limit");
+                }
+                query.limit = match self.next() {
+                    Some(Token::Number(n)) => match n.parse() {
+                        Ok(n) => Some(n),
+                        Err(_) => {
+                            self.pos -= 1;
+                            return self.error("limit must be a positive integer");
+                        }
+                    },
+                    _ => {
+                        self.pos -= 1;
+                        return self.error("expected a number after 'limit'");
+                    }
+                };
+            } else {
+                return self.error("expected select, where, group by, sort by or limit");
+            }
+        }
+        if query.select.is_some() && query.group.is_some() {
+            return self.error("select cannot be combined with group by");
+        }
+        Ok(query)
+    }
+
+    /// 排序时可以直接引用聚合结果，例如 `sort by avg(gravity) desc`
+    fn sort_column(&mut self) -> Result<String> {
+        let start = self.pos;
+        if let Ok(aggregate) = self.aggregate() {
+            return Ok(aggregate.name());
+        }
+        self.pos = start;
+        self.column()
+    }
+
+    fn aggregate(&mut self) -> Result<Aggregate> {
+        let function = match self.peek() {
+            Some(Token::Ident(name)) => match name.to_ascii_lowercase().as_str() {
+                "count" => AggregateFn::Count,
+                "sum" => AggregateFn::Sum,
+                "avg" => AggregateFn::Avg,
+                "min" => AggregateFn::Min,
+                "max" => AggregateFn::Max,
+                _ => return self.error("expected count, sum, avg, min or max"),
+            },
+            _ => return self.error("expected an aggregate function"),
+        };
+        self.pos += 1;
+        self.expect(&Token::LParen, "'('")?;
+        let column = if function == AggregateFn::Count && self.peek() == Some(&Token::RParen) {
+            None
+        } else {
+            Some(self.column()?)
+        };
+        self.expect(&Token::RParen, "')'")?;
+        let alias = if self.eat_keyword("as") {
+            Some(self.column()?)
+        } else {
+            None
+        };
+        Ok(Aggregate {
+            function,
+            column,
+            alias,
+        })
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.eat_keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.eat_keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.eat(&Token::LParen) {
+            let expr = self.or()?;
+            self.expect(&Token::RParen, "')'")?;
+            return Ok(expr);
+        }
+        let left = self.operand()?;
+        let op = match self.next() {
+            Some(Token::Op(op)) => op,
+            _ => {
+                self.pos -= 1;
+                return self.error("expected a comparison operator");
+            }
+        };
+        let right = self.operand()?;
+        Ok(Expr::Compare(left, op, right))
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        match self.peek() {
+            Some(Token::Number(_)) | Some(Token::Str(_)) => match self.next() {
+                Some(Token::Number(v)) | Some(Token::Str(v)) => Ok(Operand::Literal(v)),
+                _ => unreachable!("Checked by peek"),
+            },
+            _ => self.column().map(Operand::Column),
+        }
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    const RESERVED: [&str; 13] = [
+        "select", "where", "group", "by", "agg", "sort", "asc", "desc", "limit", "and", "or",
+        "not", "as",
+    ];
+    RESERVED.iter().any(|r| r.eq_ignore_ascii_case(word))
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            end: s.len(),
+        };
+        parser.query()
+    }
+}
+
+// ---------- 执行 ----------
+
+/// 如果两边都是数字就按数字比较，否则按字符串比较
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+/// 列名被替换成下标之后的表达式
+enum Compiled {
+    And(Box<Compiled>, Box<Compiled>),
+    Or(Box<Compiled>, Box<Compiled>),
+    Not(Box<Compiled>),
+    Compare(CompiledOperand, CompareOp, CompiledOperand),
+}
+
+enum CompiledOperand {
+    Column(usize),
+    Literal(String),
+}
+
+fn index_of(headers: &[String], column: &str) -> Result<usize> {
+    headers
+        .iter()
+        .position(|h| h == column)
+        .ok_or_else(|| QueryError::UnknownColumn(column.to_string()))
+}
+
+impl Expr {
+    fn compile(&self, headers: &[String]) -> Result<Compiled> {
+        let operand = |operand: &Operand| -> Result<CompiledOperand> {
+            Ok(match operand {
+                Operand::Column(c) => CompiledOperand::Column(index_of(headers, c)?),
+                Operand::Literal(l) => CompiledOperand::Literal(l.clone()),
+            })
+        };
+        Ok(match self {
+            Self::And(a, b) => {
+                Compiled::And(Box::new(a.compile(headers)?), Box::new(b.compile(headers)?))
+            }
+            Self::Or(a, b) => {
+                Compiled::Or(Box::new(a.compile(headers)?), Box::new(b.compile(headers)?))
+            }
+            Self::Not(e) => Compiled::Not(Box::new(e.compile(headers)?)),
+            Self::Compare(a, op, b) => Compiled::Compare(operand(a)?, *op, operand(b)?),
+        })
+    }
+}
+
+impl Compiled {
+    fn eval(&self, row: &csv::StringRecord) -> bool {
+        let value = |operand: &CompiledOperand| match operand {
+            CompiledOperand::Column(i) => row.get(*i).unwrap_or("").to_string(),
+            CompiledOperand::Literal(l) => l.clone(),
+        };
+        match self {
+            Self::And(a, b) => a.eval(row) && b.eval(row),
+            Self::Or(a, b) => a.eval(row) || b.eval(row),
+            Self::Not(e) => !e.eval(row),
+            Self::Compare(a, op, b) => {
+                let ordering = compare_cells(&value(a), &value(b));
+                match op {
+                    CompareOp::Eq => ordering == Ordering::Equal,
+                    CompareOp::Ne => ordering != Ordering::Equal,
+                    CompareOp::Lt => ordering == Ordering::Less,
+                    CompareOp::Le => ordering != Ordering::Greater,
+                    CompareOp::Gt => ordering == Ordering::Greater,
+                    CompareOp::Ge => ordering != Ordering::Less,
+                }
+            }
+        }
+    }
+}
+
+/// 一个分组中某个聚合函数的中间状态
+enum Accumulator {
+    Count(usize),
+    /// 还没有遇到数字时是 None，和 avg 一样输出空单元格
+    Sum(Option<f64>),
+    Avg(f64, usize),
+    Min(Option<String>),
+    Max(Option<String>),
+}
+
+impl Accumulator {
+    fn new(function: AggregateFn) -> Self {
+        match function {
+            AggregateFn::Count => Self::Count(0),
+            AggregateFn::Sum => Self::Sum(None),
+            AggregateFn::Avg => Self::Avg(0., 0),
+            AggregateFn::Min => Self::Min(None),
+            AggregateFn::Max => Self::Max(None),
+        }
+    }
+
+    /// 空单元格不参与聚合，sum和avg会忽略无法解析成数字的值
+    fn add(&mut self, value: Option<&str>) {
+        let value = match value.map(str::trim) {
+            Some(v) if !v.is_empty() => v,
+            _ => return,
+        };
+        match self {
+            Self::Count(n) => *n += 1,
+            Self::Sum(sum) => {
+                if let Ok(v) = value.parse::<f64>() {
+                    *sum = Some(sum.unwrap_or(0.) + v);
+                }
+            }
+            Self::Avg(sum, n) => {
+                if let Ok(v) = value.parse::<f64>() {
+                    *sum += v;
+                    *n += 1;
+                }
+            }
+            Self::Min(min) => {
+                if min
+                    .as_deref()
+                    .is_none_or(|m| compare_cells(value, m) == Ordering::Less)
+                {
+                    *min = Some(value.to_string());
+                }
+            }
+            Self::Max(max) => {
+                if max
+                    .as_deref()
+                    .is_none_or(|m| compare_cells(value, m) == Ordering::Greater)
+                {
+                    *max = Some(value.to_string());
+                }
+            }
+        }
+    }
+
+    fn finish(&self) -> String {
+        match self {
+            Self::Count(n) => n.to_string(),
+            Self::Sum(sum) => sum.map(|sum| sum.to_string()).unwrap_or_default(),
+            Self::Avg(_, 0) => String::new(),
+            Self::Avg(sum, n) => (sum / *n as f64).to_string(),
+            Self::Min(v) | Self::Max(v) => v.clone().unwrap_or_default(),
+        }
+    }
+}
+
+type Rows<'a> = Box<dyn Iterator<Item = Result<Vec<String>>> + 'a>;
+
+/// 把结果行写到CSV或者缓存起来最后画成表格
+enum Sink<W: Write> {
+    Csv(Box<csv::Writer<W>>),
+    Table(W, Vec<Vec<String>>),
+}
+
+impl<W: Write> Sink<W> {
+    fn new(writer: W, output: Output, headers: Vec<String>) -> Result<Self> {
+        Ok(match output {
+            Output::Csv => {
+                let mut wtr = csv::Writer::from_writer(writer);
+                wtr.write_record(&headers)?;
+                Sink::Csv(Box::new(wtr))
+            }
+            Output::Table => Sink::Table(writer, vec![headers]),
+        })
+    }
+
+    fn push(&mut self, row: Vec<String>) -> Result<()> {
+        match self {
+            Sink::Csv(wtr) => wtr.write_record(&row)?,
+            Sink::Table(_, rows) => rows.push(row),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Sink::Csv(mut wtr) => wtr.flush()?,
+            Sink::Table(mut writer, rows) => {
+                let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+                let widths: Vec<_> = (0..columns)
+                    .map(|i| {
+                        rows.iter()
+                            .filter_map(|r| r.get(i))
+                            .map(|c| c.chars().count())
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect();
+                for (n, row) in rows.iter().enumerate() {
+                    let line: Vec<_> = row
+                        .iter()
+                        .zip(&widths)
+                        .map(|(cell, width)| {
+                            // 数字右对齐，其它左对齐
+                            if n > 0 && cell.parse::<f64>().is_ok() {
+                                format!("{:>width$}", cell, width = width)
+                            } else {
+                                format!("{:<width$}", cell, width = width)
+                            }
+                        })
+                        .collect();
+                    writeln!(writer, "{}", line.join(" | ").trim_end())?;
+                    if n == 0 {
+                        let rule: Vec<_> = widths.iter().map(|w| "-".repeat(*w)).collect();
+                        writeln!(writer, "{}", rule.join("-+-"))?;
+                    }
+                }
+                writer.flush()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Query {
+    /// 对reader中的CSV（第一行是header）执行查询，结果写入writer
+    pub fn execute<R: Read, W: Write>(&self, reader: R, writer: W, output: Output) -> Result<()> {
+        let mut rdr = csv::Reader::from_reader(reader);
+        let headers: Vec<String> = rdr.headers()?.iter().map(str::to_string).collect();
+        let filter = self
+            .filter
+            .as_ref()
+            .map(|f| f.compile(&headers))
+            .transpose()?;
+        let rows = rdr.into_records().filter(move |r| match (r, &filter) {
+            (Ok(row), Some(filter)) => filter.eval(row),
+            _ => true,
+        });
+
+        let (out_headers, mut out_rows): (Vec<String>, Rows<'_>) = match self.group {
+            Some(ref group) => self.grouped(&headers, group, rows)?,
+            None => {
+                let selected = match self.select {
+                    Some(ref columns) => columns
+                        .iter()
+                        .map(|c| index_of(&headers, c))
+                        .collect::<Result<Vec<_>>>()?,
+                    None => (0..headers.len()).collect(),
+                };
+                let out_headers = selected.iter().map(|i| headers[*i].clone()).collect();
+                let project = move |row: csv::StringRecord| {
+                    selected
+                        .iter()
+                        .map(|i| row.get(*i).unwrap_or("").to_string())
+                        .collect::<Vec<_>>()
+                };
+                if self.sort.is_empty() {
+                    // 流式处理：读一行，写一行
+                    let rows = rows.map(move |r| Ok(project(r?)));
+                    (out_headers, Box::new(rows))
+                } else {
+                    // 排序可以使用没有被select的列，所以先在完整的行上排序，再投影
+                    let mut all = rows.collect::<csv::Result<Vec<_>>>()?;
+                    let keys = self.sort_indices(&headers)?;
+                    all.sort_by(|a, b| compare_rows(&keys, |i| a.get(i), |i| b.get(i)));
+                    (
+                        out_headers,
+                        Box::new(all.into_iter().map(move |r| Ok(project(r)))),
+                    )
+                }
+            }
+        };
+
+        let mut sink = Sink::new(writer, output, out_headers)?;
+        let mut written = 0;
+        while self.limit.is_none_or(|limit| written < limit) {
+            match out_rows.next() {
+                Some(row) => sink.push(row?)?,
+                None => break,
+            }
+            written += 1;
+        }
+        sink.finish()
+    }
+
+    fn sort_indices(&self, headers: &[String]) -> Result<Vec<(usize, bool)>> {
+        self.sort
+            .iter()
+            .map(|key| Ok((index_of(headers, &key.column)?, key.descending)))
+            .collect()
+    }
+
+    fn grouped(
+        &self,
+        headers: &[String],
+        group: &GroupBy,
+        rows: impl Iterator<Item = csv::Result<csv::StringRecord>>,
+    ) -> Result<(Vec<String>, Rows<'static>)> {
+        let key_columns = group
+            .columns
+            .iter()
+            .map(|c| index_of(headers, c))
+            .collect::<Result<Vec<_>>>()?;
+        let aggregate_columns = group
+            .aggregates
+            .iter()
+            .map(|a| a.column.as_ref().map(|c| index_of(headers, c)).transpose())
+            .collect::<Result<Vec<_>>>()?;
+
+        // 用Vec保存分组，保证输出顺序和分组第一次出现的顺序一致
+        let mut index: HashMap<Vec<String>, usize> = HashMap::new();
+        let mut groups: Vec<(Vec<String>, Vec<Accumulator>)> = Vec::new();
+        for row in rows {
+            let row = row?;
+            let key: Vec<String> = key_columns
+                .iter()
+                .map(|i| row.get(*i).unwrap_or("").to_string())
+                .collect();
+            let slot = *index.entry(key.clone()).or_insert_with(|| {
+                let accumulators = group
+                    .aggregates
+                    .iter()
+                    .map(|a| Accumulator::new(a.function))
+                    .collect();
+                groups.push((key, accumulators));
+                groups.len() - 1
+            });
+            for (acc, column) in groups[slot].1.iter_mut().zip(&aggregate_columns) {
+                // count() 没有参数，统计的是行数
+                acc.add(column.map_or(Some("*"), |i| row.get(i)));
+            }
+        }
+
+        let out_headers: Vec<String> = group
+            .columns
+            .iter()
+            .cloned()
+            .chain(group.aggregates.iter().map(Aggregate::name))
+            .collect();
+        let mut out_rows: Vec<Vec<String>> = groups
+            .into_iter()
+            .map(|(mut key, accumulators)| {
+                key.extend(accumulators.iter().map(Accumulator::finish));
+                key
+            })
+            .collect();
+        let keys = self.sort_indices(&out_headers)?;
+        out_rows.sort_by(|a, b| {
+            compare_rows(
+                &keys,
+                |i| a.get(i).map(String::as_str),
+                |i| b.get(i).map(String::as_str),
+            )
+        });
+        Ok((out_headers, Box::new(out_rows.into_iter().map(Ok))))
+    }
+}
+
+fn compare_rows<'a, 'b>(
+    keys: &[(usize, bool)],
+    a: impl Fn(usize) -> Option<&'a str>,
+    b: impl Fn(usize) -> Option<&'b str>,
+) -> Ordering {
+    keys.iter()
+        .map(|&(i, descending)| {
+            let ordering = compare_cells(a(i).unwrap_or(""), b(i).unwrap_or(""));
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        })
+        .find(|o| *o != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLANETS: &str = "name,radius,distance_from_sun,gravity\n\
+                           Mercury,0.38,0.47,0.38\n\
+                           Venus,0.95,0.73,0.9\n\
+                           Earth,1,1,1\n\
+                           Mars,0.53,1.67,0.38\n\
+                           Jupiter,11.21,5.46,2.53\n\
+                           Neptune,3.88,30.33,1.14\n";
+
+    fn run(query: &str, output: Output) -> String {
+        let query: Query = query.parse().expect("Failed to parse query");
+        let mut out = Vec::new();
+        query
+            .execute(PLANETS.as_bytes(), &mut out, output)
+            .expect("Failed to execute query");
+        String::from_utf8(out).expect("Output is not UTF-8")
+    }
+
+    #[test]
+    fn filters_and_sorts() {
+        assert_eq!(
+            "name,gravity\nJupiter,2.53\nNeptune,1.14\n",
+            run(
+                r#"select name, gravity where gravity >= 1 and name != "Earth" sort by radius desc"#,
+                Output::Csv
+            )
+        );
+    }
+
+    #[test]
+    fn groups_and_aggregates() {
+        assert_eq!(
+            "gravity,n,max(radius)\n0.38,2,0.53\n1,1,1\n",
+            run(
+                "where not (gravity > 1 or radius > 0.9 and radius < 1) \
+                 group by gravity agg count() as n, max(radius) sort by n desc limit 2",
+                Output::Csv
+            )
+        );
+    }
+
+    #[test]
+    fn sum_and_avg_skip_values_that_are_not_numbers() {
+        assert_eq!(
+            "gravity,sum(name),avg(name),sum(radius),avg(radius)\n0.38,,,0.91,0.455\n",
+            run(
+                "where gravity < 0.5 group by gravity \
+                 agg sum(name), avg(name), sum(radius), avg(radius)",
+                Output::Csv
+            )
+        );
+    }
+
+    #[test]
+    fn renders_aligned_table() {
+        assert_eq!(
+            "name    | radius\n--------+-------\nJupiter |  11.21\nNeptune |   3.88\n",
+            run("select name, radius where radius > 2", Output::Table)
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        match "where gravity >".parse::<Query>() {
+            Err(QueryError::Parse { position, .. }) => assert_eq!(15, position),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        match "limit 1 limit 2".parse::<Query>() {
+            Err(QueryError::Parse { message, .. }) => assert_eq!("duplicate 'limit'", message),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        let query: Query = "where moons > 1".parse().expect("Failed to parse query");
+        match query.execute(PLANETS.as_bytes(), io::sink(), Output::Csv) {
+            Err(QueryError::UnknownColumn(column)) => assert_eq!("moons", column),
+            other => panic!("Expected an unknown column, got {:?}", other),
+        }
+    }
+}
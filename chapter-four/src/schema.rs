@@ -0,0 +1,400 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    io::Read,
+    result,
+};
+
+/// 一列可能的类型，按照“能容纳的值越来越多”排列
+/// Int可以被放宽成Float，其它类型之间冲突时只能退化成String
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColumnType {
+    Int,
+    Float,
+    Bool,
+    Date,
+    String,
+}
+
+impl ColumnType {
+    /// 单个（非空）值的类型
+    fn of(value: &str) -> Self {
+        if value.parse::<i64>().is_ok() {
+            Self::Int
+        } else if value.parse::<f64>().is_ok() {
+            Self::Float
+        } else if parse_bool(value).is_some() {
+            Self::Bool
+        } else if Date::parse(value).is_some() {
+            Self::Date
+        } else {
+            Self::String
+        }
+    }
+
+    fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Int, Self::Float) | (Self::Float, Self::Int) => Self::Float,
+            _ => Self::String,
+        }
+    }
+
+    /// 把一个单元格转换成这一列的类型，空字符串表示null
+    pub fn parse(self, value: &str) -> Option<Value> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Some(Value::Null);
+        }
+        match self {
+            Self::Int => value.parse().ok().map(Value::Int),
+            Self::Float => value.parse().ok().map(Value::Float),
+            Self::Bool => parse_bool(value).map(Value::Bool),
+            Self::Date => Date::parse(value).map(Value::Date),
+            Self::String => Some(Value::String(value.to_string())),
+        }
+    }
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Int => "int",
+            Self::Float => "float",
+            Self::Bool => "bool",
+            Self::Date => "date",
+            Self::String => "string",
+        };
+        f.pad(name)
+    }
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    if value.eq_ignore_ascii_case("true") {
+        Some(true)
+    } else if value.eq_ignore_ascii_case("false") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// ISO 8601 格式的日期（YYYY-MM-DD），为了这一个格式不值得引入chrono
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    pub fn parse(value: &str) -> Option<Self> {
+        let mut parts = value.splitn(3, '-');
+        let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return None;
+        }
+        let date = Self {
+            year: year.parse().ok()?,
+            month: month.parse().ok()?,
+            day: day.parse().ok()?,
+        };
+        let leap = date.year % 4 == 0 && (date.year % 100 != 0 || date.year % 400 == 0);
+        let days_in_month = match date.month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return None,
+        };
+        (1..=days_in_month).contains(&date.day).then_some(date)
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// 按照推断出的类型转换后的单元格
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Date(Date),
+    String(String),
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Int(v) => write!(f, "{}", v),
+            Self::Float(v) => write!(f, "{}", v),
+            Self::Bool(v) => write!(f, "{}", v),
+            Self::Date(v) => write!(f, "{}", v),
+            Self::String(v) => write!(f, "{:?}", v),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub ty: ColumnType,
+    pub nullable: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schema {
+    pub has_headers: bool,
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Csv(csv::Error),
+    /// 样本之后的某一行不符合推断出的类型
+    Type {
+        line: u64,
+        column: String,
+        value: String,
+        expected: ColumnType,
+    },
+}
+
+pub type Result<T> = result::Result<T, SchemaError>;
+
+impl error::Error for SchemaError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Csv(ref err) => Some(err),
+            Self::Type { .. } => None,
+        }
+    }
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Csv(ref err) => write!(f, "CSV error: {}", err),
+            Self::Type {
+                line,
+                ref column,
+                ref value,
+                expected,
+            } => write!(
+                f,
+                "Type error on line {}: column '{}' expected {}, found {:?}",
+                line, column, expected, value
+            ),
+        }
+    }
+}
+
+impl From<csv::Error> for SchemaError {
+    fn from(value: csv::Error) -> Self {
+        Self::Csv(value)
+    }
+}
+
+/// 读取最多 `sample_rows` 行数据来推断每一列的类型和是否可以为空，同时判断第一行是不是header
+///
+/// 判断header的方法借鉴自Python的 csv.Sniffer：如果某一列在样本中是int/float/bool/date，而第一行的值却不是这个类型，
+/// 那么第一行很可能是header。如果所有列都是字符串就没有依据可言了，这时按照 csv crate 的默认行为认为存在header
+pub fn infer_schema<R: Read>(reader: R, sample_rows: usize) -> csv::Result<Schema> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+    let mut records = rdr.records();
+    let first = match records.next() {
+        Some(first) => first?,
+        None => {
+            return Ok(Schema {
+                has_headers: false,
+                columns: Vec::new(),
+            });
+        }
+    };
+
+    let mut types: Vec<Option<ColumnType>> = vec![None; first.len()];
+    let mut nullable = vec![false; first.len()];
+    for record in records.take(sample_rows) {
+        let record = record?;
+        if record.len() > types.len() {
+            types.resize(record.len(), None);
+            nullable.resize(record.len(), true);
+        }
+        for (i, ty) in types.iter_mut().enumerate() {
+            let value = record.get(i).unwrap_or("").trim();
+            if value.is_empty() {
+                nullable[i] = true;
+                continue;
+            }
+            let value_type = ColumnType::of(value);
+            *ty = Some(ty.map_or(value_type, |ty| ty.merge(value_type)));
+        }
+    }
+
+    let votes = types
+        .iter()
+        .enumerate()
+        .filter_map(|(i, ty)| match ty {
+            Some(ColumnType::String) | None => None,
+            Some(ty) => Some(ty.parse(first.get(i).unwrap_or("")).is_none()),
+        })
+        .collect::<Vec<_>>();
+    let has_headers = if votes.is_empty() {
+        first.iter().all(|name| !name.trim().is_empty())
+    } else {
+        votes.iter().filter(|v| **v).count() * 2 > votes.len()
+    };
+
+    // 如果第一行是数据，它同样要参与类型推断
+    if !has_headers {
+        for (i, ty) in types.iter_mut().enumerate() {
+            let value = first.get(i).unwrap_or("").trim();
+            if value.is_empty() {
+                nullable[i] = true;
+            } else {
+                let value_type = ColumnType::of(value);
+                *ty = Some(ty.map_or(value_type, |ty| ty.merge(value_type)));
+            }
+        }
+    }
+
+    let columns = types
+        .into_iter()
+        .zip(nullable)
+        .enumerate()
+        .map(|(i, (ty, nullable))| Column {
+            name: match first.get(i) {
+                Some(name) if has_headers => name.trim().to_string(),
+                _ => format!("column_{}", i + 1),
+            },
+            // 样本中全是空值的列无法推断，只能当作字符串
+            ty: ty.unwrap_or(ColumnType::String),
+            nullable: nullable || ty.is_none(),
+        })
+        .collect();
+
+    Ok(Schema {
+        has_headers,
+        columns,
+    })
+}
+
+impl Schema {
+    /// 按照schema读取所有行，每一行返回转换好的值
+    /// 样本之外的行如果不符合推断的类型，或者在不可为空的列中出现了空值，会返回 SchemaError::Type
+    pub fn typed_rows<'a, R: Read + 'a>(
+        &'a self,
+        reader: R,
+    ) -> impl Iterator<Item = Result<Vec<Value>>> + 'a {
+        let rdr = csv::ReaderBuilder::new()
+            .has_headers(self.has_headers)
+            .flexible(true)
+            .from_reader(reader);
+        rdr.into_records().map(move |record| {
+            let record = record?;
+            let line = record.position().map_or(0, |p| p.line());
+            self.columns
+                .iter()
+                .enumerate()
+                .map(|(i, column)| {
+                    let raw = record.get(i).unwrap_or("");
+                    match column.ty.parse(raw) {
+                        Some(Value::Null) if !column.nullable => None,
+                        value => value,
+                    }
+                    .ok_or_else(|| SchemaError::Type {
+                        line,
+                        column: column.name.clone(),
+                        value: raw.to_string(),
+                        expected: column.ty,
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+impl Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "has headers: {}", self.has_headers)?;
+        let width = self.columns.iter().map(|c| c.name.len()).max().unwrap_or(0);
+        for column in &self.columns {
+            writeln!(
+                f,
+                "  {:<width$}  {:<6}  {}",
+                column.name,
+                column.ty,
+                if column.nullable { "nullable" } else { "" },
+                width = width
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLANETS: &str = "name,radius,distance_from_sun,gravity\n\
+                           Mercury,0.38,0.47,0.38\n\
+                           Earth,1,1,1\n\
+                           Mars,0.53,1.67,\n";
+
+    #[test]
+    fn infers_planet_schema() {
+        let schema = infer_schema(PLANETS.as_bytes(), 100).expect("Failed to infer schema");
+        assert!(schema.has_headers);
+        let types: Vec<_> = schema.columns.iter().map(|c| (c.ty, c.nullable)).collect();
+        assert_eq!(
+            vec![
+                (ColumnType::String, false),
+                (ColumnType::Float, false),
+                (ColumnType::Float, false),
+                (ColumnType::Float, true),
+            ],
+            types
+        );
+        assert_eq!("gravity", schema.columns[3].name);
+    }
+
+    #[test]
+    fn detects_missing_headers() {
+        let csv = "2018-01-12,true,3\n2020-02-29,false,4\n";
+        let schema = infer_schema(csv.as_bytes(), 100).expect("Failed to infer schema");
+        assert!(!schema.has_headers);
+        assert_eq!("column_1", schema.columns[0].name);
+        assert_eq!(ColumnType::Date, schema.columns[0].ty);
+        assert_eq!(ColumnType::Bool, schema.columns[1].ty);
+        assert_eq!(ColumnType::Int, schema.columns[2].ty);
+    }
+
+    #[test]
+    fn typed_rows_report_values_outside_the_sample() {
+        let csv = "id,score\n1,10\n2,eleven\n";
+        let schema = infer_schema(csv.as_bytes(), 1).expect("Failed to infer schema");
+        let rows: Vec<_> = schema.typed_rows(csv.as_bytes()).collect();
+        assert_eq!(
+            vec![Value::Int(1), Value::Int(10)],
+            *rows[0].as_ref().expect("First row should be valid")
+        );
+        match rows[1] {
+            Err(SchemaError::Type {
+                line, ref column, ..
+            }) => {
+                assert_eq!(3, line);
+                assert_eq!("score", column);
+            }
+            ref other => panic!("Expected a type error, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    io::Read,
+};
+
+use serde::de::DeserializeOwned;
+
+/// 用 `?` 读取CSV时，第一条坏数据就会让整个读取过程结束。对于需要人工修正的文件，更好的做法是检查每一行，把所有问题一次性报告出来
+///
+/// 检查分两步
+/// 1. 反序列化：类型不对、缺少字段等，由Serde报告出错的字段下标，我们再根据header换成列名
+/// 2. 字段规则：反序列化成功之后，对结构体执行用户提供的规则，例如 `radius > 0`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub line: u64,
+    pub column: Option<String>,
+    pub value: Option<String>,
+    pub message: String,
+}
+
+impl Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line)?;
+        if let Some(ref column) = self.column {
+            write!(f, ", column '{}'", column)?;
+        }
+        if let Some(ref value) = self.value {
+            write!(f, ", value {:?}", value)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// 一条字段级别的规则，column只用来定位出错的单元格
+pub struct Rule<T> {
+    column: String,
+    description: String,
+    check: Box<dyn Fn(&T) -> bool>,
+}
+
+pub struct Validator<T> {
+    rules: Vec<Rule<T>>,
+}
+
+impl<T: DeserializeOwned> Default for Validator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: DeserializeOwned> Validator<T> {
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// check返回false表示这一行违反了规则
+    pub fn rule<F>(mut self, column: &str, description: &str, check: F) -> Self
+    where
+        F: Fn(&T) -> bool + 'static,
+    {
+        self.rules.push(Rule {
+            column: column.to_string(),
+            description: description.to_string(),
+            check: Box::new(check),
+        });
+        self
+    }
+
+    /// 读取所有行，不会因为某一行出错而停止
+    /// 只有读不到header这种整个文件都无法处理的情况才会返回错误
+    pub fn validate<R: Read>(&self, reader: R) -> csv::Result<Report<T>> {
+        let mut rdr = csv::Reader::from_reader(reader);
+        let headers = rdr.headers()?.clone();
+        let mut report = Report {
+            rows: 0,
+            valid: Vec::new(),
+            issues: Vec::new(),
+        };
+
+        for result in rdr.records() {
+            report.rows += 1;
+            let record = match result {
+                Ok(record) => record,
+                Err(e) => {
+                    report.issues.push(Issue {
+                        line: e.position().map_or(0, |p| p.line()),
+                        column: None,
+                        value: None,
+                        message: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let line = record.position().map_or(0, |p| p.line());
+
+            let row: T = match record.deserialize(Some(&headers)) {
+                Ok(row) => row,
+                Err(e) => {
+                    let field = match e.kind() {
+                        csv::ErrorKind::Deserialize { err, .. } => err.field(),
+                        _ => None,
+                    };
+                    let field = field.map(|i| i as usize);
+                    report.issues.push(Issue {
+                        line,
+                        column: field.and_then(|i| headers.get(i)).map(str::to_string),
+                        value: field.and_then(|i| record.get(i)).map(str::to_string),
+                        message: match e.kind() {
+                            csv::ErrorKind::Deserialize { err, .. } => err.kind().to_string(),
+                            kind => format!("{:?}", kind),
+                        },
+                    });
+                    continue;
+                }
+            };
+
+            let mut valid = true;
+            for rule in self.rules.iter().filter(|rule| !(rule.check)(&row)) {
+                valid = false;
+                let index = headers.iter().position(|h| h == rule.column);
+                report.issues.push(Issue {
+                    line,
+                    column: Some(rule.column.clone()),
+                    value: index.and_then(|i| record.get(i)).map(str::to_string),
+                    message: rule.description.clone(),
+                });
+            }
+            if valid {
+                report.valid.push(row);
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// 检查结果，valid中是通过了所有检查的行
+pub struct Report<T> {
+    pub rows: usize,
+    pub valid: Vec<T>,
+    pub issues: Vec<Issue>,
+}
+
+impl<T> Report<T> {
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    pub fn invalid_rows(&self) -> usize {
+        self.rows - self.valid.len()
+    }
+}
+
+impl<T> Display for Report<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} rows checked, {} valid, {} invalid, {} problems found",
+            self.rows,
+            self.valid.len(),
+            self.invalid_rows(),
+            self.issues.len()
+        )?;
+        for issue in &self.issues {
+            writeln!(f, "  {}", issue)?;
+        }
+
+        // 按列统计，方便看出哪一列的问题最多
+        let mut per_column = BTreeMap::new();
+        for issue in &self.issues {
+            let column = issue.column.as_deref().unwrap_or("(row)");
+            *per_column.entry(column).or_insert(0) += 1;
+        }
+        if !per_column.is_empty() {
+            writeln!(f, "Problems per column:")?;
+            for (column, count) in per_column {
+                writeln!(f, "  {}: {}", column, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Planet {
+        #[allow(dead_code)]
+        name: String,
+        radius: f32,
+    }
+
+    #[test]
+    fn collects_every_problem_in_one_pass() {
+        let csv = "name,radius\nMercury,0.38\nVenus,big\nMars,-1\nEarth,1\nPluto\n";
+        let report = Validator::new()
+            .rule("radius", "radius must be greater than 0", |p: &Planet| {
+                p.radius > 0.
+            })
+            .validate(csv.as_bytes())
+            .expect("Failed to read headers");
+
+        assert_eq!(5, report.rows);
+        assert_eq!(2, report.valid.len());
+        assert_eq!(3, report.issues.len());
+
+        let venus = &report.issues[0];
+        assert_eq!(3, venus.line);
+        assert_eq!(Some("radius"), venus.column.as_deref());
+        assert_eq!(Some("big"), venus.value.as_deref());
+
+        let mars = &report.issues[1];
+        assert_eq!(4, mars.line);
+        assert_eq!(Some("-1"), mars.value.as_deref());
+        assert_eq!("radius must be greater than 0", mars.message);
+
+        assert_eq!(6, report.issues[2].line);
+    }
+}
@@ -1,3 +1,5 @@
+use chapter_one::rules::{Rules, Violations};
+
 fn main() {
     // 我们只关心需要的选项，而其它的选项使用默认值来替代
     let normal_burger = BurgerBuilder::new().build();
@@ -14,10 +16,18 @@ fn main() {
         veggie_biamac.print();
     }
 
-    // 检查无效选项
-    let invalid_burger = BurgerBuilder::new().vegetarian(true).bacon(true).build();
+    // 检查无效选项，所有违反的规则都会被报告
+    let invalid_burger = BurgerBuilder::new()
+        .vegetarian(true)
+        .bacon(true)
+        .patty_count(5)
+        .build();
     if let Err(e) = invalid_burger {
-        println!("Failed to print burger: {}", e)
+        println!("Failed to print burger: {}", e);
+        // 界面可以根据代码和选项名标出每个有问题的选项
+        for violation in &e.0 {
+            println!("{} -> {:?}", violation.code(), violation.options());
+        }
     }
 
     // 只要不调用build，可以无限重用builder
@@ -80,7 +90,7 @@ impl BurgerBuilder {
 
     /// 构建对象，返回 Result 是因为存在不合理的选项使我们无法构建成功
     /// 如果你的结构体不会因为无效配置创建失败，那么直接返回该结构体即可
-    fn build(&self) -> Result<Burger, String> {
+    fn build(&self) -> Result<Burger, Violations> {
         let burger = Burger {
             patty_count: self.patty_count,
             vegetarian: self.vegetarian,
@@ -89,14 +99,23 @@ impl BurgerBuilder {
             salad: self.salad,
         };
         // 检查无效配置
-        if burger.vegetarian && burger.bacon {
-            Err("Sorry, but we don't server vegetarian bacon yet".to_string())
-        } else {
-            Ok(burger)
-        }
+        rules().check(&burger)?;
+        Ok(burger)
     }
 }
 
+/// 无效的配置写成规则，而不是 build 中的 if
+fn rules() -> Rules<Burger> {
+    Rules::<Burger>::new()
+        .exclusive(
+            "burger.vegetarian_bacon",
+            &[("vegetarian", |b| b.vegetarian), ("bacon", |b| b.bacon)],
+        )
+        .range("burger.patty_count", "patty_count", 1..=4, |b| {
+            b.patty_count.into()
+        })
+}
+
 struct Burger {
     patty_count: i32,
     vegetarian: bool,
@@ -125,3 +144,24 @@ impl Burger {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_violated_rule_is_reported() {
+        let Err(violations) = BurgerBuilder::new()
+            .vegetarian(true)
+            .bacon(true)
+            .patty_count(5)
+            .build()
+        else {
+            panic!("A vegetarian burger with bacon and 5 patties was built");
+        };
+        assert_eq!(2, violations.0.len());
+        assert!(violations.contains("burger.vegetarian_bacon"));
+        assert!(violations.contains("burger.patty_count"));
+        assert!(BurgerBuilder::new().patty_count(4).build().is_ok());
+    }
+}
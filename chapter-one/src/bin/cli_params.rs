@@ -1,5 +1,6 @@
 use std::env;
 
+/// 直接读取 env::args，需要选项、帮助信息和错误提示时使用 chapter_one::cli，见 cli_parser.rs
 fn main() {
     println!("Got following parameters: ");
     for arg in env::args() {
@@ -8,16 +9,17 @@ fn main() {
 
     // 使用迭代器访问某个参数
     // 使用迭代器强迫你在编译期进行有效性检查
+    // 迭代器会消费它返回的值以及之前跳过的值：nth(n) 跳过 n 个值，所以连续调用 nth(0)、nth(1) 会漏掉一个参数
+    // 按顺序读取时用 next 就够了
     let mut args = env::args();
     // 大部分OS的第一个命令行参数都是执行程序本身
-    if let Some(arg) = args.nth(0) {
+    if let Some(arg) = args.next() {
         println!("The path to this program is: {}", arg)
     }
-    if let Some(arg) = args.nth(1) {
+    if let Some(arg) = args.next() {
         println!("The first parameter is: {}", arg)
     }
-    // 之前的值已经被消费，这里不会打印
-    if let Some(arg) = args.nth(2) {
+    if let Some(arg) = args.next() {
         println!("The second parameter is: {}", arg)
     }
 
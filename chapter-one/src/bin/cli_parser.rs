@@ -0,0 +1,65 @@
+use chapter_one::cli::{self, Arg, Command};
+
+/// cli_params.rs 中直接读取 env::args() 的版本见那里，这里用 chapter_one::cli 声明参数再解析
+///
+/// 试试：
+/// * cli_parser --help
+/// * cli_parser greet -n 3 --shout Jan
+/// * cli_parser sum -- 1 -2 3.5
+/// * cli_parser greet -n many Jan
+fn main() {
+    let command = Command::new("cli_parser")
+        .about("Demonstrates chapter_one::cli")
+        .arg(
+            Arg::flag("verbose")
+                .short('v')
+                .help("Print the parsed arguments"),
+        )
+        .subcommand(
+            Command::new("greet")
+                .about("Greet someone")
+                .arg(Arg::positional("name").required(true).help("Who to greet"))
+                .arg(
+                    Arg::option("times")
+                        .short('n')
+                        .default_value("1")
+                        .help("How often to greet"),
+                )
+                .arg(Arg::flag("shout").short('s').help("Use upper case")),
+        )
+        .subcommand(
+            Command::new("sum").about("Add numbers").arg(
+                Arg::positional("numbers")
+                    .multiple(true)
+                    .value_name("NUMBER"),
+            ),
+        )
+        .subcommand_required(true);
+    let matches = command.parse_env();
+    if matches.flag("verbose") {
+        println!("{:#?}", matches);
+    }
+
+    match matches.subcommand() {
+        Some(("greet", greet)) => {
+            let times: usize = greet
+                .get("times")
+                .unwrap_or_else(|e| cli::exit(&e))
+                .expect("times has a default value");
+            let name = greet.value("name").expect("name is required");
+            let greeting = format!("Hello {}!", name);
+            for _ in 0..times {
+                if greet.flag("shout") {
+                    println!("{}", greeting.to_uppercase());
+                } else {
+                    println!("{}", greeting);
+                }
+            }
+        }
+        Some(("sum", sum)) => {
+            let numbers: Vec<f64> = sum.get_all("numbers").unwrap_or_else(|e| cli::exit(&e));
+            println!("{}", numbers.iter().sum::<f64>());
+        }
+        _ => unreachable!("The subcommand is required"),
+    }
+}
@@ -1,12 +1,20 @@
 use std::env;
 
+use chapter_one::env::Dotenv;
+
 /// 根据Twelve-Factor App，https://12factor.net/，你应该在环境变量中存储你的配置
 /// 这意味着你在不同的部署环境中可以改变传递的值，例如端口号、域、数据库信息
 /// 很多程序也使用环境变量和其它程序通信
 ///
 /// 更好的方式是创建一个文件 .env 包含 k-v对形式的配置，在构建的时候加载到进程中
-/// dotenv crate
+/// dotenv crate，这里用的是 chapter_one::env 中的实现
 fn main() {
+    // 在创建任何线程之前加载 .env，已经存在的环境变量不会被覆盖
+    match Dotenv::new().load(".env") {
+        Ok(set) => println!("Loaded {:?} from .env", set),
+        Err(e) => println!("Couldn't load .env: {}", e),
+    }
+
     // 遍历当前进程的所有环境变量
     println!("Listing all env vars: ");
     // env::vars()，访问执行时为当前进程设置的所有环境变了
@@ -19,6 +27,7 @@ fn main() {
     // 为当前进程设置环境变量
     unsafe {
         // 只会影响当前进程的环境变量
+        // 但是会影响进程中的所有线程，测试中应该用 chapter_one::env_guard::EnvGuard 修改，它会在结束时恢复原来的值
         env::set_var(key, "8080");
     }
 
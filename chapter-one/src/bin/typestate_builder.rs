@@ -0,0 +1,30 @@
+use chapter_one::burger::{Bun, BurgerBuilder};
+
+/// 和 builder.rs 一样构建汉堡，但是肉饼数量和面包必须设置，素食汉堡也不能加培根
+/// 这些错误在编译时就会被发现，所以 build 直接返回 Burger，tests/ui 中是无法编译的例子
+fn main() {
+    let normal_burger = BurgerBuilder::new().patty_count(1).bun(Bun::Sesame).build();
+    let cheese_burger = BurgerBuilder::new()
+        .bun(Bun::Brioche)
+        .patty_count(1)
+        .cheese(true)
+        .build();
+    let veggie_biamac = BurgerBuilder::new()
+        .vegetarian()
+        .patty_count(2)
+        .bun(Bun::Lettuce)
+        .build();
+    normal_burger.print();
+    cheese_burger.print();
+    veggie_biamac.print();
+
+    // 下面这行无法编译：Vegetarian 状态的 builder 没有 bacon 方法
+    // BurgerBuilder::new().vegetarian().bacon();
+
+    // 设置好必需值的 builder 可以无限重用
+    let bacon_burger_builder = BurgerBuilder::new().bacon().patty_count(1).bun(Bun::Sesame);
+    for i in 1..4 {
+        println!("bacon burger number {} is ready!", i);
+        bacon_burger_builder.build().print();
+    }
+}
@@ -0,0 +1,233 @@
+/// src/bin/builder.rs 中的 BurgerBuilder 在 build 时才能发现素食汉堡加了培根，只能返回 Result
+/// 这里的 BurgerBuilder 把 builder 的状态放进泛型参数中，让编译器替我们检查：
+/// * `P`：肉饼数量，没有设置时是 [`Missing`]，设置之后是 u8
+/// * `B`：面包，没有设置时是 [`Missing`]，设置之后是 [`Bun`]
+/// * `D`：[`Regular`]、[`Vegetarian`] 或者 [`WithBacon`]，素食和培根只能从 Regular 转换过去，所以不可能同时出现
+///
+/// 设置必需值的方法消耗旧的 builder 并返回另一个类型的 builder，
+/// build 只为 `BurgerBuilder<u8, Bun, D>` 实现，所以缺少必需值时根本无法调用，也就不需要返回 Result
+///
+/// 状态都保存在类型中，没有运行时开销：Missing 和 D 都是零大小的类型
+#[derive(Debug, Clone)]
+pub struct BurgerBuilder<P, B, D> {
+    patty_count: P,
+    bun: B,
+    diet: D,
+    cheese: bool,
+    salad: bool,
+}
+
+/// 还没有设置的必需值
+#[derive(Debug, Clone, Copy)]
+pub struct Missing;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bun {
+    Sesame,
+    Brioche,
+    /// 用生菜代替面包
+    Lettuce,
+}
+
+/// 没有选择素食，也没有加培根
+#[derive(Debug, Clone, Copy)]
+pub struct Regular;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Vegetarian;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WithBacon;
+
+/// build 需要从 D 得到两个 bool，只有上面的三种状态实现了它
+pub trait Diet {
+    const VEGETARIAN: bool;
+    const BACON: bool;
+}
+
+impl Diet for Regular {
+    const VEGETARIAN: bool = false;
+    const BACON: bool = false;
+}
+
+impl Diet for Vegetarian {
+    const VEGETARIAN: bool = true;
+    const BACON: bool = false;
+}
+
+impl Diet for WithBacon {
+    const VEGETARIAN: bool = false;
+    const BACON: bool = true;
+}
+
+impl BurgerBuilder<Missing, Missing, Regular> {
+    /// 可选的值使用标准值，必需的值还没有设置
+    pub fn new() -> Self {
+        Self {
+            patty_count: Missing,
+            bun: Missing,
+            diet: Regular,
+            cheese: false,
+            salad: true,
+        }
+    }
+}
+
+impl Default for BurgerBuilder<Missing, Missing, Regular> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, B, D> BurgerBuilder<P, B, D> {
+    /// 可以重复设置，后面的值覆盖前面的
+    pub fn patty_count(self, val: u8) -> BurgerBuilder<u8, B, D> {
+        BurgerBuilder {
+            patty_count: val,
+            bun: self.bun,
+            diet: self.diet,
+            cheese: self.cheese,
+            salad: self.salad,
+        }
+    }
+
+    pub fn bun(self, val: Bun) -> BurgerBuilder<P, Bun, D> {
+        BurgerBuilder {
+            patty_count: self.patty_count,
+            bun: val,
+            diet: self.diet,
+            cheese: self.cheese,
+            salad: self.salad,
+        }
+    }
+
+    // 不改变状态的可选值和原来一样
+    pub fn cheese(mut self, val: bool) -> Self {
+        self.cheese = val;
+        self
+    }
+
+    pub fn salad(mut self, val: bool) -> Self {
+        self.salad = val;
+        self
+    }
+
+    fn diet<T>(self, diet: T) -> BurgerBuilder<P, B, T> {
+        BurgerBuilder {
+            patty_count: self.patty_count,
+            bun: self.bun,
+            diet,
+            cheese: self.cheese,
+            salad: self.salad,
+        }
+    }
+}
+
+impl<P, B> BurgerBuilder<P, B, Regular> {
+    /// 之后不能再调用 bacon
+    pub fn vegetarian(self) -> BurgerBuilder<P, B, Vegetarian> {
+        self.diet(Vegetarian)
+    }
+
+    /// 之后不能再调用 vegetarian
+    pub fn bacon(self) -> BurgerBuilder<P, B, WithBacon> {
+        self.diet(WithBacon)
+    }
+}
+
+impl<D: Diet> BurgerBuilder<u8, Bun, D> {
+    /// 能调用 build 就说明必需的值都已经设置，素食和培根也不会同时出现，所以不返回 Result
+    /// 值本身是否合理不由类型检查，例如 `patty_count(0)` 仍然可以编译，builder.rs 中用规则拒绝它
+    /// 和原来一样只借用 builder，可以用同一个 builder 构建多个汉堡
+    pub fn build(&self) -> Burger {
+        Burger {
+            patty_count: self.patty_count,
+            bun: self.bun,
+            vegetarian: D::VEGETARIAN,
+            cheese: self.cheese,
+            bacon: D::BACON,
+            salad: self.salad,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Burger {
+    pub patty_count: u8,
+    pub bun: Bun,
+    pub vegetarian: bool,
+    pub cheese: bool,
+    pub bacon: bool,
+    pub salad: bool,
+}
+
+impl Burger {
+    pub fn print(&self) {
+        let pretty_patties = if self.patty_count == 1 {
+            "patty"
+        } else {
+            "patties"
+        };
+        let pretty_bool = |val| if val { "" } else { "no " };
+        let pretty_vegetarian = if self.vegetarian { "vegetarian " } else { "" };
+        println!(
+            "This is a {}burger on a {:?} bun with {} {}, {}cheese, {}bacon and {}salad",
+            pretty_vegetarian,
+            self.bun,
+            self.patty_count,
+            pretty_patties,
+            pretty_bool(self.cheese),
+            pretty_bool(self.bacon),
+            pretty_bool(self.salad)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_values_can_be_set_in_any_order() {
+        let first = BurgerBuilder::new()
+            .patty_count(2)
+            .bun(Bun::Sesame)
+            .cheese(true)
+            .build();
+        let second = BurgerBuilder::new()
+            .cheese(true)
+            .bun(Bun::Sesame)
+            .patty_count(2)
+            .build();
+        assert_eq!(first, second);
+        assert_eq!(
+            Burger {
+                patty_count: 2,
+                bun: Bun::Sesame,
+                vegetarian: false,
+                cheese: true,
+                bacon: false,
+                salad: true,
+            },
+            first
+        );
+    }
+
+    #[test]
+    fn diet_is_carried_by_the_type() {
+        let veggie = BurgerBuilder::new()
+            .vegetarian()
+            .bun(Bun::Lettuce)
+            .patty_count(1)
+            .build();
+        assert!(veggie.vegetarian && !veggie.bacon);
+
+        let builder = BurgerBuilder::new()
+            .bacon()
+            .patty_count(1)
+            .bun(Bun::Brioche);
+        let bacon = builder.build();
+        assert!(bacon.bacon && !bacon.vegetarian);
+        assert_eq!(bacon, builder.salad(true).build());
+    }
+}
@@ -0,0 +1,727 @@
+use std::{
+    collections::BTreeMap,
+    env, error,
+    fmt::{self, Display, Write},
+    process,
+    str::FromStr,
+};
+
+/// cli_params.rs 直接按下标读取 env::args()，只适合一两个参数的情况
+///
+/// 这里是一个小的命令行解析库，先声明程序接受哪些参数，再解析：
+///
+/// ```ignore
+/// let matches = Command::new("toml-set")
+///     .about("Edit a TOML file without losing its comments")
+///     .arg(Arg::positional("file").required(true).help("The file to edit"))
+///     .arg(Arg::flag("verbose").short('v').help("Print more details"))
+///     .arg(Arg::option("output").short('o').long("output").value_name("FILE"))
+///     .parse_env();
+/// let verbose = matches.flag("verbose");
+/// let count: u32 = matches.get("count")?.unwrap_or(1);
+/// ```
+///
+/// 支持的语法：
+/// * 短选项 `-v`，可以合并 `-vq`，带值的 `-o FILE` 或者 `-oFILE`
+/// * 长选项 `--verbose`，带值的 `--output FILE` 或者 `--output=FILE`
+/// * `--` 之后的所有参数都是位置参数，例如 `rm -- -file`
+/// * 单独的 `-` 和负数 `-5` 是位置参数
+/// * 子命令，例如 `cargo build --release`，子命令之后的参数由子命令解析
+/// * 自动生成的 `-h`/`--help`
+#[derive(Debug, Clone)]
+pub struct Command {
+    name: String,
+    about: Option<String>,
+    args: Vec<Arg>,
+    subcommands: Vec<Command>,
+    subcommand_required: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Kind {
+    Flag,
+    Option,
+    Positional,
+}
+
+/// 一个声明的参数，name 是在 Matches 中查找它的键
+#[derive(Debug, Clone)]
+pub struct Arg {
+    name: String,
+    kind: Kind,
+    short: Option<char>,
+    long: Option<String>,
+    help: Option<String>,
+    value_name: Option<String>,
+    required: bool,
+    multiple: bool,
+    default: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliError {
+    /// 用户要求显示帮助，内容是帮助文本，这不是真正的错误，但是同样意味着程序不应该继续运行
+    Help(String),
+    UnknownArgument(String),
+    /// 选项后面缺少值，例如最后一个参数是 `--output`
+    MissingValue(String),
+    /// 不接受值的标志被给了一个值，例如 `--verbose=yes`
+    UnexpectedValue(String),
+    MissingRequired(String),
+    UnexpectedPositional(String),
+    UnknownSubcommand(String),
+    MissingSubcommand,
+    InvalidValue {
+        arg: String,
+        value: String,
+        message: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, CliError>;
+
+impl error::Error for CliError {}
+
+impl Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Help(ref help) => write!(f, "{}", help),
+            Self::UnknownArgument(ref arg) => write!(f, "unexpected argument '{}'", arg),
+            Self::MissingValue(ref arg) => write!(f, "a value is required for '{}'", arg),
+            Self::UnexpectedValue(ref arg) => write!(f, "'{}' does not take a value", arg),
+            Self::MissingRequired(ref arg) => {
+                write!(f, "the required argument '{}' was not provided", arg)
+            }
+            Self::UnexpectedPositional(ref arg) => {
+                write!(f, "unexpected positional argument '{}'", arg)
+            }
+            Self::UnknownSubcommand(ref name) => write!(f, "unrecognized subcommand '{}'", name),
+            Self::MissingSubcommand => write!(f, "a subcommand is required"),
+            Self::InvalidValue {
+                ref arg,
+                ref value,
+                ref message,
+            } => write!(f, "invalid value '{}' for '{}': {}", value, arg, message),
+        }
+    }
+}
+
+impl Arg {
+    fn new(name: &str, kind: Kind) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            short: None,
+            long: None,
+            help: None,
+            value_name: None,
+            required: false,
+            multiple: false,
+            default: None,
+        }
+    }
+
+    /// 不带值的开关，长选项默认是 `--name`，出现多次时可以用 Matches::count 得到次数，例如 `-vvv`
+    pub fn flag(name: &str) -> Self {
+        let long = name.replace('_', "-");
+        Self::new(name, Kind::Flag).long(&long)
+    }
+
+    /// 带一个值的选项，长选项默认是 `--name`
+    pub fn option(name: &str) -> Self {
+        let long = name.replace('_', "-");
+        Self::new(name, Kind::Option).long(&long)
+    }
+
+    /// 按照声明的顺序匹配的位置参数
+    pub fn positional(name: &str) -> Self {
+        Self::new(name, Kind::Positional)
+    }
+
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    pub fn long(mut self, long: &str) -> Self {
+        self.long = Some(long.to_string());
+        self
+    }
+
+    pub fn help(mut self, help: &str) -> Self {
+        self.help = Some(help.to_string());
+        self
+    }
+
+    /// 帮助中显示的值的名字，默认是大写的 name
+    pub fn value_name(mut self, value_name: &str) -> Self {
+        self.value_name = Some(value_name.to_string());
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    /// 选项可以出现多次，位置参数会收集剩下所有的位置参数，所以只有最后一个位置参数可以是 multiple
+    pub fn multiple(mut self, multiple: bool) -> Self {
+        self.multiple = multiple;
+        self
+    }
+
+    /// 没有给出时使用的值，和用户输入的值一样经过 Matches::get 解析
+    pub fn default_value(mut self, default: &str) -> Self {
+        self.default = Some(default.to_string());
+        self
+    }
+
+    fn takes_value(&self) -> bool {
+        self.kind != Kind::Flag
+    }
+
+    fn value_label(&self) -> String {
+        self.value_name
+            .clone()
+            .unwrap_or_else(|| self.name.to_uppercase().replace('-', "_"))
+    }
+
+    /// 错误信息中显示的名字
+    fn display_name(&self) -> String {
+        match (self.kind == Kind::Positional, &self.long, self.short) {
+            (true, _, _) => format!("<{}>", self.value_label()),
+            (false, Some(long), _) => format!("--{}", long),
+            (false, None, Some(short)) => format!("-{}", short),
+            (false, None, None) => self.name.clone(),
+        }
+    }
+}
+
+/// 解析的结果
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Matches {
+    /// 选项和位置参数的值，按出现的顺序
+    values: BTreeMap<String, Vec<String>>,
+    /// 标志出现的次数
+    flags: BTreeMap<String, usize>,
+    /// 用户给出的参数的显示名字，用于错误信息
+    labels: BTreeMap<String, String>,
+    subcommand: Option<(String, Box<Matches>)>,
+}
+
+impl Matches {
+    pub fn flag(&self, name: &str) -> bool {
+        self.count(name) > 0
+    }
+
+    pub fn count(&self, name: &str) -> usize {
+        self.flags.get(name).copied().unwrap_or(0)
+    }
+
+    /// 最后一次给出的值，或者默认值
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name)?.last().map(String::as_str)
+    }
+
+    pub fn values(&self, name: &str) -> &[String] {
+        self.values.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// 用 FromStr 解析最后一次给出的值，错误信息中包含参数的名字
+    pub fn get<T>(&self, name: &str) -> Result<Option<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.value(name).map(|v| self.parse(name, v)).transpose()
+    }
+
+    pub fn get_all<T>(&self, name: &str) -> Result<Vec<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.values(name)
+            .iter()
+            .map(|v| self.parse(name, v))
+            .collect()
+    }
+
+    pub fn subcommand(&self) -> Option<(&str, &Matches)> {
+        self.subcommand
+            .as_ref()
+            .map(|(name, matches)| (name.as_str(), &**matches))
+    }
+
+    fn parse<T>(&self, name: &str, value: &str) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        value.parse().map_err(|e: T::Err| CliError::InvalidValue {
+            arg: self
+                .labels
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| name.to_string()),
+            value: value.to_string(),
+            message: e.to_string(),
+        })
+    }
+}
+
+impl Command {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            about: None,
+            args: Vec::new(),
+            subcommands: Vec::new(),
+            subcommand_required: false,
+        }
+    }
+
+    pub fn about(mut self, about: &str) -> Self {
+        self.about = Some(about.to_string());
+        self
+    }
+
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn subcommand(mut self, subcommand: Command) -> Self {
+        self.subcommands.push(subcommand);
+        self
+    }
+
+    pub fn subcommand_required(mut self, required: bool) -> Self {
+        self.subcommand_required = required;
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// 解析 env::args()；出错时打印错误和用法并以状态码2退出，要求显示帮助时打印帮助并以状态码0退出
+    pub fn parse_env(&self) -> Matches {
+        self.parse(env::args().skip(1)).unwrap_or_else(|e| exit(&e))
+    }
+
+    /// 解析程序名之后的参数
+    pub fn parse<I, S>(&self, args: I) -> Result<Matches>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let args: Vec<String> = args.into_iter().map(Into::into).collect();
+        self.parse_from(&self.name, &args)
+    }
+
+    /// path 是帮助中显示的命令，子命令是 `app sub`
+    fn parse_from(&self, path: &str, args: &[String]) -> Result<Matches> {
+        let mut matches = Matches::default();
+        let positionals: Vec<&Arg> = self
+            .args
+            .iter()
+            .filter(|a| a.kind == Kind::Positional)
+            .collect();
+        let mut position = 0;
+        let mut only_positionals = false;
+        let mut i = 0;
+        while i < args.len() {
+            let arg = &args[i];
+            i += 1;
+            if only_positionals || !is_option(arg) {
+                // 声明了子命令时，第一个位置参数如果不属于已声明的位置参数，就是子命令的名字
+                if position >= positionals.len() && !self.subcommands.is_empty() {
+                    let subcommand = self
+                        .subcommands
+                        .iter()
+                        .find(|s| s.name == *arg)
+                        .ok_or_else(|| CliError::UnknownSubcommand(arg.clone()))?;
+                    let path = format!("{} {}", path, subcommand.name);
+                    let sub = subcommand.parse_from(&path, &args[i..])?;
+                    matches.subcommand = Some((subcommand.name.clone(), Box::new(sub)));
+                    break;
+                }
+                let positional = positionals
+                    .get(position)
+                    .ok_or_else(|| CliError::UnexpectedPositional(arg.clone()))?;
+                self.record(&mut matches, positional, arg.clone());
+                if !positional.multiple {
+                    position += 1;
+                }
+                continue;
+            }
+            if arg == "--" {
+                only_positionals = true;
+            } else if let Some(long) = arg.strip_prefix("--") {
+                let (name, inline) = match long.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (long, None),
+                };
+                if name == "help" && !self.args.iter().any(|a| a.long.as_deref() == Some("help")) {
+                    return Err(CliError::Help(self.help(path)));
+                }
+                let declared = self
+                    .args
+                    .iter()
+                    .find(|a| a.kind != Kind::Positional && a.long.as_deref() == Some(name))
+                    .ok_or_else(|| CliError::UnknownArgument(format!("--{}", name)))?;
+                if declared.takes_value() {
+                    let value = match inline {
+                        Some(value) => value,
+                        None => next_value(args, &mut i, &declared.display_name())?,
+                    };
+                    self.record(&mut matches, declared, value);
+                } else if inline.is_some() {
+                    return Err(CliError::UnexpectedValue(declared.display_name()));
+                } else {
+                    *matches.flags.entry(declared.name.clone()).or_default() += 1;
+                }
+            } else {
+                // -abc：依次处理每个字符，遇到带值的选项时，剩下的部分或者下一个参数就是它的值
+                let shorts = &arg[1..];
+                for (offset, short) in shorts.char_indices() {
+                    if short == 'h' && !self.args.iter().any(|a| a.short == Some('h')) {
+                        return Err(CliError::Help(self.help(path)));
+                    }
+                    let declared = self
+                        .args
+                        .iter()
+                        .find(|a| a.kind != Kind::Positional && a.short == Some(short))
+                        .ok_or_else(|| CliError::UnknownArgument(format!("-{}", short)))?;
+                    if declared.takes_value() {
+                        let rest = &shorts[offset + short.len_utf8()..];
+                        let value = if rest.is_empty() {
+                            next_value(args, &mut i, &format!("-{}", short))?
+                        } else {
+                            rest.to_string()
+                        };
+                        self.record(&mut matches, declared, value);
+                        break;
+                    }
+                    *matches.flags.entry(declared.name.clone()).or_default() += 1;
+                }
+            }
+        }
+
+        for arg in &self.args {
+            if matches.values.contains_key(&arg.name) || arg.kind == Kind::Flag {
+                continue;
+            }
+            match arg.default {
+                Some(ref default) => {
+                    matches
+                        .values
+                        .insert(arg.name.clone(), vec![default.clone()]);
+                    matches.labels.insert(arg.name.clone(), arg.display_name());
+                }
+                None if arg.required => {
+                    return Err(CliError::MissingRequired(arg.display_name()));
+                }
+                None => {}
+            }
+        }
+        if self.subcommand_required && matches.subcommand.is_none() {
+            return Err(CliError::MissingSubcommand);
+        }
+        Ok(matches)
+    }
+
+    fn record(&self, matches: &mut Matches, arg: &Arg, value: String) {
+        let values = matches.values.entry(arg.name.clone()).or_default();
+        if !arg.multiple {
+            values.clear();
+        }
+        values.push(value);
+        matches.labels.insert(arg.name.clone(), arg.display_name());
+    }
+
+    /// 一行的用法，例如 `Usage: app [OPTIONS] <FILE> [COMMAND]`
+    pub fn usage(&self) -> String {
+        self.usage_for(&self.name)
+    }
+
+    fn usage_for(&self, path: &str) -> String {
+        let mut usage = format!("Usage: {}", path);
+        if self
+            .args
+            .iter()
+            .any(|a| a.kind != Kind::Positional && !a.required)
+        {
+            usage.push_str(" [OPTIONS]");
+        }
+        for arg in &self.args {
+            let dots = if arg.multiple { "..." } else { "" };
+            match arg.kind {
+                Kind::Positional if arg.required => {
+                    write!(usage, " <{}>{}", arg.value_label(), dots).unwrap()
+                }
+                Kind::Positional => write!(usage, " [{}]{}", arg.value_label(), dots).unwrap(),
+                _ if arg.required => {
+                    write!(usage, " {} <{}>", arg.display_name(), arg.value_label()).unwrap()
+                }
+                _ => {}
+            }
+        }
+        match (self.subcommands.is_empty(), self.subcommand_required) {
+            (true, _) => {}
+            (false, true) => usage.push_str(" <COMMAND>"),
+            (false, false) => usage.push_str(" [COMMAND]"),
+        }
+        usage
+    }
+
+    /// --help 显示的完整帮助
+    pub fn help(&self, path: &str) -> String {
+        let mut help = String::new();
+        if let Some(ref about) = self.about {
+            help.push_str(about);
+            help.push_str("\n\n");
+        }
+        help.push_str(&self.usage_for(path));
+        help.push('\n');
+
+        let section = |help: &mut String, title: &str, rows: Vec<(String, String)>| {
+            if rows.is_empty() {
+                return;
+            }
+            let width = rows.iter().map(|(left, _)| left.len()).max().unwrap_or(0);
+            write!(help, "\n{}:\n", title).unwrap();
+            for (left, right) in rows {
+                let line = format!("  {:width$}  {}", left, right, width = width);
+                help.push_str(line.trim_end());
+                help.push('\n');
+            }
+        };
+
+        let positionals = self
+            .args
+            .iter()
+            .filter(|a| a.kind == Kind::Positional)
+            .map(|a| {
+                let dots = if a.multiple { "..." } else { "" };
+                (format!("<{}>{}", a.value_label(), dots), describe(a))
+            })
+            .collect();
+        section(&mut help, "Arguments", positionals);
+
+        let mut options: Vec<(String, String)> = self
+            .args
+            .iter()
+            .filter(|a| a.kind != Kind::Positional)
+            .map(|a| {
+                let mut left = match a.short {
+                    Some(short) => format!("-{}", short),
+                    None => "  ".to_string(),
+                };
+                if let Some(ref long) = a.long {
+                    let separator = if a.short.is_some() { ", " } else { "  " };
+                    write!(left, "{}--{}", separator, long).unwrap();
+                }
+                if a.takes_value() {
+                    write!(left, " <{}>", a.value_label()).unwrap();
+                }
+                (left, describe(a))
+            })
+            .collect();
+        if !self.args.iter().any(|a| a.long.as_deref() == Some("help")) {
+            options.push(("-h, --help".to_string(), "Print help".to_string()));
+        }
+        section(&mut help, "Options", options);
+
+        let commands = self
+            .subcommands
+            .iter()
+            .map(|s| (s.name.clone(), s.about.clone().unwrap_or_default()))
+            .collect();
+        section(&mut help, "Commands", commands);
+        help
+    }
+}
+
+/// 帮助中参数的说明，附带默认值
+fn describe(arg: &Arg) -> String {
+    let mut description = arg.help.clone().unwrap_or_default();
+    if let Some(ref default) = arg.default {
+        if !description.is_empty() {
+            description.push(' ');
+        }
+        write!(description, "[default: {}]", default).unwrap();
+    }
+    description
+}
+
+/// 以 - 开头的参数是选项，但是单独的 - 通常表示标准输入，-5 是一个负数
+fn is_option(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with('-') && !arg[1..].starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// 选项的值总是下一个参数，即使它以 - 开头，例如 `--offset -5`
+fn next_value(args: &[String], i: &mut usize, name: &str) -> Result<String> {
+    let value = args
+        .get(*i)
+        .cloned()
+        .ok_or_else(|| CliError::MissingValue(name.to_string()))?;
+    *i += 1;
+    Ok(value)
+}
+
+/// 把解析结果转换成一个结构体，通常由 chapter-five-derive 中的 `#[derive(Parser)]` 实现：
+/// 字段的类型决定参数的种类，bool 是标志，`Option<T>` 是可选的，`Vec<T>` 可以重复，文档注释是帮助文本
+pub trait Parser: Sized {
+    fn command() -> Command;
+
+    fn from_matches(matches: &Matches) -> Result<Self>;
+
+    /// 解析 env::args()，出错时和 Command::parse_env 一样退出
+    fn parse() -> Self {
+        let matches = Self::command().parse_env();
+        Self::from_matches(&matches).unwrap_or_else(|e| exit(&e))
+    }
+
+    /// 解析程序名之后的参数，不会退出
+    fn try_parse_from<I, S>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self::from_matches(&Self::command().parse(args)?)
+    }
+}
+
+/// 按照惯例：帮助打印到标准输出并以0退出，错误打印到标准错误并以2退出
+pub fn exit(error: &CliError) -> ! {
+    match *error {
+        CliError::Help(ref help) => {
+            print!("{}", help);
+            process::exit(0)
+        }
+        ref error => {
+            eprintln!("error: {}\n\nFor more information, try '--help'.", error);
+            process::exit(2)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> Command {
+        Command::new("app")
+            .about("Does things")
+            .arg(Arg::flag("verbose").short('v').help("Print more"))
+            .arg(Arg::option("output").short('o').value_name("FILE"))
+            .arg(Arg::option("count").short('n').default_value("1"))
+            .arg(Arg::option("tag").short('t').multiple(true))
+            .arg(Arg::positional("input").required(true))
+            .arg(Arg::positional("rest").multiple(true))
+    }
+
+    #[test]
+    fn parses_options_flags_and_positionals() {
+        let matches = command()
+            .parse([
+                "-vv",
+                "-oout.txt",
+                "in.txt",
+                "--tag=a",
+                "-t",
+                "b",
+                "--",
+                "-x",
+                "-",
+            ])
+            .unwrap();
+        assert_eq!(2, matches.count("verbose"));
+        assert_eq!(Some("out.txt"), matches.value("output"));
+        assert_eq!(Ok(Some(1u32)), matches.get("count"));
+        assert_eq!(&["a", "b"], matches.values("tag"));
+        assert_eq!(Some("in.txt"), matches.value("input"));
+        assert_eq!(&["-x", "-"], matches.values("rest"));
+
+        let matches = command().parse(["--count", "-5", "-5"]).unwrap();
+        assert_eq!(Ok(Some(-5i32)), matches.get("count"));
+        assert_eq!(Some("-5"), matches.value("input"));
+    }
+
+    #[test]
+    fn reports_errors() {
+        let parse = |args: &[&str]| command().parse(args.iter().copied()).unwrap_err();
+        assert_eq!(CliError::MissingRequired("<INPUT>".to_string()), parse(&[]));
+        assert_eq!(CliError::UnknownArgument("-x".to_string()), parse(&["-x"]));
+        assert_eq!(
+            CliError::MissingValue("--output".to_string()),
+            parse(&["in", "--output"])
+        );
+        assert_eq!(
+            CliError::UnexpectedValue("--verbose".to_string()),
+            parse(&["--verbose=yes"])
+        );
+        let matches = command().parse(["in", "-n", "many"]).unwrap();
+        assert_eq!(
+            "invalid value 'many' for '--count': invalid digit found in string",
+            matches.get::<u32>("count").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn dispatches_subcommands() {
+        let command = Command::new("cargo")
+            .arg(Arg::flag("quiet").short('q'))
+            .subcommand(
+                Command::new("build")
+                    .about("Compile the package")
+                    .arg(Arg::flag("release")),
+            )
+            .subcommand_required(true);
+        let matches = command.parse(["-q", "build", "--release"]).unwrap();
+        assert!(matches.flag("quiet"));
+        let (name, build) = matches.subcommand().unwrap();
+        assert_eq!("build", name);
+        assert!(build.flag("release"));
+
+        assert_eq!(
+            CliError::UnknownSubcommand("test".to_string()),
+            command.parse(["test"]).unwrap_err()
+        );
+        assert_eq!(
+            CliError::MissingSubcommand,
+            command.parse(["-q"]).unwrap_err()
+        );
+        let CliError::Help(help) = command.parse(["build", "--help"]).unwrap_err() else {
+            panic!("Expected the help of build");
+        };
+        assert!(help.starts_with("Compile the package\n\nUsage: cargo build [OPTIONS]\n"));
+    }
+
+    #[test]
+    fn generates_help() {
+        let CliError::Help(help) = command().parse(["in", "-h"]).unwrap_err() else {
+            panic!("Expected help");
+        };
+        assert_eq!(
+            "Does things
+
+Usage: app [OPTIONS] <INPUT> [REST]...
+
+Arguments:
+  <INPUT>
+  <REST>...
+
+Options:
+  -v, --verbose        Print more
+  -o, --output <FILE>
+  -n, --count <COUNT>  [default: 1]
+  -t, --tag <TAG>
+  -h, --help           Print help
+",
+            help
+        );
+    }
+}
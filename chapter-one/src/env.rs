@@ -0,0 +1,461 @@
+use std::{
+    collections::BTreeMap,
+    env, error,
+    fmt::{self, Display},
+    fs, io,
+    iter::Peekable,
+    path::Path,
+    result,
+    str::Chars,
+};
+
+/// env_vars.rs 中提到的 .env 文件：每行一个 KEY=VALUE，在程序启动时加载到环境变量中
+///
+/// ```text
+/// # 注释
+/// export PORT=8080                 # 可以有 export 前缀，这样文件也能被shell source
+/// NAME='Jan $HOME'                 # 单引号：原样保留，不处理转义和插值
+/// GREETING="Hello\n${NAME}"        # 双引号：处理 \n \t \r \" \\ \$ 转义和插值
+/// URL=http://${HOST:-localhost}:${PORT}
+/// KEY="-----BEGIN-----
+/// ...
+/// -----END-----"                   # 引号中的值可以跨越多行
+/// ```
+///
+/// 插值 `${VAR}` 先在文件前面定义的变量中查找，再到进程的环境变量中查找，都找不到时是空字符串；
+/// `${VAR:-default}` 在 VAR 不存在或者为空时使用 default
+///
+/// 已经存在的环境变量默认优先于文件中的值，这样部署环境可以覆盖 .env 中给开发者准备的默认值，
+/// 插值时看到的也是实际生效的值
+#[derive(Debug, Clone, Default)]
+pub struct Dotenv {
+    override_existing: bool,
+}
+
+#[derive(Debug)]
+pub enum DotenvError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+pub type Result<T> = result::Result<T, DotenvError>;
+
+impl error::Error for DotenvError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Self::Io(ref err) => Some(err),
+            Self::Parse { .. } => None,
+        }
+    }
+}
+
+impl Display for DotenvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Io(ref err) => write!(f, "IO error: {}", err),
+            Self::Parse { line, ref message } => write!(f, "Line {}: {}", line, message),
+        }
+    }
+}
+
+impl From<io::Error> for DotenvError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl Dotenv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 为 true 时文件中的值覆盖已经存在的环境变量
+    pub fn override_existing(mut self, override_existing: bool) -> Self {
+        self.override_existing = override_existing;
+        self
+    }
+
+    /// 解析并插值，不修改环境变量；同一个键出现多次时最后一次生效
+    pub fn parse(&self, input: &str) -> Result<BTreeMap<String, String>> {
+        let mut parser = Parser {
+            chars: input.chars().peekable(),
+            line: 1,
+        };
+        let mut values = BTreeMap::new();
+        loop {
+            let entry = parser.entry(&|name| self.lookup(&values, name))?;
+            let Some((key, value)) = entry else {
+                break;
+            };
+            values.insert(key, value);
+        }
+        Ok(values)
+    }
+
+    pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<BTreeMap<String, String>> {
+        self.parse(&fs::read_to_string(path)?)
+    }
+
+    /// 读取文件并设置环境变量，返回实际设置了的变量名
+    ///
+    /// 和 env::set_var 一样，只应该在程序启动、还没有创建其它线程的时候调用
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Vec<String>> {
+        let values = self.read(path)?;
+        let mut set = Vec::new();
+        for (key, value) in values {
+            if self.override_existing || env::var_os(&key).is_none() {
+                // 调用者保证此时没有其它线程在读写环境变量
+                unsafe {
+                    env::set_var(&key, value);
+                }
+                set.push(key);
+            }
+        }
+        Ok(set)
+    }
+
+    /// 插值时一个变量实际生效的值
+    fn lookup(&self, file: &BTreeMap<String, String>, name: &str) -> Option<String> {
+        let existing = env::var(name).ok();
+        if self.override_existing {
+            file.get(name).cloned().or(existing)
+        } else {
+            existing.or_else(|| file.get(name).cloned())
+        }
+    }
+}
+
+type Lookup<'a> = dyn Fn(&str) -> Option<String> + 'a;
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T> {
+        Err(DotenvError::Parse {
+            line: self.line,
+            message: message.into(),
+        })
+    }
+
+    fn skip_blanks(&mut self) {
+        while self.chars.next_if(|&c| c == ' ' || c == '\t').is_some() {}
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.next() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    /// 引号之后只允许空白和注释
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_blanks();
+        match self.chars.peek() {
+            None | Some('\n') | Some('#') => {
+                self.skip_line();
+                Ok(())
+            }
+            Some('\r') => {
+                self.next();
+                self.end_of_line()
+            }
+            Some(&c) => self.error(format!("Unexpected '{}' after the closing quote", c)),
+        }
+    }
+
+    /// 下一个 KEY=VALUE，跳过空行和注释，文件结束时返回 None
+    fn entry(&mut self, lookup: &Lookup) -> Result<Option<(String, String)>> {
+        loop {
+            self.skip_blanks();
+            match self.chars.peek() {
+                None => return Ok(None),
+                Some('\n') | Some('\r') => {
+                    self.next();
+                }
+                Some('#') => self.skip_line(),
+                Some(_) => break,
+            }
+        }
+
+        let mut key = self.key();
+        if key == "export" && self.chars.peek().is_some_and(|&c| c == ' ' || c == '\t') {
+            self.skip_blanks();
+            key = self.key();
+        }
+        if key.is_empty() {
+            let found = self.chars.peek().copied().unwrap_or(' ');
+            return self.error(format!("Expected a variable name, found '{}'", found));
+        }
+        self.skip_blanks();
+        if self.chars.next_if_eq(&'=').is_none() {
+            return self.error(format!("Expected '=' after {}", key));
+        }
+        self.skip_blanks();
+
+        let value = match self.chars.peek() {
+            Some('\'') => {
+                self.next();
+                let value = self.single_quoted()?;
+                self.end_of_line()?;
+                value
+            }
+            Some('"') => {
+                self.next();
+                let value = self.double_quoted(lookup)?;
+                self.end_of_line()?;
+                value
+            }
+            _ => self.unquoted(lookup)?,
+        };
+        Ok(Some((key, value)))
+    }
+
+    /// 变量名：字母、数字、下划线和 .，不能以数字开头
+    fn key(&mut self) -> String {
+        let mut key = String::new();
+        if self
+            .chars
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphabetic() || *c == '_')
+        {
+            while let Some(c) = self
+                .chars
+                .next_if(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '.')
+            {
+                key.push(c);
+            }
+        }
+        key
+    }
+
+    fn single_quoted(&mut self) -> Result<String> {
+        let start = self.line;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('\'') => return Ok(value),
+                Some(c) => value.push(c),
+                None => {
+                    self.line = start;
+                    return self.error("Unterminated single quote");
+                }
+            }
+        }
+    }
+
+    fn double_quoted(&mut self, lookup: &Lookup) -> Result<String> {
+        let start = self.line;
+        let mut value = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(value),
+                Some('\\') => match self.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some('r') => value.push('\r'),
+                    Some(c @ ('"' | '\\' | '$')) => value.push(c),
+                    // 行尾的反斜杠连接下一行
+                    Some('\n') => {}
+                    Some(c) => return self.error(format!("Unknown escape sequence '\\{}'", c)),
+                    None => break,
+                },
+                Some('$') => self.interpolate(&mut value, lookup)?,
+                Some(c) => value.push(c),
+                None => break,
+            }
+        }
+        self.line = start;
+        self.error("Unterminated double quote")
+    }
+
+    /// 到行尾或者注释为止，# 前面必须有空白，所以 URL#anchor 中的 # 是值的一部分
+    fn unquoted(&mut self, lookup: &Lookup) -> Result<String> {
+        let mut value = String::new();
+        while let Some(&c) = self.chars.peek() {
+            match c {
+                '\n' => break,
+                '#' if value.is_empty() || value.ends_with([' ', '\t']) => {
+                    self.skip_line();
+                    return Ok(value.trim_end().to_string());
+                }
+                '$' => {
+                    self.next();
+                    self.interpolate(&mut value, lookup)?;
+                }
+                _ => {
+                    self.next();
+                    value.push(c);
+                }
+            }
+        }
+        self.next();
+        Ok(value.trim_end().to_string())
+    }
+
+    /// 已经读取了 $，后面不是 { 时 $ 按字面意思保留
+    fn interpolate(&mut self, value: &mut String, lookup: &Lookup) -> Result<()> {
+        if self.chars.next_if_eq(&'{').is_none() {
+            value.push('$');
+            return Ok(());
+        }
+        let mut expression = String::new();
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Some('}') if depth == 0 => break,
+                Some(c) => {
+                    match c {
+                        '{' => depth += 1,
+                        '}' => depth -= 1,
+                        _ => {}
+                    }
+                    expression.push(c);
+                }
+                None => return self.error("Unterminated '${'"),
+            }
+        }
+        match expand(&expression, lookup) {
+            Ok(expanded) => {
+                value.push_str(&expanded);
+                Ok(())
+            }
+            Err(message) => self.error(message),
+        }
+    }
+}
+
+/// `${...}` 中的内容：NAME 或者 NAME:-default，default 中可以再次插值
+fn expand(expression: &str, lookup: &Lookup) -> result::Result<String, String> {
+    let (name, default) = match expression.split_once(":-") {
+        Some((name, default)) => (name, Some(default)),
+        None => (expression, None),
+    };
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("Invalid variable name in '${{{}}}'", expression));
+    }
+    match (lookup(name), default) {
+        (Some(value), Some(_)) if !value.is_empty() => Ok(value),
+        (Some(value), None) => Ok(value),
+        (_, Some(default)) => {
+            let mut parser = Parser {
+                chars: default.chars().peekable(),
+                line: 1,
+            };
+            let mut value = String::new();
+            while let Some(c) = parser.next() {
+                if c == '$' {
+                    parser
+                        .interpolate(&mut value, lookup)
+                        .map_err(|e| e.to_string())?;
+                } else {
+                    value.push(c);
+                }
+            }
+            Ok(value)
+        }
+        (None, None) => Ok(String::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env_guard::EnvGuard;
+
+    fn parse(input: &str) -> BTreeMap<String, String> {
+        Dotenv::new().parse(input).expect("Failed to parse .env")
+    }
+
+    #[test]
+    fn parses_quotes_comments_and_multiline_values() {
+        let values = parse(
+            r#"
+# database
+export HOST = db.local   # trailing comment
+URL=http://example.com/#anchor
+EMPTY=
+SINGLE='no $HOME or \n here' # comment
+DOUBLE="tab\there \"quoted\" \$HOME"
+KEY="-----BEGIN-----
+abc
+-----END-----"
+"#,
+        );
+        assert_eq!("db.local", values["HOST"]);
+        assert_eq!("http://example.com/#anchor", values["URL"]);
+        assert_eq!("", values["EMPTY"]);
+        assert_eq!("no $HOME or \\n here", values["SINGLE"]);
+        assert_eq!("tab\there \"quoted\" $HOME", values["DOUBLE"]);
+        assert_eq!("-----BEGIN-----\nabc\n-----END-----", values["KEY"]);
+    }
+
+    #[test]
+    fn interpolates_earlier_values_and_defaults() {
+        let values = parse(
+            r#"
+DOTENV_TEST_PORT=8080
+URL="http://${DOTENV_TEST_HOST:-localhost}:${DOTENV_TEST_PORT}"
+FALLBACK=${DOTENV_TEST_MISSING:-${DOTENV_TEST_PORT}0}
+MISSING=[${DOTENV_TEST_MISSING}]
+LITERAL='${DOTENV_TEST_PORT}'
+"#,
+        );
+        assert_eq!("http://localhost:8080", values["URL"]);
+        assert_eq!("80800", values["FALLBACK"]);
+        assert_eq!("[]", values["MISSING"]);
+        assert_eq!("${DOTENV_TEST_PORT}", values["LITERAL"]);
+    }
+
+    #[test]
+    fn existing_variables_win_unless_overridden() {
+        let mut guard = EnvGuard::new();
+        guard.set("DOTENV_TEST_EXISTING", "env");
+        let input = "DOTENV_TEST_EXISTING=file\nNAME=${DOTENV_TEST_EXISTING}\n";
+        let values = Dotenv::new().parse(input).unwrap();
+        assert_eq!("env", values["NAME"]);
+        let values = Dotenv::new().override_existing(true).parse(input).unwrap();
+        assert_eq!("file", values["NAME"]);
+    }
+
+    #[test]
+    fn load_sets_only_missing_variables() {
+        let path = env::temp_dir().join(format!("dotenv-{}.env", std::process::id()));
+        fs::write(&path, "DOTENV_TEST_A=file\nDOTENV_TEST_B=file\n").unwrap();
+        // load 设置的变量也要先经过 guard，这样 drop 时才会被删除
+        let mut guard = EnvGuard::new();
+        guard.set("DOTENV_TEST_A", "env").remove("DOTENV_TEST_B");
+        let set = Dotenv::new().load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(vec!["DOTENV_TEST_B"], set.unwrap());
+        assert_eq!(Ok("env".to_string()), env::var("DOTENV_TEST_A"));
+        assert_eq!(Ok("file".to_string()), env::var("DOTENV_TEST_B"));
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        let error = |input: &str| match Dotenv::new().parse(input) {
+            Err(DotenvError::Parse { line, message }) => (line, message),
+            other => panic!("Expected a parse error, got {:?}", other),
+        };
+        assert_eq!(2, error("A=1\nB=\"open\n\nC=3").0);
+        assert_eq!((2, "Expected '=' after B".to_string()), error("A=1\nB 2"));
+        assert_eq!(1, error("1A=1").0);
+        assert_eq!(1, error("A=\"x\" y").0);
+        assert_eq!(1, error("A=${B C}").0);
+    }
+}
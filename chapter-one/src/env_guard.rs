@@ -0,0 +1,156 @@
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    process::Command,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// 环境变量是整个进程共享的全局状态：
+/// * edition 2024 中 env::set_var 和 env::remove_var 是 unsafe 的，因为其它线程可能同时在读写环境变量
+/// * cargo test 默认在多个线程中并行运行测试，一个测试设置的变量会被另一个测试看到，结果取决于运行顺序
+///
+/// EnvGuard 在创建时获取一个全局锁，通过它修改的变量在 drop 时恢复成原来的值（原来不存在的会被删除），然后释放锁
+/// 所以同一时间只有一个测试在修改环境变量，测试结束时环境和开始时完全一样，即使测试 panic 了也是如此
+///
+/// 注意：
+/// * 同一个线程中不能同时持有两个 EnvGuard，第二个会永远等待第一个释放锁
+/// * 锁只能约束使用 EnvGuard 的代码，读取被修改变量的测试也应该持有一个 EnvGuard
+/// * 能不修改自己的环境就不修改：读取配置的代码可以接受一个映射（例如 FromEnv::from_map），
+///   启动子进程时可以用 command_with_env 直接指定子进程的环境
+pub struct EnvGuard {
+    /// 每个变量第一次被修改之前的值
+    saved: Vec<(OsString, Option<OsString>)>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+impl EnvGuard {
+    /// 等待其它 EnvGuard 释放锁
+    pub fn new() -> Self {
+        // 持有锁的测试 panic 时锁会中毒，但是它的 EnvGuard 已经在 unwind 时恢复了环境，可以放心继续使用
+        let lock = LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        Self {
+            saved: Vec::new(),
+            _lock: lock,
+        }
+    }
+
+    pub fn set<K, V>(&mut self, key: K, value: V) -> &mut Self
+    where
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.save(key.as_ref());
+        // 所有修改环境变量的地方都持有同一个锁
+        unsafe {
+            env::set_var(key, value);
+        }
+        self
+    }
+
+    pub fn remove<K: AsRef<OsStr>>(&mut self, key: K) -> &mut Self {
+        self.save(key.as_ref());
+        unsafe {
+            env::remove_var(key);
+        }
+        self
+    }
+
+    fn save(&mut self, key: &OsStr) {
+        if !self.saved.iter().any(|(saved, _)| saved == key) {
+            self.saved.push((key.to_owned(), env::var_os(key)));
+        }
+    }
+}
+
+impl Default for EnvGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 按修改的相反顺序恢复，锁在这之后才被释放
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (key, value) in self.saved.drain(..).rev() {
+            unsafe {
+                match value {
+                    Some(value) => env::set_var(&key, value),
+                    None => env::remove_var(&key),
+                }
+            }
+        }
+    }
+}
+
+/// 创建一个只看得到 vars 的子进程，不需要修改我们自己的环境
+///
+/// 子进程不继承当前进程的环境变量，只保留 PATH（除非 vars 中指定了），这样 program 仍然可以只写名字
+pub fn command_with_env<P, I, K, V>(program: P, vars: I) -> Command
+where
+    P: AsRef<OsStr>,
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    let mut command = Command::new(program);
+    command.env_clear();
+    if let Some(path) = env::var_os("PATH") {
+        command.env("PATH", path);
+    }
+    command.envs(vars);
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn restores_previous_values() {
+        {
+            let mut guard = EnvGuard::new();
+            guard
+                .set("ENV_GUARD_TEST_NEW", "1")
+                .set("ENV_GUARD_TEST_NEW", "2")
+                .remove("CARGO_PKG_NAME");
+            assert_eq!(Ok("2".to_string()), env::var("ENV_GUARD_TEST_NEW"));
+            assert!(env::var_os("CARGO_PKG_NAME").is_none());
+        }
+        let _guard = EnvGuard::new();
+        assert!(env::var_os("ENV_GUARD_TEST_NEW").is_none());
+        assert_eq!(Ok("chapter-one".to_string()), env::var("CARGO_PKG_NAME"));
+    }
+
+    #[test]
+    fn guards_are_serialized() {
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                thread::spawn(move || {
+                    let mut guard = EnvGuard::new();
+                    guard.set("ENV_GUARD_TEST_SHARED", i.to_string());
+                    thread::yield_now();
+                    assert_eq!(Ok(i.to_string()), env::var("ENV_GUARD_TEST_SHARED"));
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let _guard = EnvGuard::new();
+        assert!(env::var_os("ENV_GUARD_TEST_SHARED").is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn child_sees_only_the_given_environment() {
+        let output = command_with_env("sh", [("GREETING", "hello")])
+            .args(["-c", "echo \"$GREETING ${CARGO_PKG_NAME:-unset}\""])
+            .output()
+            .expect("Failed to run sh");
+        assert_eq!("hello unset\n", String::from_utf8_lossy(&output.stdout));
+    }
+}
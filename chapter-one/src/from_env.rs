@@ -0,0 +1,234 @@
+use std::{
+    cell::RefCell,
+    collections::BTreeMap,
+    env, error,
+    ffi::OsString,
+    fmt::{self, Display},
+    str::FromStr,
+};
+
+/// env_vars.rs 中的 print_env_var 一次只读一个变量，得到的也只是字符串
+///
+/// FromEnv 把一组环境变量读成一个结构体，通常由 chapter-five-derive 中的 `#[derive(FromEnv)]` 实现：
+///
+/// ```ignore
+/// #[derive(FromEnv)]
+/// #[env(prefix = "APP_")]
+/// struct Server {
+///     port: u16,                        // APP_PORT，必须存在
+///     #[env(default = "localhost")]
+///     host: String,                     // APP_HOST，字符串形式的默认值和变量的值一样被解析
+///     #[env(default = false)]
+///     debug: bool,                      // 其它表达式直接作为默认值
+///     #[env(name = "DATABASE_URL")]
+///     database: Option<String>,         // 完整的变量名，不加前缀；Option 表示可以不存在
+///     #[env(nested)]
+///     tls: Tls,                         // APP_TLS_CERT 等，嵌套结构体的前缀是 字段名_
+/// }
+/// ```
+///
+/// 和 `?` 不同，读取时不会停在第一个错误上，所有缺失和无法解析的变量被一次报告出来
+pub trait FromEnv: Sized {
+    /// from_env 使用的前缀，例如 APP_
+    const PREFIX: &'static str = "";
+
+    /// 从 lookup 中读取名字以 prefix 开头的变量，错误追加到 errors 中，有任何错误时返回 None
+    fn from_lookup(
+        prefix: &str,
+        lookup: &dyn Fn(&str) -> Option<String>,
+        errors: &mut Vec<EnvError>,
+    ) -> Option<Self>;
+
+    /// 从进程的环境变量中读取
+    fn from_env() -> Result<Self, FromEnvError> {
+        Self::from_os_fn(|name| env::var_os(name))
+    }
+
+    /// 从一个映射中读取，例如 env::Dotenv::parse 的结果，不涉及进程的环境变量
+    fn from_map(values: &BTreeMap<String, String>) -> Result<Self, FromEnvError> {
+        Self::from_fn(|name| values.get(name).cloned())
+    }
+
+    fn from_fn<F>(lookup: F) -> Result<Self, FromEnvError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut errors = Vec::new();
+        match Self::from_lookup(Self::PREFIX, &lookup, &mut errors) {
+            Some(value) if errors.is_empty() => Ok(value),
+            _ => Err(FromEnvError(errors)),
+        }
+    }
+
+    /// env::var(name).ok() 会把不是合法 UTF-8 的值当成不存在，Option 字段变成 None，有默认值的字段使用默认值，错误被悄悄吞掉
+    /// 所以这里的 lookup 返回 OsString，不是 UTF-8 的值被报告为 Invalid，而不是 Missing
+    fn from_os_fn<F>(lookup: F) -> Result<Self, FromEnvError>
+    where
+        F: Fn(&str) -> Option<OsString>,
+    {
+        let not_unicode = RefCell::new(Vec::new());
+        let result = Self::from_fn(|name| {
+            lookup(name)?
+                .into_string()
+                .map_err(|value| {
+                    not_unicode.borrow_mut().push(EnvError::Invalid {
+                        name: name.to_string(),
+                        value: value.to_string_lossy().into_owned(),
+                        message: "the value is not valid UTF-8".to_string(),
+                    })
+                })
+                .ok()
+        });
+        let not_unicode = not_unicode.into_inner();
+        if not_unicode.is_empty() {
+            return result;
+        }
+        let mut errors = result.err().map(|err| err.0).unwrap_or_default();
+        errors.retain(|err| match *err {
+            EnvError::Missing(ref missing) => !not_unicode
+                .iter()
+                .any(|err| matches!(*err, EnvError::Invalid { ref name, .. } if name == missing)),
+            EnvError::Invalid { .. } => true,
+        });
+        errors.extend(not_unicode);
+        Err(FromEnvError(errors))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EnvError {
+    Missing(String),
+    Invalid {
+        name: String,
+        value: String,
+        message: String,
+    },
+}
+
+impl Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Missing(ref name) => write!(f, "{} is not set", name),
+            Self::Invalid {
+                ref name,
+                ref value,
+                ref message,
+            } => write!(f, "{}={:?} is invalid: {}", name, value, message),
+        }
+    }
+}
+
+/// 读取一个结构体时遇到的所有错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FromEnvError(pub Vec<EnvError>);
+
+impl error::Error for FromEnvError {}
+
+impl Display for FromEnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} environment variable(s) are invalid", self.0.len())?;
+        for err in &self.0 {
+            write!(f, "\n  {}", err)?;
+        }
+        Ok(())
+    }
+}
+
+/// 用 FromStr 解析一个变量的值，derive 生成的代码为每个字段调用它
+pub fn parse<T>(name: &str, value: &str) -> Result<T, EnvError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value.parse().map_err(|e: T::Err| EnvError::Invalid {
+        name: name.to_string(),
+        value: value.to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// bool::from_str 只接受 true 和 false，环境变量中常见的 1/0、yes/no、on/off 也应该被接受
+pub fn parse_bool(name: &str, value: &str) -> Result<bool, EnvError> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(EnvError::Invalid {
+            name: name.to_string(),
+            value: value.to_string(),
+            message: "expected one of true/false, 1/0, yes/no, on/off".to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// derive 生成的实现的简化版本：port 必须存在，name 可以不存在
+    #[derive(Debug)]
+    struct Server {
+        port: u16,
+        name: Option<String>,
+    }
+
+    impl FromEnv for Server {
+        fn from_lookup(
+            prefix: &str,
+            lookup: &dyn Fn(&str) -> Option<String>,
+            errors: &mut Vec<EnvError>,
+        ) -> Option<Self> {
+            let port_name = format!("{}PORT", prefix);
+            let port = match lookup(&port_name) {
+                Some(value) => parse(&port_name, &value).map_err(|e| errors.push(e)).ok(),
+                None => {
+                    errors.push(EnvError::Missing(port_name));
+                    None
+                }
+            };
+            let name = lookup(&format!("{}NAME", prefix));
+            Some(Self { port: port?, name })
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn values_that_are_not_unicode_are_invalid_not_missing() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let lookup = |name: &str| match name {
+            "PORT" | "NAME" => Some(OsString::from_vec(b"80\xff".to_vec())),
+            _ => None,
+        };
+        let errors = Server::from_os_fn(lookup).unwrap_err().0;
+        assert_eq!(
+            vec!["PORT", "NAME"],
+            errors
+                .iter()
+                .map(|err| match *err {
+                    EnvError::Invalid { ref name, .. } => name.as_str(),
+                    EnvError::Missing(ref name) => panic!("{} was reported as missing", name),
+                })
+                .collect::<Vec<_>>()
+        );
+
+        let server = Server::from_os_fn(|name| (name == "PORT").then(|| OsString::from("8080")))
+            .expect("Failed to read valid variables");
+        assert_eq!(8080, server.port);
+        assert_eq!(None, server.name);
+    }
+
+    #[test]
+    fn parses_values() {
+        assert_eq!(Ok(8080u16), parse("PORT", "8080"));
+        assert!(matches!(
+            parse::<u16>("PORT", "80800"),
+            Err(EnvError::Invalid { ref name, .. }) if name == "PORT"
+        ));
+        assert_eq!(Ok(true), parse_bool("DEBUG", "Yes"));
+        assert_eq!(Ok(false), parse_bool("DEBUG", "0"));
+        assert_eq!(
+            "DEBUG=\"maybe\" is invalid: expected one of true/false, 1/0, yes/no, on/off",
+            parse_bool("DEBUG", "maybe").unwrap_err().to_string()
+        );
+    }
+}
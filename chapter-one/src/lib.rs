@@ -0,0 +1,6 @@
+pub mod burger;
+pub mod cli;
+pub mod env;
+pub mod env_guard;
+pub mod from_env;
+pub mod rules;
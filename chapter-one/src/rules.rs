@@ -0,0 +1,328 @@
+use std::{
+    error,
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
+
+/// 声明式的检查规则，用于 builder 的 build：
+///
+/// ```ignore
+/// let rules = Rules::<Pizza>::new()
+///     .exclusive("pizza.toppings", &[("pineapple", |p| p.pineapple), ("anchovies", |p| p.anchovies)])
+///     .range("pizza.size", "size", 20..=40, |p| p.size.into())
+///     .implies("pizza.extra_cheese", ("extra_cheese", |p| p.extra_cheese), ("cheese", |p| p.cheese));
+/// rules.check(&pizza)?;
+/// ```
+///
+/// check 会执行所有的规则，返回的 [`Violations`] 包含每一条没有满足的规则，而不是只有第一条，
+/// 每个 [`Violation`] 带有规则的代码和相关的选项名，界面可以据此一次标出所有有问题的选项
+///
+/// 读取选项的是函数指针，不捕获变量的闭包会自动转换成函数指针，所以 Rules 不需要装箱，也可以放在 static 之外随时构建
+pub struct Rules<T> {
+    rules: Vec<Rule<T>>,
+}
+
+/// 一个选项：名字和读取它的函数
+pub type Flag<T> = (&'static str, fn(&T) -> bool);
+
+enum Rule<T> {
+    Exclusive {
+        code: &'static str,
+        options: Vec<Flag<T>>,
+    },
+    Range {
+        code: &'static str,
+        option: &'static str,
+        range: RangeInclusive<i64>,
+        value: fn(&T) -> i64,
+    },
+    Implies {
+        code: &'static str,
+        option: Flag<T>,
+        requires: Flag<T>,
+    },
+}
+
+impl<T> Rules<T> {
+    pub fn new() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// options 中最多只能有一个为 true
+    pub fn exclusive(mut self, code: &'static str, options: &[Flag<T>]) -> Self {
+        self.rules.push(Rule::Exclusive {
+            code,
+            options: options.to_vec(),
+        });
+        self
+    }
+
+    /// option 的值必须在 range 中
+    pub fn range(
+        mut self,
+        code: &'static str,
+        option: &'static str,
+        range: RangeInclusive<i64>,
+        value: fn(&T) -> i64,
+    ) -> Self {
+        self.rules.push(Rule::Range {
+            code,
+            option,
+            range,
+            value,
+        });
+        self
+    }
+
+    /// option 为 true 时 requires 也必须为 true
+    pub fn implies(mut self, code: &'static str, option: Flag<T>, requires: Flag<T>) -> Self {
+        self.rules.push(Rule::Implies {
+            code,
+            option,
+            requires,
+        });
+        self
+    }
+
+    /// 按声明的顺序执行所有规则
+    pub fn check(&self, value: &T) -> Result<(), Violations> {
+        let violations: Vec<_> = self
+            .rules
+            .iter()
+            .filter_map(|rule| rule.check(value))
+            .collect();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(Violations(violations))
+        }
+    }
+}
+
+impl<T> Default for Rules<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Rule<T> {
+    fn check(&self, value: &T) -> Option<Violation> {
+        match *self {
+            Rule::Exclusive { code, ref options } => {
+                let set: Vec<_> = options
+                    .iter()
+                    .filter(|(_, get)| get(value))
+                    .map(|&(name, _)| name)
+                    .collect();
+                (set.len() > 1).then_some(Violation::Exclusive { code, options: set })
+            }
+            Rule::Range {
+                code,
+                option,
+                ref range,
+                value: get,
+            } => {
+                let value = get(value);
+                (!range.contains(&value)).then(|| Violation::OutOfRange {
+                    code,
+                    option,
+                    value,
+                    range: range.clone(),
+                })
+            }
+            Rule::Implies {
+                code,
+                option: (option, get_option),
+                requires: (requires, get_requires),
+            } => (get_option(value) && !get_requires(value)).then_some(Violation::Implies {
+                code,
+                option,
+                requires,
+            }),
+        }
+    }
+}
+
+/// 一条没有满足的规则
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// 同时设置了多个互斥的选项，options 是其中被设置的那些
+    Exclusive {
+        code: &'static str,
+        options: Vec<&'static str>,
+    },
+    OutOfRange {
+        code: &'static str,
+        option: &'static str,
+        value: i64,
+        range: RangeInclusive<i64>,
+    },
+    /// 设置了 option 但是没有设置 requires
+    Implies {
+        code: &'static str,
+        option: &'static str,
+        requires: &'static str,
+    },
+}
+
+impl Violation {
+    /// 声明规则时给出的代码，界面可以用它查找翻译好的提示
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Violation::Exclusive { code, .. }
+            | Violation::OutOfRange { code, .. }
+            | Violation::Implies { code, .. } => code,
+        }
+    }
+
+    /// 和这条规则有关的选项，界面应该把它们都标出来
+    pub fn options(&self) -> Vec<&'static str> {
+        match *self {
+            Violation::Exclusive { ref options, .. } => options.clone(),
+            Violation::OutOfRange { option, .. } => vec![option],
+            Violation::Implies {
+                option, requires, ..
+            } => vec![option, requires],
+        }
+    }
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Violation::Exclusive { code, ref options } => {
+                write!(f, "[{}] {} cannot be combined", code, options.join(", "))
+            }
+            Violation::OutOfRange {
+                code,
+                option,
+                value,
+                ref range,
+            } => write!(
+                f,
+                "[{}] {} must be between {} and {}, got {}",
+                code,
+                option,
+                range.start(),
+                range.end(),
+                value
+            ),
+            Violation::Implies {
+                code,
+                option,
+                requires,
+            } => write!(f, "[{}] {} requires {}", code, option, requires),
+        }
+    }
+}
+
+/// [`Rules::check`] 的错误，至少包含一条 [`Violation`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violations(pub Vec<Violation>);
+
+impl Violations {
+    /// 是否违反了代码为 code 的规则
+    pub fn contains(&self, code: &str) -> bool {
+        self.0.iter().any(|violation| violation.code() == code)
+    }
+}
+
+impl error::Error for Violations {}
+
+impl Display for Violations {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} rule(s) violated", self.0.len())?;
+        for violation in &self.0 {
+            write!(f, "\n  {}", violation)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Pizza {
+        size: i32,
+        pineapple: bool,
+        anchovies: bool,
+        extra_cheese: bool,
+        cheese: bool,
+    }
+
+    fn rules() -> Rules<Pizza> {
+        Rules::<Pizza>::new()
+            .exclusive(
+                "pizza.toppings",
+                &[
+                    ("pineapple", |p| p.pineapple),
+                    ("anchovies", |p| p.anchovies),
+                ],
+            )
+            .range("pizza.size", "size", 20..=40, |p| p.size.into())
+            .implies(
+                "pizza.extra_cheese",
+                ("extra_cheese", |p| p.extra_cheese),
+                ("cheese", |p| p.cheese),
+            )
+    }
+
+    #[test]
+    fn every_violation_is_reported() {
+        let pizza = Pizza {
+            size: 50,
+            pineapple: true,
+            anchovies: true,
+            extra_cheese: true,
+            cheese: false,
+        };
+        let Err(violations) = rules().check(&pizza) else {
+            panic!("Expected violations");
+        };
+        assert_eq!(
+            vec![
+                Violation::Exclusive {
+                    code: "pizza.toppings",
+                    options: vec!["pineapple", "anchovies"],
+                },
+                Violation::OutOfRange {
+                    code: "pizza.size",
+                    option: "size",
+                    value: 50,
+                    range: 20..=40,
+                },
+                Violation::Implies {
+                    code: "pizza.extra_cheese",
+                    option: "extra_cheese",
+                    requires: "cheese",
+                },
+            ],
+            violations.0
+        );
+        assert_eq!(vec!["extra_cheese", "cheese"], violations.0[2].options());
+        assert_eq!(
+            "3 rule(s) violated\n  [pizza.toppings] pineapple, anchovies cannot be combined\n  \
+             [pizza.size] size must be between 20 and 40, got 50\n  \
+             [pizza.extra_cheese] extra_cheese requires cheese",
+            violations.to_string()
+        );
+    }
+
+    #[test]
+    fn satisfied_rules_pass() {
+        let pizza = Pizza {
+            size: 20,
+            pineapple: true,
+            anchovies: false,
+            extra_cheese: false,
+            cheese: false,
+        };
+        assert_eq!(Ok(()), rules().check(&pizza));
+
+        let small = Pizza { size: 19, ..pizza };
+        let violations = rules().check(&small).expect_err("Expected violations");
+        assert!(violations.contains("pizza.size"));
+        assert!(!violations.contains("pizza.toppings"));
+    }
+}
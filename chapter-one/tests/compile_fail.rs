@@ -0,0 +1,8 @@
+/// burger::BurgerBuilder 承诺缺少必需值、素食加培根时无法编译，这些承诺只能通过编译失败来测试
+/// trybuild 编译 tests/ui 中的每个文件，检查编译失败并且错误信息和旁边的 .stderr 文件一致
+/// 修改错误信息之后用 `TRYBUILD=overwrite cargo test --test compile_fail` 重新生成 .stderr
+#[test]
+fn typestate_builder() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,5 @@
+use chapter_one::burger::BurgerBuilder;
+
+fn main() {
+    BurgerBuilder::new().patty_count(2).cheese(true).build();
+}
@@ -0,0 +1,5 @@
+use chapter_one::burger::{Bun, BurgerBuilder};
+
+fn main() {
+    BurgerBuilder::new().bun(Bun::Sesame).build();
+}
@@ -0,0 +1,10 @@
+use chapter_one::burger::{Bun, BurgerBuilder};
+
+fn main() {
+    BurgerBuilder::new()
+        .patty_count(1)
+        .bun(Bun::Sesame)
+        .vegetarian()
+        .bacon()
+        .build();
+}
@@ -0,0 +1,65 @@
+use chapter_one::cli::{self, Arg, Command};
+use chapter_seven::kitchen::{Kitchen, OrderStream};
+
+/// 用种子生成一串订单，交给几个厨师线程制作，最后打印延迟统计
+///
+/// 试试：
+/// * kitchen --seed 7
+/// * kitchen --cooks 1 --orders 20，厨师不够时 Low 订单的延迟会明显变长
+fn main() {
+    let matches = Command::new("kitchen")
+        .about("Simulates a burger kitchen")
+        .arg(
+            Arg::option("seed")
+                .short('s')
+                .default_value("42")
+                .help("Seed for the order stream, the same seed gives the same orders"),
+        )
+        .arg(
+            Arg::option("orders")
+                .short('n')
+                .default_value("30")
+                .help("Number of orders"),
+        )
+        .arg(
+            Arg::option("cooks")
+                .short('c')
+                .default_value("3")
+                .help("Number of cook threads"),
+        )
+        .parse_env();
+    let get = |name| -> u64 {
+        matches
+            .get(name)
+            .unwrap_or_else(|e| cli::exit(&e))
+            .expect("Every option has a default value")
+    };
+    let (seed, count, cooks) = (get("seed"), get("orders"), get("cooks"));
+    if cooks == 0 {
+        eprintln!("error: --cooks must be at least 1");
+        std::process::exit(2);
+    }
+
+    let orders: Vec<_> = OrderStream::new(seed).take(count as usize).collect();
+    for order in &orders {
+        println!(
+            "#{:<3} {:<6} {:?} bun, patties: {}, prep {:?}",
+            order.id,
+            format!("{:?}", order.priority),
+            order.burger.bun,
+            order.burger.patty_count,
+            order.prep_time()
+        );
+    }
+
+    let report = Kitchen::new(cooks as usize).run(orders);
+    println!();
+    for served in &report.served {
+        println!(
+            "cook {} served #{} ({:?}) after waiting {:.1?}",
+            served.cook, served.id, served.priority, served.wait
+        );
+    }
+    println!();
+    println!("{}", report);
+}
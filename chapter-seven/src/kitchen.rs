@@ -0,0 +1,503 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    fmt::{self, Display},
+    sync::{Condvar, Mutex, PoisonError, mpsc::channel},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chapter_one::burger::{Bun, Burger, BurgerBuilder};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// 订单的优先级，比较时 Rush 最大
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    Normal,
+    /// 加急订单，只要队列中还有它就不会先做其它订单
+    Rush,
+}
+
+impl Priority {
+    pub const ALL: [Priority; 3] = [Priority::Rush, Priority::Normal, Priority::Low];
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub id: u64,
+    pub priority: Priority,
+    pub burger: Burger,
+    /// 距离上一个订单的时间
+    pub after: Duration,
+}
+
+impl Order {
+    /// 每个肉饼、奶酪和培根都需要额外的时间
+    pub fn prep_time(&self) -> Duration {
+        let burger = &self.burger;
+        let mut millis = 10 + 8 * u64::from(burger.patty_count);
+        if burger.cheese {
+            millis += 4;
+        }
+        if burger.bacon {
+            millis += 6;
+        }
+        Duration::from_millis(millis)
+    }
+}
+
+/// 无限的订单流，用 `.take(n)` 取出需要的数量
+///
+/// 随机数生成器是用种子初始化的 ChaCha8Rng，而不是 channel.rs 中的 rand::rng()，所以结果可以重现
+/// 不使用 StdRng：rand 不保证它的算法在不同版本和平台上保持不变，升级 rand 之后同一个种子可能得到不同的订单
+pub struct OrderStream {
+    rng: ChaCha8Rng,
+    next_id: u64,
+}
+
+impl OrderStream {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            next_id: 1,
+        }
+    }
+}
+
+impl Iterator for OrderStream {
+    type Item = Order;
+
+    fn next(&mut self) -> Option<Order> {
+        let rng = &mut self.rng;
+        let bun = match rng.random_range(0..3) {
+            0 => Bun::Sesame,
+            1 => Bun::Brioche,
+            _ => Bun::Lettuce,
+        };
+        let builder = BurgerBuilder::new()
+            .patty_count(rng.random_range(1..=3))
+            .bun(bun)
+            .cheese(rng.random_bool(0.5))
+            .salad(rng.random_bool(0.8));
+        // 素食和培根是 builder 的类型状态，每个分支得到的是不同类型的 builder，但是 build 的结果都是 Burger
+        let burger = match rng.random_range(0..4) {
+            0 => builder.vegetarian().build(),
+            1 => builder.bacon().build(),
+            _ => builder.build(),
+        };
+        let priority = match rng.random_range(0..10) {
+            0 => Priority::Rush,
+            1..=6 => Priority::Normal,
+            _ => Priority::Low,
+        };
+        let order = Order {
+            id: self.next_id,
+            priority,
+            burger,
+            after: Duration::from_millis(rng.random_range(0..12)),
+        };
+        self.next_id += 1;
+        Some(order)
+    }
+}
+
+/// 按优先级排序的订单队列，同样优先级的订单先来先做
+///
+/// BinaryHeap 是最大堆，Mutex 保护它，Condvar 让空闲的厨师睡眠，直到有新订单或者队列关闭
+pub struct OrderQueue {
+    state: Mutex<QueueState>,
+    ready: Condvar,
+}
+
+struct QueueState {
+    heap: BinaryHeap<Queued>,
+    closed: bool,
+    sequence: u64,
+}
+
+/// 一个等待制作的订单
+pub struct Queued {
+    pub order: Order,
+    /// 进入队列的时间，用于计算延迟
+    pub placed: Instant,
+    sequence: u64,
+}
+
+impl Ord for Queued {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // 序号小的先进入队列，在最大堆中应该更“大”
+        self.order
+            .priority
+            .cmp(&other.order.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Queued {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Queued {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Queued {}
+
+impl OrderQueue {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(QueueState {
+                heap: BinaryHeap::new(),
+                closed: false,
+                sequence: 0,
+            }),
+            ready: Condvar::new(),
+        }
+    }
+
+    pub fn push(&self, order: Order) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        assert!(!state.closed, "Cannot push to a closed queue");
+        let sequence = state.sequence;
+        state.sequence += 1;
+        state.heap.push(Queued {
+            order,
+            placed: Instant::now(),
+            sequence,
+        });
+        self.ready.notify_one();
+    }
+
+    /// 不会再有新订单，等待中的厨师做完剩下的订单后 pop 返回 None
+    pub fn close(&self) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.closed = true;
+        self.ready.notify_all();
+    }
+
+    /// 取出最优先的订单，队列为空时阻塞，队列关闭并且为空时返回 None
+    pub fn pop(&self) -> Option<Queued> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            if let Some(queued) = state.heap.pop() {
+                return Some(queued);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self
+                .ready
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+    }
+}
+
+impl Default for OrderQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 一个做好的订单
+#[derive(Debug, Clone)]
+pub struct Served {
+    pub id: u64,
+    pub priority: Priority,
+    /// 做这个订单的厨师，从 0 开始
+    pub cook: usize,
+    /// 在队列中等待的时间
+    pub wait: Duration,
+    /// 从进入队列到做好的时间
+    pub latency: Duration,
+}
+
+/// 一个模拟的厨房，把前面几章的内容连起来：
+/// * 订单中的汉堡由 chapter_one::burger::BurgerBuilder 构建
+/// * 订单进入 [`OrderQueue`]，和 vecdeque.rs 中的 orders 一样是一个队列，但是加急的订单排在前面
+/// * Kitchen 启动 N 个厨师线程，从队列中取订单，按照汉堡的复杂程度花一段时间制作，
+///   做好之后像 channel.rs 中一样通过管道把结果发回主线程
+/// * [`Report`] 统计每种优先级从下单到做好的延迟
+///
+/// 订单由 [`OrderStream`] 根据种子生成，同一个种子总是得到同样的订单
+/// 延迟是真实测量的时间，取决于线程调度，所以每次运行会略有不同
+pub struct Kitchen {
+    cooks: usize,
+}
+
+impl Kitchen {
+    pub fn new(cooks: usize) -> Self {
+        assert!(cooks > 0, "A kitchen needs at least one cook");
+        Self { cooks }
+    }
+
+    /// 按照订单的 after 依次下单，所有订单做完后返回报告
+    ///
+    /// thread::scope 中的线程可以借用 queue，不需要 Arc，作用域结束时所有厨师线程都已经结束
+    pub fn run<I>(&self, orders: I) -> Report
+    where
+        I: IntoIterator<Item = Order>,
+    {
+        let queue = OrderQueue::new();
+        let (tx, rx) = channel();
+        let start = Instant::now();
+        thread::scope(|scope| {
+            for cook in 0..self.cooks {
+                let tx = tx.clone();
+                let queue = &queue;
+                scope.spawn(move || {
+                    while let Some(queued) = queue.pop() {
+                        let wait = queued.placed.elapsed();
+                        thread::sleep(queued.order.prep_time());
+                        let served = Served {
+                            id: queued.order.id,
+                            priority: queued.order.priority,
+                            cook,
+                            wait,
+                            latency: queued.placed.elapsed(),
+                        };
+                        tx.send(served).expect("Disconnected from the kitchen");
+                    }
+                });
+            }
+
+            for order in orders {
+                thread::sleep(order.after);
+                queue.push(order);
+            }
+            queue.close();
+        });
+        // 厨师手中的 tx 已经随线程结束被释放，释放最后一个之后 rx 的遍历会停止
+        drop(tx);
+        Report {
+            served: rx.iter().collect(),
+            elapsed: start.elapsed(),
+            cooks: self.cooks,
+        }
+    }
+}
+
+/// 一组延迟的统计
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    pub count: usize,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p95: Duration,
+    pub max: Duration,
+}
+
+impl Stats {
+    /// 没有数据时返回 None
+    pub fn new(latencies: &[Duration]) -> Option<Self> {
+        let mut sorted = latencies.to_vec();
+        sorted.sort();
+        let max = *sorted.last()?;
+        // nearest-rank 百分位数：至少有 p 比例的数据不大于它
+        let percentile = |p: f64| {
+            let rank = (p * sorted.len() as f64).ceil() as usize;
+            sorted[rank.max(1) - 1]
+        };
+        Some(Self {
+            count: sorted.len(),
+            mean: sorted.iter().sum::<Duration>() / sorted.len() as u32,
+            p50: percentile(0.5),
+            p95: percentile(0.95),
+            max,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    /// 按做好的顺序
+    pub served: Vec<Served>,
+    pub elapsed: Duration,
+    pub cooks: usize,
+}
+
+impl Report {
+    /// priority 为 None 时统计所有订单
+    pub fn stats(&self, priority: Option<Priority>) -> Option<Stats> {
+        let latencies: Vec<_> = self
+            .served
+            .iter()
+            .filter(|served| priority.is_none_or(|priority| served.priority == priority))
+            .map(|served| served.latency)
+            .collect();
+        Stats::new(&latencies)
+    }
+
+    /// 每个厨师做了多少个订单
+    pub fn per_cook(&self) -> Vec<usize> {
+        let mut counts = vec![0; self.cooks];
+        for served in &self.served {
+            counts[served.cook] += 1;
+        }
+        counts
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} orders served by {} cooks in {:?}",
+            self.served.len(),
+            self.cooks,
+            self.elapsed
+        )?;
+        writeln!(
+            f,
+            "{:<8} {:>5} {:>10} {:>10} {:>10} {:>10}",
+            "priority", "count", "mean", "p50", "p95", "max"
+        )?;
+        let rows = Priority::ALL
+            .into_iter()
+            .map(|priority| (format!("{:?}", priority), self.stats(Some(priority))))
+            .chain([("All".to_string(), self.stats(None))]);
+        for (name, stats) in rows {
+            let Some(stats) = stats else {
+                continue;
+            };
+            writeln!(
+                f,
+                "{:<8} {:>5} {:>10} {:>10} {:>10} {:>10}",
+                name,
+                stats.count,
+                format!("{:.1?}", stats.mean),
+                format!("{:.1?}", stats.p50),
+                format!("{:.1?}", stats.p95),
+                format!("{:.1?}", stats.max)
+            )?;
+        }
+        write!(f, "orders per cook: {:?}", self.per_cook())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_stream_is_reproducible() {
+        let first: Vec<_> = OrderStream::new(7).take(20).collect();
+        let second: Vec<_> = OrderStream::new(7).take(20).collect();
+        let other: Vec<_> = OrderStream::new(8).take(20).collect();
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        // 固定前几个订单，这样换了随机数算法或者改变了抽取的顺序时测试会失败
+        let order = |id, priority, burger, after| Order {
+            id,
+            priority,
+            burger,
+            after: Duration::from_millis(after),
+        };
+        let burger = |patty_count, bun, vegetarian, cheese, bacon, salad| Burger {
+            patty_count,
+            bun,
+            vegetarian,
+            cheese,
+            bacon,
+            salad,
+        };
+        assert_eq!(
+            vec![
+                order(
+                    1,
+                    Priority::Low,
+                    burger(1, Bun::Sesame, true, true, false, true),
+                    7
+                ),
+                order(
+                    2,
+                    Priority::Normal,
+                    burger(2, Bun::Lettuce, false, true, true, false),
+                    11
+                ),
+                order(
+                    3,
+                    Priority::Normal,
+                    burger(1, Bun::Brioche, false, true, false, true),
+                    5
+                ),
+            ],
+            first[..3]
+        );
+        assert!(
+            first
+                .iter()
+                .all(|order| !(order.burger.vegetarian && order.burger.bacon))
+        );
+        assert_eq!(
+            (1..=20).collect::<Vec<_>>(),
+            first.iter().map(|order| order.id).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn queue_serves_rush_orders_first() {
+        let queue = OrderQueue::new();
+        let mut orders = OrderStream::new(1);
+        for (id, priority) in [
+            (1, Priority::Low),
+            (2, Priority::Normal),
+            (3, Priority::Rush),
+            (4, Priority::Normal),
+            (5, Priority::Rush),
+        ] {
+            let order = orders.next().expect("The stream is infinite");
+            queue.push(Order {
+                id,
+                priority,
+                ..order
+            });
+        }
+        queue.close();
+        let ids: Vec<_> = std::iter::from_fn(|| queue.pop())
+            .map(|queued| queued.order.id)
+            .collect();
+        assert_eq!(vec![3, 5, 2, 4, 1], ids);
+    }
+
+    #[test]
+    fn kitchen_serves_every_order_once() {
+        let orders: Vec<_> = OrderStream::new(42)
+            .take(12)
+            .map(|order| Order {
+                after: Duration::ZERO,
+                ..order
+            })
+            .collect();
+        let report = Kitchen::new(3).run(orders);
+        let mut ids: Vec<_> = report.served.iter().map(|served| served.id).collect();
+        ids.sort();
+        assert_eq!((1..=12).collect::<Vec<_>>(), ids);
+        assert_eq!(12, report.per_cook().iter().sum::<usize>());
+        let stats = report.stats(None).expect("Orders were served");
+        assert_eq!(12, stats.count);
+        assert!(stats.p50 <= stats.p95 && stats.p95 <= stats.max);
+    }
+
+    #[test]
+    fn stats_use_nearest_rank_percentiles() {
+        let latencies: Vec<_> = (1..=20).map(Duration::from_millis).collect();
+        assert_eq!(
+            Some(Stats {
+                count: 20,
+                mean: Duration::from_micros(10_500),
+                p50: Duration::from_millis(10),
+                p95: Duration::from_millis(19),
+                max: Duration::from_millis(20),
+            }),
+            Stats::new(&latencies)
+        );
+        assert_eq!(None, Stats::new(&[]));
+    }
+}
@@ -0,0 +1,38 @@
+use std::{env, time::Duration};
+
+use chapter_three::watcher::{Event, Watcher};
+
+/// 平台相关的文件通知API（Linux的inotify，macOS的FSEvents，Windows的ReadDirectoryChangesW）各不相同，notify crate封装了它们
+/// 如果只需要“过一会儿发现有新文件”，轮询是最简单也最可移植的做法：用 WalkDir 定期拍快照，比较前后两次快照的差异
+///
+/// 监视器在后台线程中运行，事件通过 mpsc 管道发送给主线程。和 channel.rs 中一样，遍历 rx 会一直阻塞，直到发送端被丢弃
+///
+/// 用法：watch [DIR] [GLOB...]，例如 `watch . "**/*.rs"`
+fn main() {
+    let mut args = env::args().skip(1);
+    let root = args.next().unwrap_or_else(|| ".".to_string());
+
+    let mut watcher = Watcher::new(&root)
+        .interval(Duration::from_millis(250))
+        .debounce(Duration::from_millis(500))
+        .exclude("target/**")
+        .expect("Failed to parse exclude pattern");
+    for pattern in args {
+        watcher = watcher
+            .include(&pattern)
+            .expect("Failed to parse glob pattern");
+    }
+
+    println!("Watching '{}' for changes, press Ctrl C to stop", root);
+    let (rx, _handle) = watcher.spawn();
+    for event in rx {
+        match event {
+            Event::Created(path) => println!("created:  {}", path.display()),
+            Event::Modified(path) => println!("modified: {}", path.display()),
+            Event::Removed(path) => println!("removed:  {}", path.display()),
+            Event::Renamed { from, to } => {
+                println!("renamed:  {} -> {}", from.display(), to.display())
+            }
+        }
+    }
+}
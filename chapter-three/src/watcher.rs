@@ -0,0 +1,369 @@
+use std::{
+    collections::HashMap,
+    fs::Metadata,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, channel},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use glob::{Pattern, PatternError};
+use walkdir::WalkDir;
+
+/// 基于轮询的目录监视器，不依赖任何平台相关的通知API（inotify、FSEvents等）
+///
+/// 原理很简单：每隔 `interval` 用 WalkDir 扫描一次目录，得到一个 路径 -> 文件状态 的快照，和上一次发出的快照比较，差异就是事件
+///
+/// 防抖（debounce）：写一个大文件时可能连续几次扫描都看到它在变化，我们只在目录**安静**了 `debounce` 这么久之后才比较并发出事件，
+/// 这样一次写入只会产生一个事件，而不是 Created 后面跟着一串 Modified
+/// 但是一直在写的文件（例如日志）会让目录永远不安静，所以从第一次看到变化开始最多等待 `max_wait`，到时间就照常发出事件
+///
+/// 重命名检测：同一轮中如果一个路径消失、另一个路径出现，并且大小和修改时间相同（Unix上还要求inode相同），就把这两个事件合并为 Renamed
+/// 只比较inode是不够的：删除一个文件之后新建的文件可能重用同一个inode
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Created(PathBuf),
+    Modified(PathBuf),
+    Removed(PathBuf),
+    Renamed { from: PathBuf, to: PathBuf },
+}
+
+/// 快照中记录的文件状态，只要其中一个成员变化就认为文件被修改了
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileState {
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    pub file_id: Option<u64>,
+}
+
+impl FileState {
+    fn from_metadata(metadata: &Metadata) -> Self {
+        Self {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            file_id: file_id(metadata),
+        }
+    }
+
+    /// 重命名不会改变大小和修改时间，两边都有inode时还要求inode相同
+    fn same_file(&self, other: &FileState) -> bool {
+        let same_id = match (self.file_id, other.file_id) {
+            (Some(a), Some(b)) => a == b,
+            _ => true,
+        };
+        same_id && self.len == other.len && self.modified == other.modified
+    }
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+pub type Snapshot = HashMap<PathBuf, FileState>;
+
+/// 监视器的builder，和 WalkDir 一样先配置，再调用 spawn 开始工作
+pub struct Watcher {
+    root: PathBuf,
+    interval: Duration,
+    debounce: Duration,
+    max_wait: Duration,
+    max_depth: usize,
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl Watcher {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            interval: Duration::from_millis(500),
+            debounce: Duration::from_millis(200),
+            max_wait: Duration::from_secs(2),
+            max_depth: usize::MAX,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// 设置为 Duration::ZERO 可以关闭防抖，每次扫描到的变化都会立刻发出
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// 变化一直持续时，从第一次看到变化到发出事件最多等待多久
+    pub fn max_wait(mut self, max_wait: Duration) -> Self {
+        self.max_wait = max_wait;
+        self
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// 只关心匹配这个glob模式的文件，模式是相对于根目录进行匹配的，例如 `**/*.rs`
+    /// 没有设置任何include模式时，所有文件都会被监视
+    pub fn include(mut self, pattern: &str) -> Result<Self, PatternError> {
+        self.include.push(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    /// 忽略匹配这个glob模式的文件，优先级高于 include
+    pub fn exclude(mut self, pattern: &str) -> Result<Self, PatternError> {
+        self.exclude.push(Pattern::new(pattern)?);
+        Ok(self)
+    }
+
+    fn is_watched(&self, path: &Path) -> bool {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let included =
+            self.include.is_empty() || self.include.iter().any(|p| p.matches_path(relative));
+        included && !self.exclude.iter().any(|p| p.matches_path(relative))
+    }
+
+    /// 扫描一次目录，无法访问的条目会被直接跳过
+    pub fn scan(&self) -> Snapshot {
+        WalkDir::new(&self.root)
+            .max_depth(self.max_depth)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| self.is_watched(entry.path()))
+            .filter_map(|entry| {
+                let state = FileState::from_metadata(&entry.metadata().ok()?);
+                Some((entry.into_path(), state))
+            })
+            .collect()
+    }
+
+    /// 在后台线程中开始轮询，事件通过管道发送
+    ///
+    /// 第一次扫描的结果作为基准，不会产生事件。当接收端被丢弃或者调用了 WatchHandle::stop 时线程退出
+    /// 基准在返回之前就已经扫描完成，所以 spawn 返回之后发生的变化一定会被报告
+    pub fn spawn(self) -> (Receiver<Event>, WatchHandle) {
+        let (tx, rx) = channel();
+        let running = Arc::new(AtomicBool::new(true));
+        let mut emitted = self.scan();
+        let thread = {
+            let running = Arc::clone(&running);
+            thread::spawn(move || {
+                let mut latest = emitted.clone();
+                let mut last_change = Instant::now();
+                // 第一次看到还没有发出的变化的时间
+                let mut pending_since: Option<Instant> = None;
+                while running.load(Ordering::SeqCst) {
+                    thread::sleep(self.interval);
+                    let current = self.scan();
+                    if current != latest {
+                        latest = current;
+                        last_change = Instant::now();
+                        pending_since.get_or_insert(last_change);
+                    }
+                    if latest == emitted {
+                        // 变化又被撤销了，例如文件被创建之后马上被删除
+                        pending_since = None;
+                        continue;
+                    }
+                    let waited_enough =
+                        pending_since.is_some_and(|since| since.elapsed() >= self.max_wait);
+                    if last_change.elapsed() < self.debounce && !waited_enough {
+                        continue;
+                    }
+                    pending_since = None;
+                    for event in diff(&emitted, &latest) {
+                        if tx.send(event).is_err() {
+                            return;
+                        }
+                    }
+                    emitted = latest.clone();
+                }
+            })
+        };
+        (
+            rx,
+            WatchHandle {
+                running,
+                thread: Some(thread),
+            },
+        )
+    }
+}
+
+/// 后台轮询线程的handle，被丢弃时会停止线程
+pub struct WatchHandle {
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl WatchHandle {
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        self.running.store(false, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            thread.join().expect("The watcher thread panicked");
+        }
+    }
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// 比较两个快照，返回按路径排序的事件列表
+pub fn diff(old: &Snapshot, new: &Snapshot) -> Vec<Event> {
+    let mut removed: Vec<_> = old.keys().filter(|p| !new.contains_key(*p)).collect();
+    let mut created: Vec<_> = new.keys().filter(|p| !old.contains_key(*p)).collect();
+    removed.sort();
+    created.sort();
+
+    let mut events = Vec::new();
+    let mut renamed_to = Vec::new();
+    removed.retain(|from| {
+        let state = &old[*from];
+        let to = created
+            .iter()
+            .find(|to| !renamed_to.contains(*to) && new[**to].same_file(state));
+        match to {
+            Some(to) => {
+                renamed_to.push(*to);
+                events.push(Event::Renamed {
+                    from: from.to_path_buf(),
+                    to: to.to_path_buf(),
+                });
+                false
+            }
+            None => true,
+        }
+    });
+    created.retain(|path| !renamed_to.contains(path));
+
+    let mut modified: Vec<_> = new
+        .iter()
+        .filter(|(path, state)| old.get(*path).is_some_and(|old| old != *state))
+        .map(|(path, _)| path)
+        .collect();
+    modified.sort();
+
+    events.extend(created.into_iter().map(|p| Event::Created(p.clone())));
+    events.extend(modified.into_iter().map(|p| Event::Modified(p.clone())));
+    events.extend(removed.into_iter().map(|p| Event::Removed(p.clone())));
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(len: u64, file_id: u64) -> FileState {
+        FileState {
+            len,
+            modified: None,
+            file_id: Some(file_id),
+        }
+    }
+
+    #[test]
+    fn detects_created_modified_removed() {
+        let old = Snapshot::from([
+            (PathBuf::from("a.txt"), state(1, 1)),
+            (PathBuf::from("b.txt"), state(2, 2)),
+        ]);
+        let new = Snapshot::from([
+            (PathBuf::from("a.txt"), state(10, 1)),
+            (PathBuf::from("c.txt"), state(3, 3)),
+        ]);
+        assert_eq!(
+            vec![
+                Event::Created(PathBuf::from("c.txt")),
+                Event::Modified(PathBuf::from("a.txt")),
+                Event::Removed(PathBuf::from("b.txt")),
+            ],
+            diff(&old, &new)
+        )
+    }
+
+    #[test]
+    fn detects_rename_by_file_id() {
+        let old = Snapshot::from([(PathBuf::from("old.txt"), state(5, 42))]);
+        let new = Snapshot::from([(PathBuf::from("new.txt"), state(5, 42))]);
+        assert_eq!(
+            vec![Event::Renamed {
+                from: PathBuf::from("old.txt"),
+                to: PathBuf::from("new.txt"),
+            }],
+            diff(&old, &new)
+        )
+    }
+
+    #[test]
+    fn reused_file_id_is_not_a_rename() {
+        let old = Snapshot::from([(PathBuf::from("old.txt"), state(5, 42))]);
+        let new = Snapshot::from([(PathBuf::from("new.txt"), state(7, 42))]);
+        assert_eq!(
+            vec![
+                Event::Created(PathBuf::from("new.txt")),
+                Event::Removed(PathBuf::from("old.txt")),
+            ],
+            diff(&old, &new)
+        )
+    }
+
+    #[test]
+    fn continuous_writes_are_reported_after_max_wait() {
+        let root = std::env::temp_dir().join(format!("watcher-max-wait-{}", std::process::id()));
+        std::fs::create_dir_all(&root).expect("Failed to create test directory");
+        let (events, handle) = Watcher::new(&root)
+            .interval(Duration::from_millis(10))
+            .debounce(Duration::from_secs(60))
+            .max_wait(Duration::from_millis(100))
+            .spawn();
+
+        let file = root.join("log.txt");
+        let start = Instant::now();
+        let mut event = None;
+        for i in 0..200 {
+            std::fs::write(&file, "x".repeat(i + 1)).expect("Failed to write test file");
+            if let Ok(received) = events.recv_timeout(Duration::from_millis(10)) {
+                event = Some(received);
+                break;
+            }
+        }
+        handle.stop();
+        std::fs::remove_dir_all(&root).expect("Failed to remove test directory");
+        assert_eq!(Some(Event::Created(file)), event);
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn filters_relative_to_root() {
+        let watcher = Watcher::new("/data")
+            .include("**/*.rs")
+            .and_then(|w| w.exclude("target/**"))
+            .expect("Failed to build watcher");
+        assert!(watcher.is_watched(Path::new("/data/src/main.rs")));
+        assert!(!watcher.is_watched(Path::new("/data/README.md")));
+        assert!(!watcher.is_watched(Path::new("/data/target/debug/build.rs")));
+    }
+}